@@ -64,7 +64,7 @@ fn read_on_disk(c: &mut Criterion) {
         .unwrap();
 
         for _ in 0..(8 * 1024) {
-            writer.write(&[42]).unwrap();
+            writer.write_all(&[42]).unwrap();
         }
 
         writer.flush().unwrap();
@@ -111,7 +111,7 @@ fn write_on_disk(c: &mut Criterion) {
             .unwrap();
 
             for _ in 0..(8 * 1024) {
-                writer.write(&[42]).unwrap();
+                writer.write_all(&[42]).unwrap();
             }
         })
     });
@@ -122,7 +122,7 @@ fn write_on_disk(c: &mut Criterion) {
             let mut writer = bzip2::write::BzEncoder::new(wfile, bzip2::Compression::fast());
 
             for _ in 0..(8 * 1024) {
-                writer.write(&[42]).unwrap();
+                writer.write_all(&[42]).unwrap();
             }
         })
     });
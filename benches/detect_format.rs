@@ -2,9 +2,11 @@ mod share;
 
 use share::{BASIC_FILE, BGZIP_FILE, BZIP_FILE, GZIP_FILE, LZMA_FILE};
 
-use niffler;
+use std::io::Read;
 
-use codspeed_criterion_compat::{black_box, criterion_group, criterion_main, Criterion};
+use codspeed_criterion_compat::{
+    black_box, criterion_group, criterion_main, Criterion, Throughput,
+};
 
 fn detect_format(c: &mut Criterion) {
     let mut g = c.benchmark_group("Format detection");
@@ -31,5 +33,23 @@ fn detect_format(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, detect_format);
+fn read_uncompressed(c: &mut Criterion) {
+    let mut g = c.benchmark_group("Read after detection");
+
+    // detection peeks in the reader buffer, reads on uncompressed files go straight to it
+    let data = vec![b'A'; 16 * 1024 * 1024];
+    g.throughput(Throughput::Bytes(data.len() as u64));
+
+    g.bench_function("unflate 16MiB", |b| {
+        b.iter(|| {
+            let (mut reader, _) = niffler::get_reader(Box::new(&data[..])).unwrap();
+            let mut buffer = [0u8; 8192];
+            while reader.read(&mut buffer).unwrap() != 0 {
+                black_box(&buffer);
+            }
+        })
+    });
+}
+
+criterion_group!(benches, detect_format, read_uncompressed);
 criterion_main!(benches);
@@ -61,7 +61,7 @@ fn read_on_disk(c: &mut Criterion) {
         .unwrap();
 
         for _ in 0..(8 * 1024) {
-            writer.write(&[42]).unwrap();
+            writer.write_all(&[42]).unwrap();
         }
 
         writer.flush().unwrap();
@@ -108,7 +108,7 @@ fn write_on_disk(c: &mut Criterion) {
             .unwrap();
 
             for _ in 0..(8 * 1024) {
-                writer.write(&[42]).unwrap();
+                writer.write_all(&[42]).unwrap();
             }
         })
     });
@@ -119,7 +119,7 @@ fn write_on_disk(c: &mut Criterion) {
             let mut writer = liblzma::write::XzEncoder::new(wfile, 1);
 
             for _ in 0..(8 * 1024) {
-                writer.write(&[42]).unwrap();
+                writer.write_all(&[42]).unwrap();
             }
         })
     });
@@ -0,0 +1,70 @@
+//! Encode brotli streams, [brotli::CompressorWriter::into_inner] ignores the errors raised while
+//! the end of the stream is written.
+
+/* standard use */
+use std::io;
+use std::io::Write;
+
+/// Window size, as a power of two, used by the `brotli` command line tool.
+const LGWIN: u32 = 22;
+
+/// A brotli encoder whose [finish](BrotliEncoder::finish) reports write errors.
+pub(crate) struct BrotliEncoder<W: Write> {
+    encoder: brotli::CompressorWriter<Recorder<W>>,
+}
+
+/// Keep the first error of the underlying stream, the brotli encoder drops it at the end.
+struct Recorder<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> BrotliEncoder<W> {
+    pub(crate) fn new(out: W, quality: u32) -> Self {
+        BrotliEncoder {
+            encoder: brotli::CompressorWriter::new(
+                Recorder { out, error: None },
+                crate::basic::compression::BUFFER_SIZE,
+                quality,
+                LGWIN,
+            ),
+        }
+    }
+
+    /// Write the end of the compressed stream and return the underlying stream.
+    pub(crate) fn finish(self) -> io::Result<W> {
+        let recorder = self.encoder.into_inner();
+
+        match recorder.error {
+            Some(error) => Err(error),
+            None => Ok(recorder.out),
+        }
+    }
+}
+
+impl<W: Write> Write for BrotliEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+impl<W: Write> Write for Recorder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.out.write(buf) {
+            Err(error) if error.kind() != io::ErrorKind::Interrupted => {
+                let kind = error.kind();
+                self.error.get_or_insert(error);
+                Err(kind.into())
+            }
+            result => result,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -1,17 +1,151 @@
+/* standard use */
+use std::path::{Path, PathBuf};
+
+/* crates use */
 use thiserror::Error;
 
+/* project use */
+use crate::format::Format;
+use crate::level::Level;
+
 /// Errors that can be generated by niffler
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Error)]
 pub enum Error {
-    /// This feature is disabled, you can enable it in cargo dependencies sections.
-    #[error("Feature disabled, enabled it during compilation")]
-    FeatureDisabled,
+    /// Support of this format is disabled, you can enable it in cargo dependencies sections.
+    #[error("{0:?} support is disabled, enable its niffler feature during compilation")]
+    FeatureDisabled(Format),
 
-    /// This file is shorter than five bytes, it's probably not a compression file or corrupted.
-    #[error("File is too short, less than five bytes")]
+    /// This file starts with a magic number but is too short to be a compressed file, it's probably
+    /// truncated.
+    #[error("File is too short, compression header is truncated")]
     FileTooShort,
 
-    #[error("I/O error")]
-    IOError(#[from] std::io::Error),
+    /// This format can't be handled by the called function, e.g. plain Gzip can't be seeked and
+    /// UnixCompress can't be written.
+    #[error("{0:?} format isn't supported by this function")]
+    UnsupportedFormat(Format),
+
+    /// The compressed data is invalid at this offset of the compressed stream.
+    #[error("{format:?} data is corrupt at compressed offset {offset}")]
+    Corrupt { format: Format, offset: u64 },
+
+    /// The compressed stream ends before its end marker.
+    #[error("{format:?} stream is truncated")]
+    Truncated { format: Format },
+
+    /// This compression level isn't accepted by this format.
+    #[error("{level:?} isn't a valid compression level for {format:?}")]
+    InvalidLevel { format: Format, level: Level },
+
+    /// This block or frame size isn't accepted by this format.
+    #[error("{size} isn't a valid block size for {format:?}")]
+    InvalidBlockSize { format: Format, size: usize },
+
+    /// This region can't be parsed.
+    #[error("invalid region '{0}'")]
+    InvalidRegion(String),
+
+    /// An error raised while opening or creating the file at `path`.
+    #[error("{}: {source}", path.display())]
+    WithPath {
+        path: PathBuf,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("I/O error: {0}")]
+    IOError(#[source] std::io::Error),
+}
+
+impl From<std::io::Error> for Error {
+    /// niffler errors raised behind a [Read](std::io::Read) or [Write](std::io::Write) call are
+    /// given back as they were.
+    fn from(error: std::io::Error) -> Self {
+        if error.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            if let Some(Ok(inner)) = error.into_inner().map(|inner| inner.downcast::<Error>()) {
+                return *inner;
+            }
+            unreachable!("the inner error is a niffler::Error");
+        }
+
+        Error::IOError(error)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    /// I/O errors are given back as they were, other niffler errors are wrapped.
+    fn from(error: Error) -> Self {
+        match error {
+            Error::IOError(error) => error,
+            error => std::io::Error::new(error.io_kind(), error),
+        }
+    }
+}
+
+impl Error {
+    /// Attach the path of the file this error comes from, errors already holding this path are
+    /// kept as they are.
+    pub(crate) fn with_path(self, path: &Path) -> Self {
+        match self {
+            Error::WithPath {
+                path: ref inner, ..
+            } if inner == path => self,
+            error => Error::WithPath {
+                path: path.to_path_buf(),
+                source: Box::new(error),
+            },
+        }
+    }
+
+    fn io_kind(&self) -> std::io::ErrorKind {
+        match self {
+            Error::FeatureDisabled(_) | Error::UnsupportedFormat(_) => {
+                std::io::ErrorKind::Unsupported
+            }
+            Error::FileTooShort | Error::Truncated { .. } => std::io::ErrorKind::UnexpectedEof,
+            Error::Corrupt { .. } => std::io::ErrorKind::InvalidData,
+            Error::InvalidLevel { .. }
+            | Error::InvalidBlockSize { .. }
+            | Error::InvalidRegion(_) => std::io::ErrorKind::InvalidInput,
+            Error::WithPath { source, .. } => source.io_kind(),
+            Error::IOError(error) => error.kind(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn io_roundtrip() {
+        let error = std::io::Error::from(Error::Truncated {
+            format: Format::Gzip,
+        });
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        assert!(matches!(
+            Error::from(error),
+            Error::Truncated {
+                format: Format::Gzip
+            }
+        ));
+
+        let error = Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(
+            std::io::Error::from(error).kind(),
+            std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn display() {
+        let error = Error::FeatureDisabled(Format::Zstd).with_path(Path::new("file.zst"));
+
+        assert_eq!(
+            error.to_string(),
+            "file.zst: Zstd support is disabled, enable its niffler feature during compilation"
+        );
+    }
 }
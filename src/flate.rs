@@ -0,0 +1,94 @@
+//! Decode zlib and raw deflate streams, [flate2::bufread::ZlibDecoder] and
+//! [flate2::bufread::DeflateDecoder] ignore any data following the end of the stream.
+
+/* standard use */
+use std::io;
+use std::io::BufRead;
+
+/// A zlib or raw deflate decoder. Concatenated zlib streams are all read, data following a raw
+/// deflate stream is an error.
+pub(crate) struct FlateDecoder<R: BufRead> {
+    source: R,
+    stream: flate2::Decompress,
+    zlib: bool,
+    ended: bool,
+    multiple: bool,
+}
+
+impl<R: BufRead> FlateDecoder<R> {
+    /// Decode the zlib streams of `source`.
+    pub(crate) fn zlib(source: R) -> Self {
+        Self::new(source, true)
+    }
+
+    /// Decode the raw deflate stream of `source`.
+    pub(crate) fn deflate(source: R) -> Self {
+        Self::new(source, false)
+    }
+
+    fn new(source: R, zlib: bool) -> Self {
+        FlateDecoder {
+            source,
+            stream: flate2::Decompress::new(zlib),
+            zlib,
+            ended: false,
+            multiple: true,
+        }
+    }
+
+    /// Stop at the end of the first stream, the source is left at the end of this stream.
+    pub(crate) fn single_stream(mut self) -> Self {
+        self.multiple = false;
+        self
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.source
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl<R: BufRead> io::Read for FlateDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.ended {
+                if !self.multiple || self.source.fill_buf()?.is_empty() {
+                    return Ok(0);
+                } else if !self.zlib {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "data after the end of the deflate stream",
+                    ));
+                }
+
+                // a zlib header is checked by the decoder
+                self.stream.reset(true);
+                self.ended = false;
+            }
+
+            let input = self.source.fill_buf()?;
+            let eof = input.is_empty();
+            let (before_in, before_out) = (self.stream.total_in(), self.stream.total_out());
+            let status = self
+                .stream
+                .decompress(input, buf, flate2::FlushDecompress::None)?;
+            let consumed = (self.stream.total_in() - before_in) as usize;
+            let read = (self.stream.total_out() - before_out) as usize;
+            self.source.consume(consumed);
+
+            self.ended = status == flate2::Status::StreamEnd;
+            if read > 0 {
+                return Ok(read);
+            } else if eof && !self.ended {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+/// `Format` represent a compression format of a file. Currently Gzip, BGzip, Bzip, Lzma, Zstd, Lz4,
+/// Snappy, Brotli, Zlib, Deflate, UnixCompress or No are supported.
+///
+/// This type is shared by every module, a module return an error for formats it can't handle,
+/// e.g. plain Gzip can't be seeked so [seek](crate::seek) and [seeksend](crate::seeksend) refuse it.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Format {
+    Gzip,
+    BGzip,
+    Bzip,
+    Lzma,
+    Zstd,
+    Lz4,
+    Snappy,
+    /// Brotli has no magic number, it's never detected by content, see
+    /// [get_reader_with_format](crate::get_reader_with_format).
+    Brotli,
+    Zlib,
+    /// Raw deflate streams have no header, they are never detected by content, see
+    /// [get_reader_with_format](crate::get_reader_with_format).
+    Deflate,
+    /// Files written by Unix `compress` (`.Z`), they are decoded but can't be written.
+    UnixCompress,
+    No,
+}
+
+impl Format {
+    /// An alias for Lzma, Format::Lzma == Format::Xz
+    #[allow(non_upper_case_globals)]
+    pub const Xz: Format = Format::Lzma;
+}
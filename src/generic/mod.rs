@@ -0,0 +1,701 @@
+//! Static dispatch API.
+//!
+//! [get_reader] and [get_writer] are generic over the stream and return a [Decoder] or an
+//! [Encoder], an enum over the enabled formats, instead of a boxed trait object. Nothing is
+//! allocated per call and the auto traits of the stream are kept: a `Decoder<R>` is `Send` if `R`
+//! is `Send`, so there is no need for the [send](crate::send) copy of the API. The seekable
+//! formats are in [seek].
+//!
+//! # Example
+//! ```
+//! # fn main() -> Result<(), niffler::Error> {
+//! # #[cfg(feature = "gz")] {
+//! use std::io::{Read, Write};
+//! use niffler::generic::{get_reader, get_writer};
+//!
+//! let mut writer = get_writer(Vec::new(), niffler::Format::Gzip, niffler::Level::Six)?;
+//! writer.write_all(b"I'm compress in gzip\n")?;
+//! let buffer = writer.finish()?;
+//!
+//! let mut reader = get_reader(std::io::Cursor::new(buffer))?;
+//! let handle = std::thread::spawn(move || {
+//!     let mut contents = String::new();
+//!     reader.read_to_string(&mut contents).map(|_| contents)
+//! });
+//!
+//! assert_eq!(handle.join().unwrap()?, "I'm compress in gzip\n");
+//! # }
+//! # Ok(())
+//! # }
+//! ```
+
+/* declare mod */
+pub mod seek;
+
+/* standard use */
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+/* project use */
+use crate::basic::compression::{Format, BUFFER_SIZE};
+use crate::error::Error;
+use crate::level::Level;
+use crate::utils::Peek;
+use crate::zlib_check::{zlib_start, ZLIB_CHECK_SIZE};
+
+/// Run `$body` on the decoder of any variant.
+macro_rules! dispatch {
+    ($inner:expr, $decoder:ident => $body:expr) => {
+        match $inner {
+            #[cfg(feature = "gz")]
+            DecoderInner::Gzip($decoder) => $body,
+            #[cfg(feature = "gz")]
+            DecoderInner::Flate($decoder) => $body,
+            #[cfg(feature = "bgz")]
+            DecoderInner::BGzip($decoder) => $body,
+            #[cfg(feature = "bz2")]
+            DecoderInner::Bzip($decoder) => $body,
+            #[cfg(feature = "lzma")]
+            DecoderInner::Lzma($decoder) => $body,
+            #[cfg(feature = "zstd")]
+            DecoderInner::Zstd($decoder) => $body,
+            #[cfg(feature = "lz4")]
+            DecoderInner::Lz4($decoder) => $body,
+            #[cfg(feature = "snappy")]
+            DecoderInner::Snappy($decoder) => $body,
+            #[cfg(feature = "brotli")]
+            DecoderInner::Brotli($decoder) => $body,
+            DecoderInner::UnixCompress($decoder) => $body,
+            DecoderInner::No($decoder) => $body,
+        }
+    };
+}
+
+/// A decompressed stream returned by [get_reader], it implements [BufRead].
+///
+/// Truncated compressed streams give an error which converts to [Error::Truncated].
+pub struct Decoder<R: io::Read> {
+    inner: DecoderInner<R>,
+    format: Format,
+}
+
+enum DecoderInner<R: io::Read> {
+    #[cfg(feature = "gz")]
+    Gzip(io::BufReader<flate2::bufread::MultiGzDecoder<Peek<R>>>),
+    // zlib and raw deflate
+    #[cfg(feature = "gz")]
+    Flate(io::BufReader<crate::flate::FlateDecoder<Peek<R>>>),
+    #[cfg(feature = "bgz")]
+    BGzip(io::BufReader<crate::seek::bgzf::BgzfDecoder<Peek<R>>>),
+    #[cfg(feature = "bz2")]
+    Bzip(io::BufReader<bzip2::bufread::MultiBzDecoder<Peek<R>>>),
+    #[cfg(feature = "lzma")]
+    Lzma(io::BufReader<liblzma::bufread::XzDecoder<Peek<R>>>),
+    #[cfg(feature = "zstd")]
+    Zstd(io::BufReader<zstd::stream::read::Decoder<'static, Peek<R>>>),
+    #[cfg(feature = "lz4")]
+    Lz4(io::BufReader<crate::lz4_frame::Lz4Decoder<Peek<R>>>),
+    #[cfg(feature = "snappy")]
+    Snappy(io::BufReader<snap::read::FrameDecoder<Peek<R>>>),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<io::BufReader<brotli::Decompressor<Peek<R>>>>),
+    UnixCompress(io::BufReader<crate::unix_compress::LzwDecoder<Peek<R>>>),
+    No(Peek<R>),
+}
+
+impl<R: io::Read> Decoder<R> {
+    /// Compression format of the stream.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Return the underlying stream, data read ahead is lost.
+    pub fn into_inner(self) -> R {
+        self.into_source().into_inner()
+    }
+
+    /// Return the compressed stream with the data the decoder didn't consume.
+    pub(crate) fn into_source(self) -> Peek<R> {
+        match self.inner {
+            #[cfg(feature = "gz")]
+            DecoderInner::Gzip(decoder) => decoder.into_inner().into_inner(),
+            #[cfg(feature = "gz")]
+            DecoderInner::Flate(decoder) => decoder.into_inner().into_inner(),
+            #[cfg(feature = "bgz")]
+            DecoderInner::BGzip(decoder) => decoder.into_inner().into_inner(),
+            #[cfg(feature = "bz2")]
+            DecoderInner::Bzip(decoder) => decoder.into_inner().into_inner(),
+            #[cfg(feature = "lzma")]
+            DecoderInner::Lzma(decoder) => decoder.into_inner().into_inner(),
+            #[cfg(feature = "zstd")]
+            DecoderInner::Zstd(decoder) => decoder.into_inner().finish(),
+            #[cfg(feature = "lz4")]
+            DecoderInner::Lz4(decoder) => decoder.into_inner().into_inner(),
+            #[cfg(feature = "snappy")]
+            DecoderInner::Snappy(decoder) => decoder.into_inner().into_inner(),
+            #[cfg(feature = "brotli")]
+            DecoderInner::Brotli(decoder) => (*decoder).into_inner().into_inner(),
+            DecoderInner::UnixCompress(decoder) => decoder.into_inner().into_inner(),
+            DecoderInner::No(decoder) => decoder,
+        }
+    }
+
+    /// The compressed stream.
+    #[allow(clippy::infallible_destructuring_match)]
+    pub(crate) fn source(&self) -> &Peek<R> {
+        match &self.inner {
+            #[cfg(feature = "gz")]
+            DecoderInner::Gzip(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "gz")]
+            DecoderInner::Flate(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "bgz")]
+            DecoderInner::BGzip(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "bz2")]
+            DecoderInner::Bzip(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "lzma")]
+            DecoderInner::Lzma(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "zstd")]
+            DecoderInner::Zstd(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "lz4")]
+            DecoderInner::Lz4(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "snappy")]
+            DecoderInner::Snappy(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "brotli")]
+            DecoderInner::Brotli(decoder) => decoder.get_ref().get_ref(),
+            DecoderInner::UnixCompress(decoder) => decoder.get_ref().get_ref(),
+            DecoderInner::No(decoder) => decoder,
+        }
+    }
+
+    fn check_truncated(&self, error: io::Error) -> io::Error {
+        crate::utils::check_truncated(error, self.format, self.source().exhausted())
+    }
+}
+
+impl<R: io::Read> io::Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        dispatch!(&mut self.inner, decoder => decoder.read(buf))
+            .map_err(|e| self.check_truncated(e))
+    }
+}
+
+impl<R: io::Read> BufRead for Decoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if let Err(e) = dispatch!(&mut self.inner, decoder => decoder.fill_buf().map(|_| ())) {
+            return Err(self.check_truncated(e));
+        }
+
+        dispatch!(&mut self.inner, decoder => decoder.fill_buf())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        dispatch!(&mut self.inner, decoder => decoder.consume(amt))
+    }
+}
+
+/// Detect the compression format by peeking in the buffer of the returned stream.
+pub(crate) fn sniff_source<R: io::Read>(in_stream: R) -> Result<(Peek<R>, Format), Error> {
+    let mut in_stream = Peek::with_capacity(BUFFER_SIZE, in_stream);
+
+    // peek without consuming, the decoder reads the header again
+    let mut first_bytes = [0u8; 16];
+    let peeked = in_stream.peek(first_bytes.len())?;
+    let len = peeked.len().min(first_bytes.len());
+    first_bytes[..len].copy_from_slice(&peeked[..len]);
+
+    match crate::basic::compression::bytes2type(first_bytes) {
+        // a magic number completed by the zero padding of `first_bytes`, or a cut header
+        format if len < crate::basic::compression::header_len(format) => Err(Error::FileTooShort),
+        Format::No => Ok((in_stream, Format::No)),
+        // plain text can start with a zlib header
+        Format::Zlib if !zlib_start(in_stream.peek(ZLIB_CHECK_SIZE)?) => {
+            Ok((in_stream, Format::No))
+        }
+        format => Ok((in_stream, format)),
+    }
+}
+
+/// Same as [get_reader](crate::get_reader) but without boxing the stream.
+pub fn get_reader<R: io::Read>(in_stream: R) -> Result<Decoder<R>, Error> {
+    let (in_stream, format) = sniff_source(in_stream)?;
+
+    new_decoder(in_stream, format)
+}
+
+/// Same as [get_reader_with_format](crate::get_reader_with_format) but without boxing the stream.
+pub fn get_reader_with_format<R: io::Read>(
+    in_stream: R,
+    format: Format,
+) -> Result<Decoder<R>, Error> {
+    new_decoder(Peek::with_capacity(BUFFER_SIZE, in_stream), format)
+}
+
+/// Decoder of `format`, the boxed APIs wrap it.
+pub(crate) fn new_decoder<R: io::Read>(
+    in_stream: Peek<R>,
+    format: Format,
+) -> Result<Decoder<R>, Error> {
+    let inner = match format {
+        #[cfg(feature = "gz")]
+        Format::Gzip => {
+            DecoderInner::Gzip(buffered(flate2::bufread::MultiGzDecoder::new(in_stream)))
+        }
+        #[cfg(feature = "bgz")]
+        Format::BGzip => {
+            DecoderInner::BGzip(buffered(crate::seek::bgzf::BgzfDecoder::new(in_stream)))
+        }
+        #[cfg(feature = "gz")]
+        Format::Zlib => DecoderInner::Flate(buffered(crate::flate::FlateDecoder::zlib(in_stream))),
+        #[cfg(feature = "gz")]
+        Format::Deflate => {
+            DecoderInner::Flate(buffered(crate::flate::FlateDecoder::deflate(in_stream)))
+        }
+        // BGZF files are also gzip files
+        #[cfg(all(feature = "gz", not(feature = "bgz")))]
+        Format::BGzip => {
+            DecoderInner::Gzip(buffered(flate2::bufread::MultiGzDecoder::new(in_stream)))
+        }
+        #[cfg(feature = "bz2")]
+        Format::Bzip => {
+            DecoderInner::Bzip(buffered(bzip2::bufread::MultiBzDecoder::new(in_stream)))
+        }
+        #[cfg(feature = "lzma")]
+        Format::Lzma => DecoderInner::Lzma(buffered(liblzma::bufread::XzDecoder::new(in_stream))),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => DecoderInner::Zstd(buffered(zstd::stream::read::Decoder::with_buffer(
+            in_stream,
+        )?)),
+        #[cfg(feature = "lz4")]
+        Format::Lz4 => DecoderInner::Lz4(buffered(crate::lz4_frame::Lz4Decoder::new(in_stream)?)),
+        #[cfg(feature = "snappy")]
+        Format::Snappy => DecoderInner::Snappy(buffered(snap::read::FrameDecoder::new(in_stream))),
+        #[cfg(feature = "brotli")]
+        Format::Brotli => DecoderInner::Brotli(Box::new(buffered(brotli::Decompressor::new(
+            in_stream,
+            BUFFER_SIZE,
+        )))),
+        Format::UnixCompress => {
+            DecoderInner::UnixCompress(buffered(crate::unix_compress::LzwDecoder::new(in_stream)?))
+        }
+        Format::No => DecoderInner::No(in_stream),
+        #[allow(unreachable_patterns)]
+        _ => return Err(Error::FeatureDisabled(format)),
+    };
+
+    Ok(Decoder { inner, format })
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "gz",
+        feature = "bgz",
+        feature = "bz2",
+        feature = "lzma",
+        feature = "zstd",
+        feature = "lz4",
+        feature = "snappy",
+        feature = "brotli"
+    )),
+    allow(dead_code)
+)]
+fn buffered<D: io::Read>(decoder: D) -> io::BufReader<D> {
+    io::BufReader::with_capacity(BUFFER_SIZE, decoder)
+}
+
+/// Same as [from_path](crate::from_path) but without boxing the stream.
+pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Decoder<std::fs::File>, Error> {
+    let path = path.as_ref();
+    let result = sniff_source(crate::utils::open(path)?).and_then(|(in_stream, format)| {
+        new_decoder(in_stream, crate::utils::path_format(path, format))
+    });
+
+    result.map_err(|e| e.with_path(path))
+}
+
+/// A compressed stream returned by [get_writer], [finish](Encoder::finish) writes the end of
+/// the compressed stream and reports errors.
+///
+/// Dropping an `Encoder` also ends the compressed stream, but errors are lost.
+pub struct Encoder<W: io::Write> {
+    inner: Option<EncoderInner<W>>,
+    format: Format,
+}
+
+enum EncoderInner<W: io::Write> {
+    #[cfg(feature = "gz")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "gz")]
+    Zlib(flate2::write::ZlibEncoder<W>),
+    #[cfg(feature = "gz")]
+    Deflate(flate2::write::DeflateEncoder<W>),
+    #[cfg(feature = "bgz")]
+    BGzip(crate::seek::bgzf::BgzfWriter<W>),
+    #[cfg(feature = "bz2")]
+    Bzip(bzip2::write::BzEncoder<W>),
+    #[cfg(feature = "lzma")]
+    Lzma(liblzma::write::XzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    #[cfg(feature = "lz4")]
+    Lz4(lz4::Encoder<W>),
+    #[cfg(feature = "snappy")]
+    Snappy(Box<crate::snappy_encoder::SnappyEncoder<W>>),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<crate::brotli_encoder::BrotliEncoder<W>>),
+    No(W),
+}
+
+impl<W: io::Write> EncoderInner<W> {
+    fn get_mut(&mut self) -> &mut dyn io::Write {
+        match self {
+            #[cfg(feature = "gz")]
+            EncoderInner::Gzip(encoder) => encoder,
+            #[cfg(feature = "gz")]
+            EncoderInner::Zlib(encoder) => encoder,
+            #[cfg(feature = "gz")]
+            EncoderInner::Deflate(encoder) => encoder,
+            #[cfg(feature = "bgz")]
+            EncoderInner::BGzip(encoder) => encoder,
+            #[cfg(feature = "bz2")]
+            EncoderInner::Bzip(encoder) => encoder,
+            #[cfg(feature = "lzma")]
+            EncoderInner::Lzma(encoder) => encoder,
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(encoder) => encoder,
+            #[cfg(feature = "lz4")]
+            EncoderInner::Lz4(encoder) => encoder,
+            #[cfg(feature = "snappy")]
+            EncoderInner::Snappy(encoder) => encoder,
+            #[cfg(feature = "brotli")]
+            EncoderInner::Brotli(encoder) => encoder,
+            EncoderInner::No(encoder) => encoder,
+        }
+    }
+
+    #[allow(clippy::infallible_destructuring_match)]
+    fn finish(self) -> Result<W, Error> {
+        let mut out = match self {
+            #[cfg(feature = "gz")]
+            EncoderInner::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "gz")]
+            EncoderInner::Zlib(encoder) => encoder.finish()?,
+            #[cfg(feature = "gz")]
+            EncoderInner::Deflate(encoder) => encoder.finish()?,
+            #[cfg(feature = "bgz")]
+            EncoderInner::BGzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "bz2")]
+            EncoderInner::Bzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "lzma")]
+            EncoderInner::Lzma(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(encoder) => encoder.finish()?,
+            #[cfg(feature = "lz4")]
+            EncoderInner::Lz4(encoder) => {
+                let (out, result) = encoder.finish();
+                result?;
+                out
+            }
+            #[cfg(feature = "snappy")]
+            EncoderInner::Snappy(encoder) => (*encoder).finish()?,
+            #[cfg(feature = "brotli")]
+            EncoderInner::Brotli(encoder) => (*encoder).finish()?,
+            EncoderInner::No(encoder) => encoder,
+        };
+        out.flush()?;
+
+        Ok(out)
+    }
+}
+
+impl<W: io::Write> Encoder<W> {
+    /// Compression format of the stream.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Write the end of the compressed stream, flush and return the underlying stream.
+    pub fn finish(mut self) -> Result<W, Error> {
+        match self.inner.take() {
+            Some(inner) => inner.finish(),
+            None => Err(finished().into()),
+        }
+    }
+
+    fn inner(&mut self) -> io::Result<&mut dyn io::Write> {
+        self.inner
+            .as_mut()
+            .map(EncoderInner::get_mut)
+            .ok_or_else(finished)
+    }
+}
+
+impl<W: io::Write> io::Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner()?.flush()
+    }
+}
+
+impl<W: io::Write> Drop for Encoder<W> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            let _ = inner.finish();
+        }
+    }
+}
+
+fn finished() -> io::Error {
+    io::Error::other("compressed stream is already finished")
+}
+
+/// Same as [get_writer](crate::get_writer) but without boxing the stream.
+#[cfg_attr(
+    not(any(
+        feature = "gz",
+        feature = "bgz",
+        feature = "bz2",
+        feature = "lzma",
+        feature = "zstd",
+        feature = "lz4",
+        feature = "brotli"
+    )),
+    allow(unused_variables)
+)]
+pub fn get_writer<W: io::Write>(
+    out_stream: W,
+    format: Format,
+    level: Level,
+) -> Result<Encoder<W>, Error> {
+    let inner = match format {
+        #[cfg(feature = "gz")]
+        Format::Gzip => EncoderInner::Gzip(flate2::write::GzEncoder::new(out_stream, level.into())),
+        #[cfg(feature = "gz")]
+        Format::Zlib => {
+            EncoderInner::Zlib(flate2::write::ZlibEncoder::new(out_stream, level.into()))
+        }
+        #[cfg(feature = "gz")]
+        Format::Deflate => {
+            EncoderInner::Deflate(flate2::write::DeflateEncoder::new(out_stream, level.into()))
+        }
+        #[cfg(feature = "bgz")]
+        Format::BGzip => EncoderInner::BGzip(crate::seek::bgzf::BgzfWriter::new(out_stream, level)),
+        #[cfg(feature = "bz2")]
+        Format::Bzip => EncoderInner::Bzip(bzip2::write::BzEncoder::new(out_stream, level.into())),
+        #[cfg(feature = "lzma")]
+        Format::Lzma => EncoderInner::Lzma(liblzma::write::XzEncoder::new(
+            out_stream,
+            level.xz_preset()?,
+        )),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => {
+            EncoderInner::Zstd(zstd::stream::write::Encoder::new(out_stream, level.into())?)
+        }
+        #[cfg(feature = "lz4")]
+        Format::Lz4 => EncoderInner::Lz4(
+            lz4::EncoderBuilder::new()
+                .level(level.lz4_level())
+                .build(out_stream)?,
+        ),
+        #[cfg(feature = "snappy")]
+        Format::Snappy => EncoderInner::Snappy(Box::new(
+            crate::snappy_encoder::SnappyEncoder::new(out_stream),
+        )),
+        #[cfg(feature = "brotli")]
+        Format::Brotli => EncoderInner::Brotli(Box::new(
+            crate::brotli_encoder::BrotliEncoder::new(out_stream, level.brotli_quality()),
+        )),
+        Format::No => EncoderInner::No(out_stream),
+        // decode only
+        Format::UnixCompress => return Err(Error::UnsupportedFormat(format)),
+        #[allow(unreachable_patterns)]
+        _ => return Err(Error::FeatureDisabled(format)),
+    };
+
+    Ok(Encoder {
+        inner: Some(inner),
+        format,
+    })
+}
+
+/// Same as [to_path](crate::to_path) but without boxing the stream.
+pub fn to_path<P: AsRef<Path>>(
+    path: P,
+    format: Format,
+    level: Level,
+) -> Result<Encoder<io::BufWriter<std::fs::File>>, Error> {
+    let path = path.as_ref();
+    get_writer(
+        io::BufWriter::new(crate::utils::create(path)?),
+        format,
+        level,
+    )
+    .map_err(|e| e.with_path(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+
+    const LOREM_IPSUM: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. Ut ultricies scelerisque diam, a scelerisque enim sagittis at.";
+
+    fn open(compressed: &[u8], format: Format) -> Decoder<&[u8]> {
+        match format {
+            // no magic number
+            Format::Brotli | Format::Deflate => get_reader_with_format(compressed, format).unwrap(),
+            _ => get_reader(compressed).unwrap(),
+        }
+    }
+
+    fn roundtrip(format: Format) {
+        // empty data gives the shortest stream of each format
+        for input in [LOREM_IPSUM, b""] {
+            let mut writer = get_writer(Vec::new(), format, Level::Six).unwrap();
+            assert_eq!(writer.format(), format);
+            writer.write_all(input).unwrap();
+            let compressed = writer.finish().unwrap();
+
+            let mut reader = open(&compressed, format);
+            assert_eq!(reader.format(), format);
+
+            let mut buffer = Vec::new();
+            reader.read_to_end(&mut buffer).unwrap();
+            assert_eq!(input, buffer.as_slice());
+
+            if format != Format::No && !input.is_empty() {
+                let mut reader = open(&compressed[..compressed.len() - 1], format);
+                let error = Error::from(reader.read_to_end(&mut Vec::new()).unwrap_err());
+                assert!(matches!(error, Error::Truncated { format: f } if f == format));
+            }
+        }
+    }
+
+    #[test]
+    fn no_compression() {
+        roundtrip(Format::No);
+    }
+
+    #[cfg(feature = "gz")]
+    #[test]
+    fn gzip() {
+        roundtrip(Format::Gzip);
+    }
+
+    #[test]
+    fn unix_compress() {
+        // `printf 'abababababababab\n' | compress`
+        let compressed = [
+            0x1f, 0x9d, 0x90, 0x61, 0xc4, 0x4, 0x1c, 0x28, 0xb0, 0x20, 0x41, 0x5,
+        ];
+        let mut reader = get_reader(&compressed[..]).unwrap();
+        assert_eq!(reader.format(), Format::UnixCompress);
+
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, b"abababababababab\n");
+
+        assert!(matches!(
+            get_writer(Vec::new(), Format::UnixCompress, Level::Six),
+            Err(Error::UnsupportedFormat(Format::UnixCompress))
+        ));
+    }
+
+    #[cfg(feature = "gz")]
+    #[test]
+    fn zlib() {
+        roundtrip(Format::Zlib);
+    }
+
+    #[cfg(feature = "gz")]
+    #[test]
+    fn deflate() {
+        roundtrip(Format::Deflate);
+    }
+
+    #[cfg(feature = "bgz")]
+    #[test]
+    fn bgzip() {
+        roundtrip(Format::BGzip);
+    }
+
+    #[cfg(feature = "bz2")]
+    #[test]
+    fn bzip() {
+        roundtrip(Format::Bzip);
+    }
+
+    #[cfg(feature = "lzma")]
+    #[test]
+    fn lzma() {
+        roundtrip(Format::Lzma);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        roundtrip(Format::Zstd);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4() {
+        roundtrip(Format::Lz4);
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn snappy() {
+        roundtrip(Format::Snappy);
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli() {
+        roundtrip(Format::Brotli);
+    }
+
+    #[cfg(not(feature = "gz"))]
+    #[test]
+    fn no_gzip_feature() {
+        assert!(get_writer(Vec::new(), Format::Gzip, Level::Six).is_err());
+        assert!(get_reader(&[0x1f, 0x8b, 0x8, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x3][..]).is_err());
+    }
+
+    #[test]
+    fn auto_traits() {
+        fn send<T: Send>() {}
+        fn sync<T: Sync>() {}
+
+        send::<Decoder<std::fs::File>>();
+        send::<Encoder<std::fs::File>>();
+        sync::<Decoder<&[u8]>>();
+    }
+
+    #[test]
+    fn finish() {
+        let mut writer = get_writer(Vec::new(), Format::No, Level::One).unwrap();
+        writer.write_all(LOREM_IPSUM).unwrap();
+        assert_eq!(writer.finish().unwrap(), LOREM_IPSUM);
+    }
+
+    #[cfg(feature = "gz")]
+    #[test]
+    fn drop_finish() {
+        let mut compressed = Vec::new();
+        {
+            let mut writer = get_writer(&mut compressed, Format::Gzip, Level::Six).unwrap();
+            writer.write_all(LOREM_IPSUM).unwrap();
+        }
+
+        let mut buffer = Vec::new();
+        get_reader(compressed.as_slice())
+            .unwrap()
+            .read_to_end(&mut buffer)
+            .unwrap();
+        assert_eq!(LOREM_IPSUM, buffer.as_slice());
+    }
+}
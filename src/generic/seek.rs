@@ -0,0 +1,383 @@
+//! Static dispatch API for seekable formats.
+//!
+//! Same as [seek](crate::seek) but [get_reader] and [get_writer] are generic over the stream, a
+//! [Decoder] is `Send` if the stream is `Send` so there is no need for the
+//! [seeksend](crate::seeksend) copy of the API.
+//!
+//! # Example
+//! ```
+//! # fn main() -> Result<(), niffler::Error> {
+//! # #[cfg(feature = "bgz")] {
+//! use std::io::{Read, Seek, Write};
+//! use niffler::Format;
+//!
+//! let mut writer = niffler::generic::seek::get_writer(std::io::Cursor::new(Vec::new()), Format::BGzip, niffler::Level::Six)?;
+//! writer.write_all(b"I'm compress in bgzip\n")?;
+//! let mut buffer = writer.finish()?;
+//! buffer.set_position(0);
+//!
+//! let mut reader = niffler::generic::seek::get_reader(buffer)?;
+//! reader.seek(std::io::SeekFrom::Start(4))?;
+//!
+//! let mut contents = String::new();
+//! reader.read_to_string(&mut contents)?;
+//!
+//! assert_eq!(reader.format(), Format::BGzip);
+//! assert_eq!(contents, "compress in bgzip\n");
+//! # }
+//! # Ok(())
+//! # }
+//! ```
+
+/* standard use */
+use std::io;
+use std::io::{BufRead, Read, Seek};
+use std::path::Path;
+
+/* project use */
+use crate::error::Error;
+use crate::format::Format;
+use crate::level::Level;
+
+/// Run `$body` on the reader of any variant.
+macro_rules! dispatch {
+    ($inner:expr, $reader:ident => $body:expr) => {
+        match $inner {
+            #[cfg(feature = "bgz")]
+            DecoderInner::BGzip($reader) => $body,
+            #[cfg(feature = "zstd")]
+            DecoderInner::Zstd($reader) => $body,
+            #[cfg(feature = "lzma")]
+            DecoderInner::Lzma($reader) => $body,
+            #[cfg(feature = "bz2")]
+            DecoderInner::Bzip($reader) => $body,
+            DecoderInner::No($reader) => $body,
+        }
+    };
+}
+
+/// A decompressed stream returned by [get_reader], seeking is done on uncompressed offsets.
+pub struct Decoder<R: Read + Seek> {
+    inner: DecoderInner<R>,
+}
+
+enum DecoderInner<R: Read + Seek> {
+    #[cfg(feature = "bgz")]
+    BGzip(crate::seek::bgzf::BgzfReader<R>),
+    #[cfg(feature = "zstd")]
+    Zstd(crate::seek::zstd_seekable::SeekableReader<R>),
+    #[cfg(feature = "lzma")]
+    Lzma(crate::seek::xz::XzReader<R>),
+    #[cfg(feature = "bz2")]
+    Bzip(crate::seek::bz2::Bzip2Reader<R>),
+    No(io::BufReader<R>),
+}
+
+impl<R: Read + Seek> Decoder<R> {
+    /// Compression format of the stream.
+    pub fn format(&self) -> Format {
+        match self.inner {
+            #[cfg(feature = "bgz")]
+            DecoderInner::BGzip(_) => Format::BGzip,
+            #[cfg(feature = "zstd")]
+            DecoderInner::Zstd(_) => Format::Zstd,
+            #[cfg(feature = "lzma")]
+            DecoderInner::Lzma(_) => Format::Lzma,
+            #[cfg(feature = "bz2")]
+            DecoderInner::Bzip(_) => Format::Bzip,
+            DecoderInner::No(_) => Format::No,
+        }
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> R {
+        dispatch!(self.inner, reader => reader.into_inner())
+    }
+}
+
+impl<R: Read + Seek> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        dispatch!(&mut self.inner, reader => reader.read(buf))
+    }
+}
+
+impl<R: Read + Seek> BufRead for Decoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        dispatch!(&mut self.inner, reader => reader.fill_buf())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        dispatch!(&mut self.inner, reader => reader.consume(amt))
+    }
+}
+
+impl<R: Read + Seek> Seek for Decoder<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        dispatch!(&mut self.inner, reader => reader.seek(pos))
+    }
+}
+
+/// Same as [seek::get_reader](crate::seek::get_reader) but without boxing the stream.
+pub fn get_reader<R: Read + Seek>(mut in_stream: R) -> Result<Decoder<R>, Error> {
+    let format = crate::seek::compression::sniff_format(&mut in_stream)?;
+
+    let inner = match format {
+        #[cfg(feature = "bgz")]
+        Format::BGzip => DecoderInner::BGzip(crate::seek::bgzf::BgzfReader::new(in_stream)?),
+        #[cfg(feature = "zstd")]
+        Format::Zstd if crate::seek::compression::has_seek_table(&mut in_stream)? => {
+            DecoderInner::Zstd(crate::seek::zstd_seekable::SeekableReader::new(in_stream)?)
+        }
+        #[cfg(feature = "lzma")]
+        Format::Lzma => DecoderInner::Lzma(crate::seek::xz::XzReader::new(in_stream)?),
+        #[cfg(feature = "bz2")]
+        Format::Bzip => DecoderInner::Bzip(crate::seek::bz2::Bzip2Reader::new(in_stream)?),
+        Format::No => DecoderInner::No(io::BufReader::new(in_stream)),
+        Format::Gzip
+        | Format::Zstd
+        | Format::Lz4
+        | Format::Snappy
+        | Format::Brotli
+        | Format::Zlib
+        | Format::Deflate
+        | Format::UnixCompress => return Err(Error::UnsupportedFormat(format)),
+        #[allow(unreachable_patterns)]
+        _ => return Err(Error::FeatureDisabled(format)),
+    };
+
+    Ok(Decoder { inner })
+}
+
+/// Same as [seek::from_path](crate::seek::from_path) but without boxing the stream.
+pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Decoder<std::fs::File>, Error> {
+    let path = path.as_ref();
+    get_reader(crate::utils::open(path)?).map_err(|e| e.with_path(path))
+}
+
+/// A compressed stream returned by [get_writer], it only support `Seek` to get the current
+/// uncompressed position. [finish](Encoder::finish) writes the end of the compressed stream and
+/// reports errors.
+pub struct Encoder<W: io::Write + Seek> {
+    inner: EncoderInner<W>,
+}
+
+enum EncoderInner<W: io::Write + Seek> {
+    #[cfg(feature = "bgz")]
+    BGzip(crate::seek::bgzf::BgzfWriter<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(crate::seek::zstd_seekable::SeekableWriter<W>),
+    #[cfg(feature = "lzma")]
+    Lzma(crate::seek::xz::XzWriter<W>),
+    #[cfg(feature = "bz2")]
+    Bzip(crate::seek::bz2::Bzip2Writer<W>),
+    No(W),
+}
+
+/// Run `$body` on the writer of any variant.
+macro_rules! dispatch_writer {
+    ($inner:expr, $writer:ident => $body:expr) => {
+        match $inner {
+            #[cfg(feature = "bgz")]
+            EncoderInner::BGzip($writer) => $body,
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd($writer) => $body,
+            #[cfg(feature = "lzma")]
+            EncoderInner::Lzma($writer) => $body,
+            #[cfg(feature = "bz2")]
+            EncoderInner::Bzip($writer) => $body,
+            EncoderInner::No($writer) => $body,
+        }
+    };
+}
+
+impl<W: io::Write + Seek> Encoder<W> {
+    /// Compression format of the stream.
+    pub fn format(&self) -> Format {
+        match self.inner {
+            #[cfg(feature = "bgz")]
+            EncoderInner::BGzip(_) => Format::BGzip,
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(_) => Format::Zstd,
+            #[cfg(feature = "lzma")]
+            EncoderInner::Lzma(_) => Format::Lzma,
+            #[cfg(feature = "bz2")]
+            EncoderInner::Bzip(_) => Format::Bzip,
+            EncoderInner::No(_) => Format::No,
+        }
+    }
+
+    /// Write the end of the compressed stream, flush and return the underlying stream.
+    #[allow(clippy::infallible_destructuring_match)]
+    pub fn finish(self) -> Result<W, Error> {
+        let mut out = match self.inner {
+            #[cfg(feature = "bgz")]
+            EncoderInner::BGzip(writer) => writer.finish()?,
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(writer) => writer.finish()?,
+            #[cfg(feature = "lzma")]
+            EncoderInner::Lzma(writer) => writer.finish()?,
+            #[cfg(feature = "bz2")]
+            EncoderInner::Bzip(writer) => writer.finish()?,
+            EncoderInner::No(writer) => writer,
+        };
+        out.flush()?;
+
+        Ok(out)
+    }
+}
+
+impl<W: io::Write + Seek> io::Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        dispatch_writer!(&mut self.inner, writer => writer.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        dispatch_writer!(&mut self.inner, writer => writer.flush())
+    }
+}
+
+impl<W: io::Write + Seek> Seek for Encoder<W> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        dispatch_writer!(&mut self.inner, writer => writer.seek(pos))
+    }
+}
+
+/// Same as [seek::get_writer](crate::seek::get_writer) but without boxing the stream.
+#[cfg_attr(
+    not(any(feature = "bgz", feature = "bz2", feature = "lzma", feature = "zstd")),
+    allow(unused_variables)
+)]
+pub fn get_writer<W: io::Write + Seek>(
+    out_stream: W,
+    format: Format,
+    level: Level,
+) -> Result<Encoder<W>, Error> {
+    let inner = match format {
+        #[cfg(feature = "bgz")]
+        Format::BGzip => EncoderInner::BGzip(crate::seek::bgzf::BgzfWriter::new(out_stream, level)),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => EncoderInner::Zstd(crate::seek::zstd_seekable::SeekableWriter::new(
+            out_stream, level,
+        )),
+        #[cfg(feature = "lzma")]
+        Format::Lzma => {
+            level.xz_preset()?;
+            EncoderInner::Lzma(crate::seek::xz::XzWriter::new(out_stream, level))
+        }
+        #[cfg(feature = "bz2")]
+        Format::Bzip => EncoderInner::Bzip(crate::seek::bz2::Bzip2Writer::new(out_stream, level)),
+        Format::No => EncoderInner::No(out_stream),
+        Format::Gzip
+        | Format::Lz4
+        | Format::Snappy
+        | Format::Brotli
+        | Format::Zlib
+        | Format::Deflate
+        | Format::UnixCompress => return Err(Error::UnsupportedFormat(format)),
+        #[allow(unreachable_patterns)]
+        _ => return Err(Error::FeatureDisabled(format)),
+    };
+
+    Ok(Encoder { inner })
+}
+
+/// Same as [seek::to_path](crate::seek::to_path) but without boxing the stream.
+pub fn to_path<P: AsRef<Path>>(
+    path: P,
+    format: Format,
+    level: Level,
+) -> Result<Encoder<io::BufWriter<std::fs::File>>, Error> {
+    let path = path.as_ref();
+    get_writer(
+        io::BufWriter::new(crate::utils::create(path)?),
+        format,
+        level,
+    )
+    .map_err(|e| e.with_path(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    /// Large enough for several xz blocks.
+    fn data() -> Vec<u8> {
+        (0..200_000)
+            .flat_map(|i| format!("line {i:06}\n").into_bytes())
+            .collect()
+    }
+
+    fn roundtrip(format: Format) {
+        let data = data();
+        let mut writer = get_writer(io::Cursor::new(Vec::new()), format, Level::One).unwrap();
+        assert_eq!(writer.format(), format);
+        writer.write_all(&data).unwrap();
+        assert_eq!(writer.stream_position().unwrap(), data.len() as u64);
+        let mut compressed = writer.finish().unwrap();
+        compressed.set_position(0);
+
+        let mut reader = get_reader(compressed).unwrap();
+        assert_eq!(reader.format(), format);
+
+        let mut line = [0u8; 11];
+        reader.seek(io::SeekFrom::Start(12 * 123_456)).unwrap();
+        reader.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"line 123456");
+
+        reader.rewind().unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, data);
+    }
+
+    #[test]
+    fn no_compression() {
+        roundtrip(Format::No);
+    }
+
+    #[cfg(feature = "bgz")]
+    #[test]
+    fn bgzip() {
+        roundtrip(Format::BGzip);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        roundtrip(Format::Zstd);
+    }
+
+    #[cfg(feature = "lzma")]
+    #[test]
+    fn lzma() {
+        roundtrip(Format::Lzma);
+    }
+
+    #[cfg(feature = "bz2")]
+    #[test]
+    fn bzip() {
+        roundtrip(Format::Bzip);
+    }
+
+    #[test]
+    fn unseekable_format() {
+        // `printf 'abababababababab\n' | compress`
+        let compressed = [
+            0x1f, 0x9d, 0x90, 0x61, 0xc4, 0x4, 0x1c, 0x28, 0xb0, 0x20, 0x41, 0x5,
+        ];
+
+        assert!(matches!(
+            get_reader(io::Cursor::new(compressed)),
+            Err(Error::UnsupportedFormat(Format::UnixCompress))
+        ));
+    }
+
+    #[test]
+    fn auto_traits() {
+        fn send<T: Send>() {}
+
+        send::<Decoder<std::fs::File>>();
+        send::<Encoder<std::fs::File>>();
+    }
+}
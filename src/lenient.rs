@@ -0,0 +1,276 @@
+//! Decode compressed streams member by member, and stop at the first data which doesn't start a
+//! new member (gzip member, bzip2, xz or zlib stream, zstd or lz4 frame) instead of failing on it.
+
+#![cfg_attr(
+    not(any(
+        feature = "gz",
+        feature = "bgz",
+        feature = "bz2",
+        feature = "lzma",
+        feature = "zstd",
+        feature = "lz4"
+    )),
+    allow(
+        unused_mut,
+        unused_variables,
+        dead_code,
+        unreachable_code,
+        clippy::never_loop
+    )
+)]
+
+/* standard use */
+use std::io;
+use std::io::Read;
+
+/* project use */
+use crate::error::Error;
+use crate::format::Format;
+use crate::utils::Peek;
+
+/// A decoder which ignores trailing garbage or padding after the last complete member.
+pub(crate) struct Lenient<R: Read> {
+    // None only if a member decoder can't be created
+    member: Option<Member<R>>,
+    format: Format,
+    trailing: u64,
+}
+
+enum Member<R: Read> {
+    #[cfg(feature = "gz")]
+    Gzip(flate2::bufread::GzDecoder<Peek<R>>),
+    #[cfg(feature = "gz")]
+    Flate(crate::flate::FlateDecoder<Peek<R>>),
+    #[cfg(feature = "bgz")]
+    BGzip(crate::seek::bgzf::BgzfDecoder<Peek<R>>),
+    #[cfg(feature = "bz2")]
+    Bzip(bzip2::bufread::BzDecoder<Peek<R>>),
+    #[cfg(feature = "lzma")]
+    Lzma(Xz<R>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, Peek<R>>),
+    #[cfg(feature = "lz4")]
+    Lz4(crate::lz4_frame::Lz4Decoder<Peek<R>>),
+    End(Peek<R>),
+}
+
+impl<R: Read> Lenient<R> {
+    pub(crate) fn new(source: Peek<R>, format: Format) -> Result<Self, Error> {
+        Ok(Lenient {
+            member: Some(start(source, format)?),
+            format,
+            trailing: 0,
+        })
+    }
+
+    /// Number of bytes ignored after the last member, known once the end is reached.
+    pub(crate) fn trailing_bytes(&self) -> u64 {
+        self.trailing
+    }
+
+    /// The end of the compressed stream was reached and every buffered byte was consumed.
+    pub(crate) fn exhausted(&self) -> bool {
+        self.member
+            .as_ref()
+            .map_or(true, |member| member.source().exhausted())
+    }
+
+    /// Return the compressed stream.
+    pub(crate) fn into_source(self) -> Option<Peek<R>> {
+        self.member.map(Member::into_source)
+    }
+
+    /// Start the next member, or skip the remaining data if it isn't one.
+    fn next_member(&mut self) -> io::Result<()> {
+        let Some(member) = self.member.take() else {
+            return Ok(());
+        };
+        let mut source = member.into_source();
+
+        if starts_member(&mut source, self.format)? {
+            self.member = Some(start(source, self.format)?);
+        } else {
+            self.trailing = io::copy(&mut source, &mut io::sink())?;
+            self.member = Some(Member::End(source));
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Lenient<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let read = match self.member.as_mut() {
+                #[cfg(feature = "gz")]
+                Some(Member::Gzip(decoder)) => decoder.read(buf)?,
+                #[cfg(feature = "gz")]
+                Some(Member::Flate(decoder)) => decoder.read(buf)?,
+                #[cfg(feature = "bgz")]
+                Some(Member::BGzip(decoder)) => decoder.read(buf)?,
+                #[cfg(feature = "bz2")]
+                Some(Member::Bzip(decoder)) => decoder.read(buf)?,
+                #[cfg(feature = "lzma")]
+                Some(Member::Lzma(decoder)) => decoder.read(buf)?,
+                #[cfg(feature = "zstd")]
+                Some(Member::Zstd(decoder)) => decoder.read(buf)?,
+                #[cfg(feature = "lz4")]
+                Some(Member::Lz4(decoder)) => decoder.read(buf)?,
+                Some(Member::End(_)) | None => return Ok(0),
+            };
+
+            if read != 0 {
+                return Ok(read);
+            }
+
+            self.next_member()?;
+        }
+    }
+}
+
+impl<R: Read> Member<R> {
+    fn source(&self) -> &Peek<R> {
+        match self {
+            #[cfg(feature = "gz")]
+            Member::Gzip(decoder) => decoder.get_ref(),
+            #[cfg(feature = "gz")]
+            Member::Flate(decoder) => decoder.get_ref(),
+            #[cfg(feature = "bgz")]
+            Member::BGzip(decoder) => decoder.get_ref(),
+            #[cfg(feature = "bz2")]
+            Member::Bzip(decoder) => decoder.get_ref(),
+            #[cfg(feature = "lzma")]
+            Member::Lzma(decoder) => &decoder.source,
+            #[cfg(feature = "zstd")]
+            Member::Zstd(decoder) => decoder.get_ref(),
+            #[cfg(feature = "lz4")]
+            Member::Lz4(decoder) => decoder.get_ref(),
+            Member::End(source) => source,
+        }
+    }
+
+    fn into_source(self) -> Peek<R> {
+        match self {
+            #[cfg(feature = "gz")]
+            Member::Gzip(decoder) => decoder.into_inner(),
+            #[cfg(feature = "gz")]
+            Member::Flate(decoder) => decoder.into_inner(),
+            #[cfg(feature = "bgz")]
+            Member::BGzip(decoder) => decoder.into_inner(),
+            #[cfg(feature = "bz2")]
+            Member::Bzip(decoder) => decoder.into_inner(),
+            #[cfg(feature = "lzma")]
+            Member::Lzma(decoder) => decoder.source,
+            #[cfg(feature = "zstd")]
+            Member::Zstd(decoder) => decoder.finish(),
+            #[cfg(feature = "lz4")]
+            Member::Lz4(decoder) => decoder.into_inner(),
+            Member::End(source) => source,
+        }
+    }
+}
+
+/// A single xz stream, [liblzma::bufread::XzDecoder] fails on any data following the stream.
+#[cfg(feature = "lzma")]
+struct Xz<R: Read> {
+    source: Peek<R>,
+    stream: liblzma::stream::Stream,
+    ended: bool,
+}
+
+#[cfg(feature = "lzma")]
+impl<R: Read> Read for Xz<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use io::BufRead;
+        use liblzma::stream::{Action, Status};
+
+        while !self.ended {
+            let input = self.source.fill_buf()?;
+            let eof = input.is_empty();
+            let (before_in, before_out) = (self.stream.total_in(), self.stream.total_out());
+            let action = if eof { Action::Finish } else { Action::Run };
+            let status = self.stream.process(input, buf, action)?;
+            let consumed = (self.stream.total_in() - before_in) as usize;
+            let read = (self.stream.total_out() - before_out) as usize;
+            self.source.consume(consumed);
+
+            self.ended = status == Status::StreamEnd;
+            if read > 0 {
+                return Ok(read);
+            } else if eof && !self.ended {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+/// Create the decoder of a single member.
+fn start<R: Read>(source: Peek<R>, format: Format) -> Result<Member<R>, Error> {
+    Ok(match format {
+        #[cfg(feature = "gz")]
+        Format::Gzip => Member::Gzip(flate2::bufread::GzDecoder::new(source)),
+        #[cfg(feature = "gz")]
+        Format::Zlib => Member::Flate(crate::flate::FlateDecoder::zlib(source).single_stream()),
+        // BGZF blocks are gzip members, the decoder reads them all and stops before other data
+        #[cfg(feature = "bgz")]
+        Format::BGzip => Member::BGzip(
+            crate::seek::bgzf::BgzfDecoder::new(source)
+                .stop_at(|source| starts_member(source, Format::BGzip).map(|member| !member)),
+        ),
+        #[cfg(all(feature = "gz", not(feature = "bgz")))]
+        Format::BGzip => Member::Gzip(flate2::bufread::GzDecoder::new(source)),
+        #[cfg(feature = "bz2")]
+        Format::Bzip => Member::Bzip(bzip2::bufread::BzDecoder::new(source)),
+        #[cfg(feature = "lzma")]
+        Format::Lzma => Member::Lzma(Xz {
+            source,
+            stream: liblzma::stream::Stream::new_stream_decoder(u64::MAX, 0)
+                .map_err(io::Error::from)?,
+            ended: false,
+        }),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => {
+            Member::Zstd(zstd::stream::read::Decoder::with_buffer(source)?.single_frame())
+        }
+        #[cfg(feature = "lz4")]
+        Format::Lz4 => Member::Lz4(crate::lz4_frame::Lz4Decoder::new(source)?.single_frame()),
+        Format::No => Member::End(source),
+        #[allow(unreachable_patterns)]
+        _ => return Err(Error::FeatureDisabled(format)),
+    })
+}
+
+/// The next bytes of `source` start a member of this format.
+fn starts_member<R: Read>(source: &mut Peek<R>, format: Format) -> io::Result<bool> {
+    let head = source.peek(16)?;
+    if head.is_empty() {
+        return Ok(false);
+    }
+
+    let len = head.len().min(16);
+    let mut magic = [0u8; 16];
+    magic[..len].copy_from_slice(&head[..len]);
+
+    Ok(
+        match (format, crate::basic::compression::bytes2type(magic)) {
+            (Format::Gzip, Format::Gzip | Format::BGzip) => true,
+            // without the bgz feature BGZF files are read as gzip
+            (Format::BGzip, Format::BGzip) => true,
+            #[cfg(not(feature = "bgz"))]
+            (Format::BGzip, Format::Gzip) => true,
+            // skippable frames, shared by zstd and lz4
+            (Format::Zstd | Format::Lz4, _)
+                if magic[0] & 0xf0 == 0x50 && magic[1..4] == [0x2a, 0x4d, 0x18] =>
+            {
+                true
+            }
+            (format, next) => format == next,
+        },
+    )
+}
@@ -5,7 +5,14 @@
 /// For bzip2:
 ///  - `Zero`/`One` are convert to `bzip2::Compression::Fastest`,
 ///  - `Nine` in `bzip2::Compression::Best`
-///     and other value is convert to `bzip2::Compression::Default.
+///    and other value is convert to `bzip2::Compression::Default.
+///
+/// For lz4, `Zero` to `Two` use the fast compressor and `Three` to `Twelve` the high compression
+/// (HC) one, higher levels are convert to `Twelve`.
+///
+/// Snappy has no compression level, it's ignored.
+///
+/// Brotli qualities stop at 11, higher levels are convert to `Eleven`.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Level {
     Zero,
@@ -128,6 +135,57 @@ impl From<Level> for bzip2::Compression {
     }
 }
 
+#[cfg(feature = "bgz")]
+impl From<Level> for bgzip::Compression {
+    fn from(level: Level) -> Self {
+        // every bgzip backend accept levels 0 to 9
+        match level {
+            Level::Zero => bgzip::Compression::new(0),
+            Level::One => bgzip::Compression::new(1),
+            Level::Two => bgzip::Compression::new(2),
+            Level::Three => bgzip::Compression::new(3),
+            Level::Four => bgzip::Compression::new(4),
+            Level::Five => bgzip::Compression::new(5),
+            Level::Six => bgzip::Compression::new(6),
+            Level::Seven => bgzip::Compression::new(7),
+            Level::Eight => bgzip::Compression::new(8),
+            Level::Nine => bgzip::Compression::new(9),
+            _ => bgzip::Compression::new(9),
+        }
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "lzma")]
+impl Level {
+    /// xz presets stop at 9, liblzma panics on higher values.
+    pub(crate) fn xz_preset(self) -> Result<u32, crate::Error> {
+        match u32::from(self) {
+            preset @ 0..=9 => Ok(preset),
+            _ => Err(crate::Error::InvalidLevel {
+                format: crate::Format::Lzma,
+                level: self,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl Level {
+    /// LZ4 frame levels below 3 are fast, HC levels stop at 12.
+    pub(crate) fn lz4_level(self) -> u32 {
+        u32::from(self).min(12)
+    }
+}
+
+#[cfg(feature = "brotli")]
+impl Level {
+    /// Brotli quality goes from 0 to 11.
+    pub(crate) fn brotli_quality(self) -> u32 {
+        u32::from(self).min(11)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -403,4 +461,24 @@ mod test {
         let tmp: bzip2::Compression = Level::TwentyOne.into();
         assert_eq!(tmp.level(), bzip2::Compression::new(9).level());
     }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn level2lz4() {
+        assert_eq!(Level::Zero.lz4_level(), 0);
+        assert_eq!(Level::Two.lz4_level(), 2);
+        assert_eq!(Level::Three.lz4_level(), 3);
+        assert_eq!(Level::Twelve.lz4_level(), 12);
+        assert_eq!(Level::Thirteen.lz4_level(), 12);
+        assert_eq!(Level::TwentyOne.lz4_level(), 12);
+    }
+
+    #[test]
+    #[cfg(feature = "brotli")]
+    fn level2brotli() {
+        assert_eq!(Level::Zero.brotli_quality(), 0);
+        assert_eq!(Level::Eleven.brotli_quality(), 11);
+        assert_eq!(Level::Twelve.brotli_quality(), 11);
+        assert_eq!(Level::TwentyOne.brotli_quality(), 11);
+    }
 }
@@ -2,15 +2,28 @@
 
 /* declare mod */
 pub mod basic;
+#[cfg(feature = "brotli")]
+mod brotli_encoder;
 pub mod error;
+#[cfg(feature = "gz")]
+mod flate;
+mod format;
+pub mod generic;
+mod lenient;
 pub mod level;
+#[cfg(feature = "lz4")]
+mod lz4_frame;
 pub mod seek;
 pub mod seeksend;
 pub mod send;
+#[cfg(feature = "snappy")]
+mod snappy_encoder;
+mod unix_compress;
 pub(crate) mod utils;
+mod zlib_check;
 
 /* reexport for convinent usage of niffler */
-pub use crate::basic::compression::Format;
 pub use crate::basic::*;
 pub use crate::error::Error;
+pub use crate::format::Format;
 pub use crate::level::Level;
@@ -0,0 +1,111 @@
+//! Decode LZ4 frames, [lz4::Decoder] stops after the first frame and ends truncated frames
+//! silently.
+
+/* standard use */
+use std::io;
+use std::io::BufRead;
+
+/// A LZ4 decoder which reads every concatenated frame and reports truncated frames as an
+/// [UnexpectedEof](io::ErrorKind::UnexpectedEof) error.
+pub(crate) struct Lz4Decoder<R: BufRead> {
+    state: State<R>,
+    multiple: bool,
+}
+
+enum State<R: BufRead> {
+    Frame(lz4::Decoder<R>),
+    // the previous frame ended, the next one isn't started
+    Between(R),
+    End(R),
+    // only while moving from a state to the next
+    Moving,
+}
+
+impl<R: BufRead> Lz4Decoder<R> {
+    /// Decode every frame of `source`.
+    pub(crate) fn new(source: R) -> io::Result<Self> {
+        Ok(Lz4Decoder {
+            state: State::Frame(lz4::Decoder::new(source)?),
+            multiple: true,
+        })
+    }
+
+    /// Stop after the first frame, the source is left at the end of this frame.
+    pub(crate) fn single_frame(mut self) -> Self {
+        self.multiple = false;
+        self
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        match &self.state {
+            State::Frame(decoder) => decoder.reader(),
+            State::Between(source) | State::End(source) => source,
+            State::Moving => unreachable!("a state is always set back"),
+        }
+    }
+
+    /// Return the compressed stream, data read ahead by the current frame decoder is lost.
+    pub(crate) fn into_inner(self) -> R {
+        match self.state {
+            State::Frame(decoder) => decoder.finish().0,
+            State::Between(source) | State::End(source) => source,
+            State::Moving => unreachable!("a state is always set back"),
+        }
+    }
+}
+
+impl<R: BufRead> io::Read for Lz4Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match &mut self.state {
+                State::Frame(decoder) => {
+                    let read = decoder.read(buf)?;
+                    if read != 0 {
+                        return Ok(read);
+                    }
+
+                    let State::Frame(decoder) = std::mem::replace(&mut self.state, State::Moving)
+                    else {
+                        unreachable!("the state is a frame");
+                    };
+                    let (source, end) = decoder.finish();
+
+                    if end.is_err() {
+                        self.state = State::End(source);
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+
+                    self.state = if self.multiple {
+                        State::Between(source)
+                    } else {
+                        State::End(source)
+                    };
+                }
+                State::Between(source) => {
+                    let more = !source.fill_buf()?.is_empty();
+
+                    let State::Between(source) = std::mem::replace(&mut self.state, State::Moving)
+                    else {
+                        unreachable!("the state is between frames");
+                    };
+
+                    self.state = if more {
+                        // the first frame decoder was created, only an allocation can fail
+                        State::Frame(
+                            lz4::Decoder::new(source)
+                                .expect("allocation of a LZ4 decompression context"),
+                        )
+                    } else {
+                        State::End(source)
+                    };
+                }
+                State::End(_) => return Ok(0),
+                State::Moving => unreachable!("a state is always set back"),
+            }
+        }
+    }
+}
@@ -0,0 +1,947 @@
+//! Random access to [BGZF](https://samtools.github.io/hts-specs/SAMv1.pdf) files.
+//!
+//! A BGZF file is a series of gzip members (blocks) holding at most 64 KiB of
+//! uncompressed data each, with the compressed size of the block stored in the
+//! `BC` extra subfield. [BgzfReader] uses this to build a block index and
+//! implements [Read](std::io::Read) and [Seek](std::io::Seek) over uncompressed
+//! offsets. It also understands htslib virtual offsets
+//! (`block_offset << 16 | offset_in_block`), as used by `.tbi`, `.csi` and `.bai` indexes.
+//!
+//! [BgzfWriter] produces BGZF files and can write the matching `.gzi` index while
+//! blocks are emitted.
+
+/* standard use */
+use std::io;
+use std::io::{BufRead, Read, Seek};
+
+/* project use */
+use crate::error::Error;
+use crate::format::Format;
+use crate::level::Level;
+
+/// Largest amount of uncompressed data a BGZF block can hold.
+pub const MAX_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Amount of uncompressed data written in each block by [BgzfWriter], same as htslib.
+pub const DEFAULT_BLOCK_SIZE: usize = 0xff00;
+
+/// Smallest possible BGZF block, its header with the `BC` subfield and the gzip footer.
+const MIN_BLOCK_SIZE: u64 = 18 + 8;
+
+/// Largest possible BGZF block, `BSIZE` is the block size minus one on 16 bits.
+const MAX_COMPRESSED_SIZE: u64 = 1 << 16;
+
+/// Empty block marking the end of a BGZF file.
+pub const EOF_MARKER: [u8; 28] = bgzip::EOF_MARKER;
+
+/// Build a htslib virtual offset from the compressed offset of a block and an
+/// offset inside the uncompressed block.
+pub fn virtual_offset(block_offset: u64, offset_in_block: u16) -> u64 {
+    (block_offset << 16) | u64::from(offset_in_block)
+}
+
+/// Position of one BGZF block in the compressed and uncompressed stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    /// Offset of the block header in the compressed file.
+    pub compressed_offset: u64,
+    /// Offset of the first byte of the block in the uncompressed data.
+    pub uncompressed_offset: u64,
+    /// Size of the block in the compressed file, header and footer included.
+    pub compressed_size: u64,
+    /// Number of bytes the block decompresses to.
+    pub uncompressed_size: u64,
+}
+
+/// List of all blocks of a BGZF file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BgzfIndex {
+    blocks: Vec<Block>,
+}
+
+impl BgzfIndex {
+    /// Build the index by walking the block headers of a BGZF file.
+    ///
+    /// Only headers and footers are read, no data is decompressed.
+    pub fn build<R: Read + Seek>(reader: &mut R) -> Result<Self, Error> {
+        let end = reader.seek(io::SeekFrom::End(0))?;
+
+        let mut blocks = Vec::new();
+        let mut compressed_offset = 0;
+        let mut uncompressed_offset = 0;
+        while compressed_offset < end {
+            let block = read_block_info(reader, compressed_offset, uncompressed_offset)?;
+
+            compressed_offset += block.compressed_size;
+            uncompressed_offset += block.uncompressed_size;
+            blocks.push(block);
+        }
+
+        reader.seek(io::SeekFrom::Start(0))?;
+
+        Ok(BgzfIndex { blocks })
+    }
+
+    /// Build the index from a `.gzi` index (as written by `bgzip -i`).
+    ///
+    /// `.gzi` files don't store the size of the last block, so it's read from `reader`.
+    /// Entries must strictly increase and describe blocks of a valid size.
+    pub fn from_gzi<G: Read, R: Read + Seek>(gzi: G, reader: &mut R) -> Result<Self, Error> {
+        let entries = bgzip::index::BGZFIndex::from_reader(gzi)?;
+        let end = reader.seek(io::SeekFrom::End(0))?;
+
+        let mut starts = vec![(0, 0)];
+        starts.extend(
+            entries
+                .entries()
+                .iter()
+                .map(|e| (e.compressed_offset, e.uncompressed_offset)),
+        );
+
+        let mut blocks = Vec::with_capacity(starts.len());
+        for (i, &(compressed_offset, uncompressed_offset)) in starts.iter().enumerate() {
+            if compressed_offset >= end {
+                break;
+            }
+
+            match starts.get(i + 1) {
+                Some(&(next_compressed, next_uncompressed)) => {
+                    let compressed_size = next_compressed
+                        .checked_sub(compressed_offset)
+                        .filter(|size| (MIN_BLOCK_SIZE..=MAX_COMPRESSED_SIZE).contains(size));
+                    let uncompressed_size = next_uncompressed
+                        .checked_sub(uncompressed_offset)
+                        .filter(|size| (1..=MAX_BLOCK_SIZE as u64).contains(size));
+                    let (Some(compressed_size), Some(uncompressed_size)) =
+                        (compressed_size, uncompressed_size)
+                    else {
+                        return Err(corrupt_block(compressed_offset));
+                    };
+
+                    blocks.push(Block {
+                        compressed_offset,
+                        uncompressed_offset,
+                        compressed_size,
+                        uncompressed_size,
+                    });
+                }
+                None => {
+                    // The last entry can be followed by more than one block (at least the EOF marker)
+                    let mut compressed_offset = compressed_offset;
+                    let mut uncompressed_offset = uncompressed_offset;
+                    while compressed_offset < end {
+                        let block =
+                            read_block_info(reader, compressed_offset, uncompressed_offset)?;
+                        compressed_offset += block.compressed_size;
+                        uncompressed_offset += block.uncompressed_size;
+                        blocks.push(block);
+                    }
+                }
+            }
+        }
+
+        reader.seek(io::SeekFrom::Start(0))?;
+
+        Ok(BgzfIndex { blocks })
+    }
+
+    /// Write the index in `.gzi` format.
+    pub fn write_gzi<W: io::Write>(&self, mut writer: W) -> Result<(), Error> {
+        let entries: Vec<&Block> = self
+            .blocks
+            .iter()
+            .skip(1)
+            .filter(|b| b.uncompressed_size != 0)
+            .collect();
+
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for block in entries {
+            writer.write_all(&block.compressed_offset.to_le_bytes())?;
+            writer.write_all(&block.uncompressed_offset.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// All blocks of the file, in order.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Size of the uncompressed data.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.blocks
+            .last()
+            .map(|b| b.uncompressed_offset + b.uncompressed_size)
+            .unwrap_or(0)
+    }
+
+    /// Convert an uncompressed offset to a virtual offset.
+    ///
+    /// Offsets past the end of the data are mapped to the end of the last block.
+    pub fn to_virtual(&self, pos: u64) -> u64 {
+        match self.block_for(pos) {
+            Some(i) => {
+                let block = &self.blocks[i];
+                virtual_offset(
+                    block.compressed_offset,
+                    (pos - block.uncompressed_offset) as u16,
+                )
+            }
+            None => virtual_offset(self.data_end(), 0),
+        }
+    }
+
+    /// Convert a virtual offset to an uncompressed offset.
+    pub fn from_virtual(&self, voffset: u64) -> Result<u64, Error> {
+        let block_offset = voffset >> 16;
+        let offset_in_block = voffset & 0xffff;
+
+        match self
+            .blocks
+            .binary_search_by_key(&block_offset, |b| b.compressed_offset)
+        {
+            Ok(i) if offset_in_block <= self.blocks[i].uncompressed_size => {
+                Ok(self.blocks[i].uncompressed_offset + offset_in_block)
+            }
+            Err(i) if i == self.blocks.len() && offset_in_block == 0 => Ok(self.uncompressed_len()),
+            // the virtual offset doesn't point inside a block
+            _ => Err(corrupt_block(block_offset)),
+        }
+    }
+
+    /// Index of the block holding the uncompressed offset `pos`, if any.
+    pub(crate) fn block_for(&self, pos: u64) -> Option<usize> {
+        let i = self
+            .blocks
+            .partition_point(|b| b.uncompressed_offset + b.uncompressed_size <= pos);
+
+        if i < self.blocks.len() {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    /// Compressed offset of the first block after the data (usually the EOF marker).
+    fn data_end(&self) -> u64 {
+        self.blocks
+            .iter()
+            .rev()
+            .find(|b| b.uncompressed_size != 0)
+            .map(|b| b.compressed_offset + b.compressed_size)
+            .unwrap_or(0)
+    }
+}
+
+fn corrupt_block(offset: u64) -> Error {
+    Error::Corrupt {
+        format: Format::BGzip,
+        offset,
+    }
+}
+
+fn read_block_info<R: Read + Seek>(
+    reader: &mut R,
+    compressed_offset: u64,
+    uncompressed_offset: u64,
+) -> Result<Block, Error> {
+    reader.seek(io::SeekFrom::Start(compressed_offset))?;
+    let header = bgzip::header::BGZFHeader::from_reader(reader)
+        .map_err(|_| corrupt_block(compressed_offset))?;
+    let compressed_size = u64::from(
+        header
+            .block_size()
+            .map_err(|_| corrupt_block(compressed_offset))?,
+    );
+    if compressed_size < MIN_BLOCK_SIZE {
+        // BSIZE doesn't even cover the header and footer
+        return Err(corrupt_block(compressed_offset));
+    }
+
+    reader.seek(io::SeekFrom::Start(compressed_offset + compressed_size - 4))?;
+    let mut isize = [0u8; 4];
+    reader.read_exact(&mut isize)?;
+    let uncompressed_size = u64::from(u32::from_le_bytes(isize));
+    if uncompressed_size > MAX_BLOCK_SIZE as u64 {
+        // blocks hold at most 64 KiB
+        return Err(corrupt_block(compressed_offset));
+    }
+
+    Ok(Block {
+        compressed_offset,
+        uncompressed_offset,
+        compressed_size,
+        uncompressed_size,
+    })
+}
+
+/// A BGZF reader with [Seek] support over uncompressed offsets.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "bgz")] {
+/// use std::io::{Read, Seek, Write};
+/// use niffler::seek::bgzf::{BgzfReader, BgzfWriter};
+///
+/// let mut writer = BgzfWriter::new(Vec::new(), niffler::Level::Six);
+/// writer.write_all(b"hello bgzf")?;
+///
+/// let mut reader = BgzfReader::new(std::io::Cursor::new(writer.finish()?))?;
+/// reader.seek(std::io::SeekFrom::Start(6))?;
+///
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents)?;
+/// assert_eq!(contents, "bgzf");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct BgzfReader<R: Read + Seek> {
+    reader: R,
+    index: BgzfIndex,
+    decompress: bgzip::deflate::Decompress,
+    compressed: Vec<u8>,
+    buffer: Vec<u8>,
+    current_block: Option<usize>,
+    pos: u64,
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    /// Open a BGZF stream, the block index is built by walking block headers.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let index = BgzfIndex::build(&mut reader)?;
+        Ok(Self::with_index(reader, index))
+    }
+
+    /// Open a BGZF stream with an already built index.
+    pub fn with_index(reader: R, index: BgzfIndex) -> Self {
+        BgzfReader {
+            reader,
+            index,
+            decompress: bgzip::deflate::Decompress::new(),
+            compressed: Vec::new(),
+            buffer: Vec::with_capacity(MAX_BLOCK_SIZE),
+            current_block: None,
+            pos: 0,
+        }
+    }
+
+    /// Block index of the stream.
+    pub fn index(&self) -> &BgzfIndex {
+        &self.index
+    }
+
+    /// Current position as a virtual offset.
+    pub fn virtual_offset(&self) -> u64 {
+        self.index.to_virtual(self.pos)
+    }
+
+    /// Move to a virtual offset, returns the new uncompressed position.
+    pub fn seek_virtual(&mut self, voffset: u64) -> Result<u64, Error> {
+        self.pos = self.index.from_virtual(voffset)?;
+        Ok(self.pos)
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn load_block(&mut self, i: usize) -> io::Result<()> {
+        if self.current_block == Some(i) {
+            return Ok(());
+        }
+
+        let block = self.index.blocks[i];
+        self.current_block = None;
+        self.reader
+            .seek(io::SeekFrom::Start(block.compressed_offset))?;
+        bgzip::read::load_block(&mut self.reader, &mut self.compressed)
+            .map_err(|e| e.into_io_error())?;
+
+        self.buffer.clear();
+        bgzip::read::decompress_block(&mut self.buffer, &self.compressed, &mut self.decompress)
+            .map_err(|e| e.into_io_error())?;
+        if self.buffer.len() as u64 != block.uncompressed_size {
+            // the index doesn't match the file, as with a wrong `.gzi`
+            return Err(corrupt_block(block.compressed_offset).into());
+        }
+        self.current_block = Some(i);
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> BufRead for BgzfReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let i = match self.index.block_for(self.pos) {
+            Some(i) => i,
+            None => return Ok(&[]),
+        };
+        self.load_block(i)?;
+
+        let block = self.index.blocks[i];
+        let start = (self.pos - block.uncompressed_offset) as usize;
+        self.buffer
+            .get(start..)
+            .ok_or_else(|| corrupt_block(block.compressed_offset).into())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<R: Read + Seek> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for BgzfReader<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(p) => {
+                self.pos = p;
+                return Ok(p);
+            }
+            io::SeekFrom::Current(p) => (self.pos, p),
+            io::SeekFrom::End(p) => (self.index.uncompressed_len(), p),
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(p) => {
+                self.pos = p;
+                Ok(p)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// A streaming BGZF reader, blocks are decompressed in order without an index.
+pub(crate) struct BgzfDecoder<R: BufRead> {
+    reader: R,
+    decompress: bgzip::deflate::Decompress,
+    compressed: Vec<u8>,
+    buffer: Vec<u8>,
+    pos: usize,
+    // the last block is empty, as the EOF marker
+    ended: bool,
+    // blocks aren't read once it returns true
+    stop: Option<fn(&mut R) -> io::Result<bool>>,
+}
+
+impl<R: BufRead> BgzfDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        BgzfDecoder {
+            reader,
+            decompress: bgzip::deflate::Decompress::new(),
+            compressed: Vec::new(),
+            buffer: Vec::with_capacity(MAX_BLOCK_SIZE),
+            pos: 0,
+            ended: false,
+            stop: None,
+        }
+    }
+
+    /// End the stream before the data for which `stop` returns true.
+    pub(crate) fn stop_at(mut self, stop: fn(&mut R) -> io::Result<bool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// The underlying stream.
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Return the underlying stream.
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> BufRead for BgzfDecoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // skip empty blocks, as the EOF marker
+        while self.pos >= self.buffer.len() {
+            let stop = match self.stop {
+                Some(stop) => stop(&mut self.reader)?,
+                None => false,
+            };
+            if stop || self.reader.fill_buf()?.is_empty() {
+                if !self.ended {
+                    return Err(Error::Truncated {
+                        format: Format::BGzip,
+                    }
+                    .into());
+                }
+                break;
+            }
+
+            bgzip::read::load_block(&mut self.reader, &mut self.compressed)
+                .map_err(|e| e.into_io_error())?;
+            self.buffer.clear();
+            self.pos = 0;
+            bgzip::read::decompress_block(&mut self.buffer, &self.compressed, &mut self.decompress)
+                .map_err(|e| e.into_io_error())?;
+            self.ended = self.buffer.is_empty();
+        }
+
+        Ok(&self.buffer[self.pos.min(self.buffer.len())..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+impl<R: BufRead> Read for BgzfDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+/// A BGZF writer.
+///
+/// Data is cut in blocks of [DEFAULT_BLOCK_SIZE] bytes, each compressed as a gzip member with
+/// the `BC` extra subfield, and the [EOF_MARKER] is written by [finish](BgzfWriter::finish)
+/// or when the writer is dropped. Errors on drop are ignored, call `finish` to get them.
+///
+/// With [with_gzi](BgzfWriter::with_gzi) each block is also recorded in a `.gzi` index
+/// as soon as it is written.
+///
+/// [Seek] is only implemented to report the current uncompressed position
+/// (`SeekFrom::Current(0)`), any other move returns an error.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "bgz")] {
+/// use std::io::Write;
+/// use niffler::seek::bgzf::BgzfWriter;
+///
+/// let mut writer = BgzfWriter::new(Vec::new(), niffler::Level::Six);
+/// writer.write_all(b"hello bgzf")?;
+/// let buffer = writer.finish()?;
+///
+/// assert!(buffer.ends_with(&niffler::seek::bgzf::EOF_MARKER));
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct BgzfWriter<W: io::Write, I: io::Write + Seek = std::fs::File> {
+    writer: Option<W>,
+    gzi: Option<GziWriter<I>>,
+    compress: bgzip::deflate::Compress,
+    block_size: usize,
+    buffer: Vec<u8>,
+    compressed: Vec<u8>,
+    compressed_pos: u64,
+    uncompressed_pos: u64,
+}
+
+/// Write `.gzi` entries as blocks are emitted, the entry count is patched on finish.
+struct GziWriter<I: io::Write + Seek> {
+    writer: I,
+    entries: u64,
+}
+
+impl<I: io::Write + Seek> GziWriter<I> {
+    fn new(mut writer: I) -> io::Result<Self> {
+        writer.write_all(&0u64.to_le_bytes())?;
+        Ok(GziWriter { writer, entries: 0 })
+    }
+
+    fn add(&mut self, compressed_offset: u64, uncompressed_offset: u64) -> io::Result<()> {
+        self.writer.write_all(&compressed_offset.to_le_bytes())?;
+        self.writer.write_all(&uncompressed_offset.to_le_bytes())?;
+        self.entries += 1;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let end = self.writer.stream_position()?;
+        self.writer.seek(io::SeekFrom::Start(0))?;
+        self.writer.write_all(&self.entries.to_le_bytes())?;
+        self.writer.seek(io::SeekFrom::Start(end))?;
+        self.writer.flush()
+    }
+}
+
+impl<W: io::Write> BgzfWriter<W> {
+    /// Create a BGZF writer with the given compression level.
+    pub fn new(writer: W, level: Level) -> Self {
+        BgzfWriter::build(writer, None, level, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Create a BGZF writer, blocks hold at most `block_size` bytes of uncompressed data.
+    pub fn with_block_size(writer: W, level: Level, block_size: usize) -> Result<Self, Error> {
+        check_block_size(block_size)?;
+        Ok(BgzfWriter::build(writer, None, level, block_size))
+    }
+}
+
+impl<W: io::Write, I: io::Write + Seek> BgzfWriter<W, I> {
+    /// Create a BGZF writer that also writes a `.gzi` index in `gzi`.
+    pub fn with_gzi(writer: W, gzi: I, level: Level) -> Result<Self, Error> {
+        Ok(BgzfWriter::build(
+            writer,
+            Some(GziWriter::new(gzi)?),
+            level,
+            DEFAULT_BLOCK_SIZE,
+        ))
+    }
+
+    fn build(writer: W, gzi: Option<GziWriter<I>>, level: Level, block_size: usize) -> Self {
+        BgzfWriter {
+            writer: Some(writer),
+            gzi,
+            compress: bgzip::deflate::Compress::new(level.into()),
+            block_size,
+            buffer: Vec::with_capacity(block_size),
+            compressed: Vec::with_capacity(MAX_BLOCK_SIZE),
+            compressed_pos: 0,
+            uncompressed_pos: 0,
+        }
+    }
+
+    /// Change the amount of uncompressed data stored in each following block.
+    pub fn set_block_size(&mut self, block_size: usize) -> Result<(), Error> {
+        check_block_size(block_size)?;
+        self.block_size = block_size;
+        Ok(())
+    }
+
+    /// Current position as a virtual offset, can be used to build `.tbi` or `.csi` indexes.
+    pub fn virtual_offset(&self) -> u64 {
+        virtual_offset(self.compressed_pos, self.buffer.len() as u16)
+    }
+
+    /// Number of uncompressed bytes written so far.
+    pub fn position(&self) -> u64 {
+        self.uncompressed_pos + self.buffer.len() as u64
+    }
+
+    /// Write the pending block, the EOF marker and the `.gzi` entry count,
+    /// then return the underlying stream.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.try_finish()?;
+        Ok(self.writer.take().expect("writer is only taken by finish"))
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+
+        self.write_block()?;
+        let writer = self
+            .writer
+            .as_mut()
+            .expect("writer is only taken by finish");
+        writer.write_all(&EOF_MARKER)?;
+        writer.flush()?;
+        self.compressed_pos += EOF_MARKER.len() as u64;
+
+        if let Some(gzi) = self.gzi.as_mut() {
+            gzi.finish()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.compressed.clear();
+        bgzip::write::write_block(&mut self.compressed, &self.buffer, &mut self.compress)
+            .map_err(io::Error::other)?;
+
+        self.writer
+            .as_mut()
+            .expect("writer is only taken by finish")
+            .write_all(&self.compressed)?;
+        if self.compressed_pos != 0 {
+            if let Some(gzi) = self.gzi.as_mut() {
+                gzi.add(self.compressed_pos, self.uncompressed_pos)?;
+            }
+        }
+
+        self.compressed_pos += self.compressed.len() as u64;
+        self.uncompressed_pos += self.buffer.len() as u64;
+        self.buffer.clear();
+
+        Ok(())
+    }
+}
+
+fn check_block_size(block_size: usize) -> Result<(), Error> {
+    if block_size == 0 || block_size > DEFAULT_BLOCK_SIZE {
+        return Err(Error::InvalidBlockSize {
+            format: Format::BGzip,
+            size: block_size,
+        });
+    }
+
+    Ok(())
+}
+
+impl<W: io::Write, I: io::Write + Seek> io::Write for BgzfWriter<W, I> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() >= self.block_size {
+            self.write_block()?;
+        }
+
+        let len = buf.len().min(self.block_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: io::Write, I: io::Write + Seek> Seek for BgzfWriter<W, I> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let current = self.position();
+        match pos {
+            io::SeekFrom::Current(0) => Ok(current),
+            io::SeekFrom::Start(p) if p == current => Ok(current),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "BGZF writer can only report its position",
+            )),
+        }
+    }
+}
+
+impl<W: io::Write, I: io::Write + Seek> Drop for BgzfWriter<W, I> {
+    fn drop(&mut self) {
+        let _ = self.try_finish();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn bgzf(data: &[u8], block_size: usize) -> Vec<u8> {
+        let mut writer = BgzfWriter::with_block_size(Vec::new(), Level::Six, block_size).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn data() -> Vec<u8> {
+        (0..10_000u32).flat_map(|i| i.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn index() {
+        let data = data();
+        let mut file = io::Cursor::new(bgzf(&data, 1000));
+
+        let index = BgzfIndex::build(&mut file).unwrap();
+        // 40 data blocks and the EOF marker
+        assert_eq!(index.blocks().len(), 41);
+        assert_eq!(index.uncompressed_len(), data.len() as u64);
+        assert_eq!(index.blocks()[3].uncompressed_offset, 3000);
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut compressed = bgzf(&data(), 1000);
+        let third = BgzfIndex::build(&mut io::Cursor::new(&compressed))
+            .unwrap()
+            .blocks()[2];
+        // BC subfield identifier
+        compressed[third.compressed_offset as usize + 12] ^= 0xff;
+
+        assert!(matches!(
+            BgzfIndex::build(&mut io::Cursor::new(compressed)),
+            Err(Error::Corrupt {
+                format: Format::BGzip,
+                offset,
+            }) if offset == third.compressed_offset
+        ));
+    }
+
+    #[test]
+    fn block_size_too_small() {
+        let compressed = bgzf(&data(), 1000);
+        let third = BgzfIndex::build(&mut io::Cursor::new(&compressed))
+            .unwrap()
+            .blocks()[2];
+
+        // BSIZE of 0, the block would be a single byte long
+        for offset in [0, third.compressed_offset] {
+            let mut corrupted = compressed.clone();
+            corrupted[offset as usize + 16..][..2].copy_from_slice(&[0, 0]);
+
+            assert!(matches!(
+                BgzfIndex::build(&mut io::Cursor::new(corrupted)),
+                Err(Error::Corrupt {
+                    format: Format::BGzip,
+                    offset: o,
+                }) if o == offset
+            ));
+        }
+    }
+
+    #[test]
+    fn seek() {
+        let data = data();
+        let mut reader = BgzfReader::new(io::Cursor::new(bgzf(&data, 1000))).unwrap();
+
+        let mut buf = [0u8; 2500];
+        for pos in [0u64, 999, 1000, 12345, 37500, 39999, 38000, 5] {
+            reader.seek(io::SeekFrom::Start(pos)).unwrap();
+            let len = buf.len().min(data.len() - pos as usize);
+            reader.read_exact(&mut buf[..len]).unwrap();
+            assert_eq!(&buf[..len], &data[pos as usize..pos as usize + len]);
+        }
+
+        assert_eq!(reader.seek(io::SeekFrom::End(-4)).unwrap(), 39996);
+        let mut end = Vec::new();
+        reader.read_to_end(&mut end).unwrap();
+        assert_eq!(end, 9999u32.to_le_bytes());
+
+        assert!(reader.seek(io::SeekFrom::Current(-50_000)).is_err());
+    }
+
+    #[test]
+    fn virtual_offsets() {
+        let data = data();
+        let mut reader = BgzfReader::new(io::Cursor::new(bgzf(&data, 1000))).unwrap();
+        let third = reader.index().blocks()[2];
+
+        reader.seek(io::SeekFrom::Start(2010)).unwrap();
+        let voffset = reader.virtual_offset();
+        assert_eq!(voffset, virtual_offset(third.compressed_offset, 10));
+
+        reader.seek(io::SeekFrom::Start(0)).unwrap();
+        assert_eq!(reader.seek_virtual(voffset).unwrap(), 2010);
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &data[2010..2014]);
+
+        assert!(matches!(
+            reader.seek_virtual(virtual_offset(third.compressed_offset + 1, 0)),
+            Err(Error::Corrupt {
+                format: Format::BGzip,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn gzi() {
+        let data = data();
+        let mut file = io::Cursor::new(bgzf(&data, 1000));
+        let index = BgzfIndex::build(&mut file).unwrap();
+
+        let mut gzi = Vec::new();
+        index.write_gzi(&mut gzi).unwrap();
+
+        assert_eq!(BgzfIndex::from_gzi(&gzi[..], &mut file).unwrap(), index);
+    }
+
+    #[test]
+    fn corrupted_gzi() {
+        let data = data();
+        let mut file = io::Cursor::new(bgzf(&data, 1000));
+        let mut gzi = Vec::new();
+        BgzfIndex::build(&mut file)
+            .unwrap()
+            .write_gzi(&mut gzi)
+            .unwrap();
+
+        // the second and third entries swapped, offsets decrease
+        let mut swapped = gzi.clone();
+        swapped[24..40].copy_from_slice(&gzi[40..56]);
+        swapped[40..56].copy_from_slice(&gzi[24..40]);
+        assert!(matches!(
+            BgzfIndex::from_gzi(&swapped[..], &mut file),
+            Err(Error::Corrupt {
+                format: Format::BGzip,
+                ..
+            })
+        ));
+
+        // the second block is said to hold 1500 bytes instead of 1000
+        let mut shifted = gzi.clone();
+        shifted[32..40].copy_from_slice(&2500u64.to_le_bytes());
+        let index = BgzfIndex::from_gzi(&shifted[..], &mut file).unwrap();
+        let mut reader = BgzfReader::with_index(file, index);
+        reader.seek(io::SeekFrom::Start(2200)).unwrap();
+        let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn writer_virtual_offsets() {
+        let mut writer = BgzfWriter::with_block_size(Vec::new(), Level::One, 100).unwrap();
+        let mut offsets = Vec::new();
+        for i in 0..100 {
+            offsets.push((writer.virtual_offset(), writer.position(), i));
+            writeln!(writer, "line {i}").unwrap();
+        }
+        let buffer = writer.finish().unwrap();
+        assert!(buffer.ends_with(&EOF_MARKER));
+
+        let mut reader = BgzfReader::new(io::Cursor::new(buffer)).unwrap();
+        let mut line = String::new();
+        for (voffset, pos, i) in offsets.into_iter().rev() {
+            assert_eq!(reader.seek_virtual(voffset).unwrap(), pos);
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(line, format!("line {i}\n"));
+        }
+    }
+
+    #[test]
+    fn writer_gzi() {
+        let data = data();
+        let mut gzi = io::Cursor::new(Vec::new());
+        let mut writer = BgzfWriter::with_gzi(Vec::new(), &mut gzi, Level::One).unwrap();
+        writer.set_block_size(1000).unwrap();
+        writer.write_all(&data).unwrap();
+        let mut file = io::Cursor::new(writer.finish().unwrap());
+
+        let index = BgzfIndex::from_gzi(&gzi.get_ref()[..], &mut file).unwrap();
+        assert_eq!(index, BgzfIndex::build(&mut file).unwrap());
+        assert_eq!(index.uncompressed_len(), data.len() as u64);
+
+        assert!(matches!(
+            BgzfWriter::with_block_size(Vec::new(), Level::One, 0),
+            Err(Error::InvalidBlockSize { size: 0, .. })
+        ));
+        assert!(BgzfWriter::with_block_size(Vec::new(), Level::One, MAX_BLOCK_SIZE).is_err());
+    }
+}
@@ -0,0 +1,693 @@
+//! Random access to bzip2 files.
+//!
+//! bzip2 compresses data in independent blocks of at most 900 kB, each starting with the 48 bits
+//! magic number `0x314159265359`. Blocks aren't aligned on bytes, so [Bzip2Index::build] scans
+//! the file bit by bit for this magic number and decodes each block once to get its size.
+//! [Bzip2Reader] then implements [Seek] by decoding only the block holding the target offset.
+//!
+//! Building the index reads the whole file, it can be saved next to it (`.bz2idx`) and loaded by
+//! [from_path].
+
+/* standard use */
+use std::io;
+use std::io::{BufRead, Read, Seek, Write};
+use std::path::Path;
+
+/* project use */
+use crate::error::Error;
+use crate::level::Level;
+use crate::seek::compression::ReadSeek;
+
+/// Suffix of the index file saved next to the bzip2 file.
+pub const INDEX_SUFFIX: &str = ".bz2idx";
+
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+const EOS_MAGIC: u64 = 0x1772_4538_5090;
+const MAGIC_MASK: u64 = (1 << 48) - 1;
+const INDEX_MAGIC: &[u8; 8] = b"BZ2IDX\x00\x01";
+/// Magic numbers found inside the compressed data of a block before giving up. Each failed
+/// match decodes the block again, in real files a match is a 2^-48 event for each bit.
+const MAX_FALSE_MATCHES: usize = 8;
+
+/// Position of one block in the compressed and uncompressed stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    /// Offset, in bits, of the block magic number in the compressed file.
+    pub start_bit: u64,
+    /// Offset, in bits, of the end of the block in the compressed file.
+    pub end_bit: u64,
+    /// Offset of the first byte of the block in the uncompressed stream.
+    pub uncompressed_offset: u64,
+    /// Size of the uncompressed data of the block.
+    pub uncompressed_size: u64,
+}
+
+/// Block index of a bzip2 file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bzip2Index {
+    blocks: Vec<Block>,
+}
+
+impl Bzip2Index {
+    /// Scan a bzip2 stream for blocks and decode each of them to get its size.
+    ///
+    /// The block magic number can also appear inside compressed data, such a block fails to
+    /// decode and is merged with the next one. A block holding more than a few of these is
+    /// rejected, so the scan stays linear.
+    pub fn build<R: Read>(reader: R) -> Result<Self, Error> {
+        let mut reader = io::BufReader::new(reader);
+        let mut index = Bzip2Index::default();
+
+        // bytes of the current block, starting with the byte holding its first bit
+        let mut pending: Vec<u8> = Vec::new();
+        let mut start: Option<u64> = None;
+        let mut false_matches = 0;
+        let mut buffer = Vec::new();
+        let mut register = 0u64;
+        let mut position = 0u64;
+
+        loop {
+            let bytes = reader.fill_buf()?;
+            if bytes.is_empty() {
+                break;
+            }
+            let len = bytes.len();
+
+            for &byte in bytes {
+                pending.push(byte);
+                for shift in (0..8).rev() {
+                    register = (register << 1) | u64::from((byte >> shift) & 1);
+                    position += 1;
+
+                    let magic = register & MAGIC_MASK;
+                    if position < 48 || (magic != BLOCK_MAGIC && magic != EOS_MAGIC) {
+                        continue;
+                    }
+                    let marker = position - 48;
+
+                    if let Some(block_start) = start {
+                        let first = block_start % 8;
+                        buffer.clear();
+                        let decoded =
+                            decode_block(&pending, first, marker - block_start, &mut buffer);
+
+                        if decoded.is_err() {
+                            // magic number inside compressed data, try with the next one
+                            false_matches += 1;
+                            if false_matches > MAX_FALSE_MATCHES {
+                                return Err(
+                                    corrupt("too many block magic numbers in a block").into()
+                                );
+                            }
+                            continue;
+                        }
+                        false_matches = 0;
+
+                        index.push(block_start, marker, buffer.len() as u64);
+                    }
+
+                    if magic == BLOCK_MAGIC {
+                        start = Some(marker);
+                        // keep the bytes from the one holding the magic number
+                        let keep = (position.div_ceil(8) - marker / 8) as usize;
+                        pending.drain(..pending.len() - keep);
+                    } else {
+                        start = None;
+                        pending.clear();
+                    }
+                }
+            }
+
+            reader.consume(len);
+        }
+
+        if start.is_some() {
+            return Err(corrupt("can't decode the last block").into());
+        }
+
+        Ok(index)
+    }
+
+    /// Read an index written by [write_to](Bzip2Index::write_to).
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(corrupt("not a bzip2 block index").into());
+        }
+
+        let mut index = Bzip2Index::default();
+        for _ in 0..read_u64(&mut reader)? {
+            let start_bit = read_u64(&mut reader)?;
+            let end_bit = read_u64(&mut reader)?;
+            let uncompressed_size = read_u64(&mut reader)?;
+
+            // blocks are sorted and don't overlap
+            let previous_end = index.blocks.last().map_or(0, |b| b.end_bit);
+            if end_bit <= start_bit
+                || start_bit < previous_end
+                || index
+                    .uncompressed_len()
+                    .checked_add(uncompressed_size)
+                    .is_none()
+            {
+                return Err(corrupt("invalid block in index").into());
+            }
+            index.push(start_bit, end_bit, uncompressed_size);
+        }
+
+        Ok(index)
+    }
+
+    /// Read an index from a file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        Self::from_reader(io::BufReader::new(crate::utils::open(path)?))
+            .map_err(|e| e.with_path(path))
+    }
+
+    /// Write the index.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&(self.blocks.len() as u64).to_le_bytes())?;
+        for block in &self.blocks {
+            writer.write_all(&block.start_bit.to_le_bytes())?;
+            writer.write_all(&block.end_bit.to_le_bytes())?;
+            writer.write_all(&block.uncompressed_size.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the index to a file.
+    pub fn to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut writer = io::BufWriter::new(crate::utils::create(path)?);
+        self.write_to(&mut writer)
+            .and_then(|()| Ok(writer.flush()?))
+            .map_err(|e| e.with_path(path))
+    }
+
+    /// Blocks of the file, in order.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Length of the uncompressed stream.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.blocks
+            .last()
+            .map(|b| b.uncompressed_offset + b.uncompressed_size)
+            .unwrap_or(0)
+    }
+
+    /// Index of the block holding uncompressed offset `pos`.
+    fn block_for(&self, pos: u64) -> Option<usize> {
+        let i = self
+            .blocks
+            .partition_point(|b| b.uncompressed_offset + b.uncompressed_size <= pos);
+        (i < self.blocks.len()).then_some(i)
+    }
+
+    fn push(&mut self, start_bit: u64, end_bit: u64, uncompressed_size: u64) {
+        let uncompressed_offset = self.uncompressed_len();
+        self.blocks.push(Block {
+            start_bit,
+            end_bit,
+            uncompressed_offset,
+            uncompressed_size,
+        });
+    }
+}
+
+/// Append bits, most significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u64, count: u32) {
+        for i in (0..count).rev() {
+            if self.bits % 8 == 0 {
+                self.bytes.push(0);
+            }
+            let last = self.bytes.len() - 1;
+            self.bytes[last] |= (((value >> i) & 1) as u8) << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+    }
+
+    /// Append `len` bits of `bytes` starting at bit `first` of `bytes[0]`, must be aligned.
+    fn extend(&mut self, bytes: &[u8], first: u64, len: u64) {
+        let shift = first as u32;
+        let whole = (len / 8) as usize;
+        self.bytes.extend((0..whole).map(|i| {
+            let high = bytes[i] << shift;
+            let low = match (shift, bytes.get(i + 1)) {
+                (0, _) | (_, None) => 0,
+                (_, Some(next)) => next >> (8 - shift),
+            };
+            high | low
+        }));
+        self.bits += whole as u64 * 8;
+
+        for bit in first + whole as u64 * 8..first + len {
+            let byte = bytes[(bit / 8) as usize];
+            self.push(u64::from((byte >> (7 - bit % 8)) & 1), 1);
+        }
+    }
+}
+
+/// Decode the block of `len` bits starting at bit `first` of `bytes`, by wrapping it in a stream.
+fn decode_block(bytes: &[u8], first: u64, len: u64, out: &mut Vec<u8>) -> io::Result<()> {
+    if len < 80 {
+        return Err(corrupt("block is too short"));
+    }
+
+    let mut stream = BitWriter {
+        bytes: Vec::with_capacity((len / 8) as usize + 20),
+        bits: 0,
+    };
+    // the largest block size, any block can be decoded
+    stream.push(u64::from(u32::from_be_bytes(*b"BZh9")), 32);
+    stream.extend(bytes, first, len);
+    stream.push(EOS_MAGIC, 48);
+
+    // a single block stream has the block crc as stream crc
+    let mut crc = BitWriter {
+        bytes: Vec::with_capacity(5),
+        bits: 0,
+    };
+    crc.extend(&bytes[((first + 48) / 8) as usize..], (first + 48) % 8, 32);
+    stream.push(
+        u64::from(u32::from_be_bytes(
+            crc.bytes[..4].try_into().expect("4 bytes"),
+        )),
+        32,
+    );
+
+    bzip2::read::BzDecoder::new(&stream.bytes[..]).read_to_end(out)?;
+
+    Ok(())
+}
+
+/// A bzip2 reader with [Seek] support over uncompressed offsets.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "bz2")] {
+/// use std::io::{Read, Seek, Write};
+/// use niffler::seek::bz2::Bzip2Reader;
+///
+/// let mut writer = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::best());
+/// writer.write_all(b"hello bzip2")?;
+///
+/// let mut reader = Bzip2Reader::new(std::io::Cursor::new(writer.finish()?))?;
+/// reader.seek(std::io::SeekFrom::Start(6))?;
+///
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents)?;
+/// assert_eq!(contents, "bzip2");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Bzip2Reader<R: Read + Seek> {
+    reader: R,
+    index: Bzip2Index,
+    compressed: Vec<u8>,
+    buffer: Vec<u8>,
+    current_block: Option<usize>,
+    pos: u64,
+}
+
+impl<R: Read + Seek> Bzip2Reader<R> {
+    /// Open a bzip2 stream, the block index is built by decoding the whole stream.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let index = Bzip2Index::build(&mut reader)?;
+        reader.seek(io::SeekFrom::Start(0))?;
+
+        Ok(Self::with_index(reader, index))
+    }
+
+    /// Open a bzip2 stream with an already built index.
+    pub fn with_index(reader: R, index: Bzip2Index) -> Self {
+        Bzip2Reader {
+            reader,
+            index,
+            compressed: Vec::new(),
+            buffer: Vec::new(),
+            current_block: None,
+            pos: 0,
+        }
+    }
+
+    /// Block index of the stream.
+    pub fn index(&self) -> &Bzip2Index {
+        &self.index
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn load_block(&mut self, i: usize) -> io::Result<()> {
+        if self.current_block == Some(i) {
+            return Ok(());
+        }
+
+        let block = self.index.blocks[i];
+        self.current_block = None;
+        self.reader.seek(io::SeekFrom::Start(block.start_bit / 8))?;
+        self.compressed.resize(
+            (block.end_bit.div_ceil(8) - block.start_bit / 8) as usize,
+            0,
+        );
+        self.reader.read_exact(&mut self.compressed)?;
+
+        self.buffer.clear();
+        decode_block(
+            &self.compressed,
+            block.start_bit % 8,
+            block.end_bit - block.start_bit,
+            &mut self.buffer,
+        )?;
+        if self.buffer.len() as u64 != block.uncompressed_size {
+            return Err(corrupt(&format!("block {i} size doesn't match the index")));
+        }
+        self.current_block = Some(i);
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> BufRead for Bzip2Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let i = match self.index.block_for(self.pos) {
+            Some(i) => i,
+            None => return Ok(&[]),
+        };
+        self.load_block(i)?;
+
+        let start = (self.pos - self.index.blocks[i].uncompressed_offset) as usize;
+        Ok(&self.buffer[start..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<R: Read + Seek> Read for Bzip2Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for Bzip2Reader<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(p) => {
+                self.pos = p;
+                return Ok(p);
+            }
+            io::SeekFrom::Current(p) => (self.pos, p),
+            io::SeekFrom::End(p) => (self.index.uncompressed_len(), p),
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(p) => {
+                self.pos = p;
+                Ok(p)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// A bzip2 writer, any bzip2 file can be read by [Bzip2Reader].
+///
+/// The block size is set by the compression level (100 kB per level). [Seek] is only
+/// implemented to report the current uncompressed position (`SeekFrom::Current(0)`), any other
+/// move returns an error.
+pub struct Bzip2Writer<W: Write> {
+    encoder: bzip2::write::BzEncoder<W>,
+    position: u64,
+}
+
+impl<W: Write> Bzip2Writer<W> {
+    /// Create a bzip2 writer with the given compression level.
+    pub fn new(writer: W, level: Level) -> Self {
+        Bzip2Writer {
+            encoder: bzip2::write::BzEncoder::new(writer, level.into()),
+            position: 0,
+        }
+    }
+
+    /// Number of uncompressed bytes written so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Write the end of the stream, then return the underlying stream.
+    pub fn finish(self) -> Result<W, Error> {
+        Ok(self.encoder.finish()?)
+    }
+}
+
+impl<W: Write> Write for Bzip2Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.encoder.write(buf)?;
+        self.position += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+impl<W: Write> Seek for Bzip2Writer<W> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match pos {
+            io::SeekFrom::Current(0) => Ok(self.position),
+            io::SeekFrom::Start(p) if p == self.position => Ok(self.position),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "bzip2 writer can only report its position",
+            )),
+        }
+    }
+}
+
+/// Open a bzip2 file with the index saved next to it (`path` with a [INDEX_SUFFIX] suffix),
+/// the index is built in memory if this file doesn't exist.
+pub fn from_path<'a, P: AsRef<Path>>(path: P) -> Result<Box<dyn ReadSeek + 'a>, Error> {
+    let path = path.as_ref();
+    let index_path = index_path(path);
+    let mut readable = io::BufReader::new(crate::utils::open(path)?);
+
+    if index_path.exists() {
+        let index = Bzip2Index::from_path(index_path)?;
+
+        let len = readable.seek(io::SeekFrom::End(0))?;
+        readable.seek(io::SeekFrom::Start(0))?;
+        if index
+            .blocks
+            .last()
+            .is_some_and(|b| b.end_bit.div_ceil(8) > len)
+        {
+            return Err(Error::from(corrupt("block index doesn't match the file")).with_path(path));
+        }
+
+        Ok(Box::new(Bzip2Reader::with_index(readable, index)))
+    } else {
+        let index = Bzip2Index::build(&mut readable).map_err(|e| e.with_path(path))?;
+        readable.seek(io::SeekFrom::Start(0))?;
+        Ok(Box::new(Bzip2Reader::with_index(readable, index)))
+    }
+}
+
+/// Build the index of a bzip2 file and save it next to the file, where [from_path] will find it.
+pub fn write_index<P: AsRef<Path>>(path: P) -> Result<Bzip2Index, Error> {
+    let path = path.as_ref();
+    let index = Bzip2Index::build(crate::utils::open(path)?).map_err(|e| e.with_path(path))?;
+    index.to_path(index_path(path))?;
+
+    Ok(index)
+}
+
+fn index_path(path: &Path) -> std::path::PathBuf {
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(INDEX_SUFFIX);
+    index_path.into()
+}
+
+fn corrupt(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid bzip2 file: {msg}"),
+    )
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn data() -> Vec<u8> {
+        (0..20_000)
+            .flat_map(|i| format!("line {i:05} of a bzip2 file\n").into_bytes())
+            .collect()
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut writer = Bzip2Writer::new(Vec::new(), Level::One);
+        writer.write_all(data).unwrap();
+        assert_eq!(writer.position(), data.len() as u64);
+        assert_eq!(writer.stream_position().unwrap(), data.len() as u64);
+        assert!(writer.seek(io::SeekFrom::Start(0)).is_err());
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn multi_block() {
+        let data = data();
+        let compressed = compress(&data);
+
+        let mut reader = Bzip2Reader::new(io::Cursor::new(&compressed)).unwrap();
+        assert!(reader.index().blocks().len() >= 5);
+        assert_eq!(reader.index().uncompressed_len(), data.len() as u64);
+
+        for pos in [0, 99_999, 100_000, 250_123, data.len() as u64 - 3] {
+            reader.seek(io::SeekFrom::Start(pos)).unwrap();
+            let mut buffer = [0u8; 3];
+            reader.read_exact(&mut buffer).unwrap();
+            assert_eq!(&buffer, &data[pos as usize..pos as usize + 3]);
+        }
+
+        reader.seek(io::SeekFrom::End(-10)).unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, &data[data.len() - 10..]);
+
+        assert!(reader.seek(io::SeekFrom::Current(-100_000_000)).is_err());
+    }
+
+    #[test]
+    fn multi_stream() {
+        let data = data();
+        let mut compressed = compress(&data[..150_000]);
+        compressed.extend(compress(&data[150_000..]));
+
+        let mut reader = Bzip2Reader::new(io::Cursor::new(compressed)).unwrap();
+        reader.seek(io::SeekFrom::Start(149_990)).unwrap();
+        let mut buffer = [0u8; 20];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &data[149_990..150_010]);
+
+        reader.rewind().unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, data);
+    }
+
+    #[test]
+    fn sidecar() {
+        let data = data();
+        let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+        ofile.write_all(&compress(&data)).unwrap();
+
+        let index = write_index(ofile.path()).unwrap();
+        let sidecar = index_path(ofile.path());
+        assert_eq!(Bzip2Index::from_path(&sidecar).unwrap(), index);
+
+        let mut reader = from_path(ofile.path()).unwrap();
+        reader.seek(io::SeekFrom::Start(200_000)).unwrap();
+        let mut buffer = [0u8; 32];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &data[200_000..200_032]);
+
+        std::fs::remove_file(&sidecar).unwrap();
+        assert!(Bzip2Index::from_reader(&b"not an index"[..]).is_err());
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut compressed = compress(&data());
+        let middle = compressed.len() / 2;
+        compressed[middle] ^= 0xff;
+
+        assert!(Bzip2Reader::new(io::Cursor::new(&compressed)).is_err());
+        assert!(Bzip2Reader::new(io::Cursor::new(&compressed[..middle])).is_err());
+    }
+
+    #[test]
+    fn false_matches() {
+        let magic = &BLOCK_MAGIC.to_be_bytes()[2..];
+        let mut compressed = b"BZh9".to_vec();
+        for _ in 0..MAX_FALSE_MATCHES + 2 {
+            compressed.extend(magic);
+            compressed.extend([0x55; 16]);
+        }
+        compressed.extend(&EOS_MAGIC.to_be_bytes()[2..]);
+        compressed.extend([0; 4]);
+
+        let error = Bzip2Index::build(io::Cursor::new(&compressed)).unwrap_err();
+        assert!(error.to_string().contains("too many block magic numbers"));
+    }
+
+    #[test]
+    fn corrupted_sidecar() {
+        let compressed = compress(&data());
+        let index = Bzip2Index::build(io::Cursor::new(&compressed)).unwrap();
+        let serialize = |blocks: &[Block]| {
+            let mut buffer = INDEX_MAGIC.to_vec();
+            buffer.extend((blocks.len() as u64).to_le_bytes());
+            for block in blocks {
+                buffer.extend(block.start_bit.to_le_bytes());
+                buffer.extend(block.end_bit.to_le_bytes());
+                buffer.extend(block.uncompressed_size.to_le_bytes());
+            }
+            buffer
+        };
+
+        // unsorted blocks
+        let mut blocks = index.blocks().to_vec();
+        blocks.swap(0, 1);
+        assert!(Bzip2Index::from_reader(&serialize(&blocks)[..]).is_err());
+
+        // uncompressed offsets overflow
+        let mut blocks = index.blocks().to_vec();
+        blocks[1].uncompressed_size = u64::MAX;
+        assert!(Bzip2Index::from_reader(&serialize(&blocks)[..]).is_err());
+
+        // blocks past the end of the file
+        let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+        ofile
+            .write_all(&compressed[..compressed.len() / 2])
+            .unwrap();
+        let sidecar = index_path(ofile.path());
+        std::fs::write(&sidecar, serialize(index.blocks())).unwrap();
+        assert!(from_path(ofile.path()).is_err());
+        std::fs::remove_file(&sidecar).unwrap();
+    }
+}
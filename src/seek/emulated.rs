@@ -0,0 +1,316 @@
+//! Emulated [Seek] for streaming formats.
+//!
+//! Gzip, bzip2, xz or zstd files without an index can't be randomly accessed, but some
+//! algorithms only need to rewind or skip ahead. [EmulatedSeek] reads a stream with
+//! [get_reader](crate::get_reader) and implements [Seek] on top of it: forward seeks decompress
+//! and discard data, backward seeks reopen the source and decompress it again from the start.
+//!
+//! The last decompressed bytes can be kept in memory with
+//! [with_history](EmulatedSeek::with_history), backward seeks inside this window don't reopen the
+//! source.
+
+/* standard use */
+use std::io;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+/* project use */
+use crate::basic::compression::Format;
+use crate::error::Error;
+use crate::seek::compression::ReadSeek;
+
+type Opener<'a> = Box<dyn FnMut() -> io::Result<Box<dyn Read + 'a>> + 'a>;
+
+/// A decompressed stream with [Seek] emulated by decompressing again.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "gz")] {
+/// use std::io::{Read, Seek, Write};
+/// use niffler::seek::emulated::EmulatedSeek;
+///
+/// let mut buffer = Vec::new();
+/// {
+///     let mut writer = niffler::get_writer(Box::new(&mut buffer), niffler::Format::Gzip, niffler::Level::Six)?;
+///     writer.write_all(b"first pass, second pass")?;
+/// }
+///
+/// let mut reader = EmulatedSeek::new(move || Ok(std::io::Cursor::new(buffer.clone())))?;
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents)?;
+///
+/// reader.seek(std::io::SeekFrom::Start(12))?;
+/// contents.clear();
+/// reader.read_to_string(&mut contents)?;
+///
+/// assert_eq!(reader.format(), niffler::Format::Gzip);
+/// assert_eq!(contents, "second pass");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct EmulatedSeek<'a> {
+    open: Opener<'a>,
+    reader: Box<dyn Read + 'a>,
+    format: Format,
+    history: Vec<u8>,
+    history_size: usize,
+    reopened: usize,
+    inner_pos: u64,
+    pos: u64,
+    len: Option<u64>,
+}
+
+impl<'a> EmulatedSeek<'a> {
+    /// Create a seekable stream, `open` is called each time the source must be read from the
+    /// start and should return the compressed stream.
+    pub fn new<F, R>(mut open: F) -> Result<Self, Error>
+    where
+        F: FnMut() -> io::Result<R> + 'a,
+        R: Read + 'a,
+    {
+        let mut open: Opener<'a> = Box::new(move || Ok(Box::new(open()?) as Box<dyn Read + 'a>));
+        let (reader, format) = crate::basic::get_reader(open()?)?;
+
+        Ok(EmulatedSeek {
+            open,
+            reader: Box::new(reader),
+            format,
+            history: Vec::new(),
+            history_size: 0,
+            reopened: 0,
+            inner_pos: 0,
+            pos: 0,
+            len: None,
+        })
+    }
+
+    /// Keep the last `size` decompressed bytes in memory, backward seeks inside this window are
+    /// served from memory. Disabled by default.
+    pub fn with_history(mut self, size: usize) -> Self {
+        self.history_size = size;
+        self
+    }
+
+    /// Compression format of the source.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Number of times the source was reopened by a backward seek.
+    pub fn reopened(&self) -> usize {
+        self.reopened
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        let (reader, _) = crate::basic::get_reader((self.open)()?).map_err(io::Error::from)?;
+        self.reader = Box::new(reader);
+        self.history.clear();
+        self.inner_pos = 0;
+        self.reopened += 1;
+
+        Ok(())
+    }
+
+    /// Read from the decompressed stream and keep a copy in history.
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.inner_pos += len as u64;
+
+        if self.history_size > 0 {
+            self.history.extend_from_slice(&buf[..len]);
+            // trim by chunks to avoid moving the history on each read
+            if self.history.len() > 2 * self.history_size {
+                self.history.drain(..self.history.len() - self.history_size);
+            }
+        }
+        if len == 0 {
+            self.len = Some(self.inner_pos);
+        }
+
+        Ok(len)
+    }
+
+    /// Decompress and discard data until `target` or the end of the stream.
+    fn skip_to(&mut self, target: u64) -> io::Result<()> {
+        let mut buffer = [0u8; 8192];
+        while self.inner_pos < target {
+            let len = (target - self.inner_pos).min(buffer.len() as u64) as usize;
+            if self.read_inner(&mut buffer[..len])? == 0 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for EmulatedSeek<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.inner_pos {
+            let history_start = self.inner_pos - self.history.len() as u64;
+            if self.pos >= history_start {
+                let start = (self.pos - history_start) as usize;
+                let len = (self.history.len() - start).min(buf.len());
+                buf[..len].copy_from_slice(&self.history[start..start + len]);
+                self.pos += len as u64;
+
+                return Ok(len);
+            }
+            self.reopen()?;
+        }
+
+        self.skip_to(self.pos)?;
+        if self.pos > self.inner_pos {
+            // seek after the end of the stream
+            return Ok(0);
+        }
+
+        let len = self.read_inner(buf)?;
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl Seek for EmulatedSeek<'_> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(p) => {
+                self.pos = p;
+                return Ok(p);
+            }
+            io::SeekFrom::Current(p) => (self.pos, p),
+            io::SeekFrom::End(p) => {
+                let len = match self.len {
+                    Some(len) => len,
+                    None => {
+                        self.skip_to(u64::MAX)?;
+                        self.inner_pos
+                    }
+                };
+                (len, p)
+            }
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(p) => {
+                self.pos = p;
+                Ok(p)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Open a compressed file with emulated [Seek], the file is reopened by backward seeks.
+///
+/// The returned stream can be used anywhere a [ReadSeek] is expected.
+pub fn from_path<'a, P: AsRef<Path>>(path: P) -> Result<(Box<dyn ReadSeek + 'a>, Format), Error> {
+    let path = path.as_ref().to_path_buf();
+    let reopen = path.clone();
+    let reader = EmulatedSeek::new(move || Ok(io::BufReader::new(crate::utils::open(&reopen)?)))
+        .map_err(|e| e.with_path(&path))?;
+    let format = reader.format();
+
+    Ok((Box::new(reader), format))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn data() -> Vec<u8> {
+        (0..20_000)
+            .flat_map(|i| format!("line {i:05}\n").into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn no_compression() {
+        let data = data();
+        let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+        ofile.write_all(&data).unwrap();
+
+        let (mut reader, format) = from_path(ofile.path()).unwrap();
+        assert_eq!(format, Format::No);
+
+        reader.seek(io::SeekFrom::Start(11 * 500)).unwrap();
+        let mut line = [0u8; 10];
+        reader.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"line 00500");
+
+        assert_eq!(
+            reader.seek(io::SeekFrom::End(0)).unwrap(),
+            data.len() as u64
+        );
+        reader.seek(io::SeekFrom::Current(-11)).unwrap();
+        reader.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"line 19999");
+
+        reader.seek(io::SeekFrom::Start(u64::MAX / 2)).unwrap();
+        assert_eq!(reader.read(&mut line).unwrap(), 0);
+        assert!(reader.seek(io::SeekFrom::Current(i64::MIN)).is_err());
+
+        // a backward seek reopens the removed file
+        let path = ofile.path().to_path_buf();
+        ofile.close().unwrap();
+        reader.seek(io::SeekFrom::Start(0)).unwrap();
+        let error = reader.read(&mut line).unwrap_err();
+        assert!(matches!(Error::from(error), Error::WithPath { path: p, .. } if p == path));
+    }
+
+    #[cfg(feature = "gz")]
+    #[test]
+    fn backward() {
+        let data = data();
+        let mut compressed = Vec::new();
+        {
+            let mut writer = crate::basic::get_writer(
+                Box::new(&mut compressed),
+                Format::Gzip,
+                crate::level::Level::One,
+            )
+            .unwrap();
+            writer.write_all(&data).unwrap();
+        }
+
+        let mut reader =
+            EmulatedSeek::new(move || Ok(io::Cursor::new(compressed.clone()))).unwrap();
+        assert_eq!(reader.format(), Format::Gzip);
+
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, data);
+
+        reader.rewind().unwrap();
+        buffer.clear();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, data);
+        assert_eq!(reader.reopened(), 1);
+
+        let mut reader = reader.with_history(1000);
+        let mut line = [0u8; 10];
+        reader.seek(io::SeekFrom::Start(11 * 100)).unwrap();
+        reader.read_exact(&mut line).unwrap();
+        assert_eq!(reader.reopened(), 2);
+
+        reader.seek(io::SeekFrom::Current(-10 - 11 * 50)).unwrap();
+        reader.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"line 00050");
+        assert_eq!(reader.reopened(), 2);
+
+        reader.seek(io::SeekFrom::Start(11 * 5000)).unwrap();
+        reader.read_exact(&mut line).unwrap();
+        reader.seek(io::SeekFrom::Start(0)).unwrap();
+        reader.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"line 00000");
+        assert_eq!(reader.reopened(), 3);
+    }
+}
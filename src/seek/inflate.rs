@@ -0,0 +1,374 @@
+//! Small DEFLATE decoder able to resume at any bit of a stream.
+//!
+//! zlib needs `inflatePrime` to restart decompression in the middle of a byte, which is not
+//! exposed by flate2, so [zran](super::zran) access points are decoded with this one. Blocks
+//! are decoded whole, the caller keeps the last 32 KiB of output for back-references.
+
+/* standard use */
+use std::io;
+use std::io::BufRead;
+use std::io::Seek;
+
+/// Largest distance a back-reference can reach.
+pub(crate) const WINDOW_SIZE: usize = 32 * 1024;
+
+const TABLE_BITS: u32 = 9;
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub(crate) fn corrupt(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid deflate stream: {msg}"),
+    )
+}
+
+/// Read a stream bit by bit, least significant bit first, and track the bit position.
+pub(crate) struct BitReader<R> {
+    inner: R,
+    bits: u64,
+    count: u32,
+    // bytes taken from inner, including the ones still in `bits`
+    position: u64,
+}
+
+impl<R: BufRead> BitReader<R> {
+    /// `position` is the offset of the next byte of `inner` in the compressed file.
+    pub(crate) fn new(inner: R, position: u64) -> Self {
+        BitReader {
+            inner,
+            bits: 0,
+            count: 0,
+            position,
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Position of the next unread bit in the compressed file.
+    pub(crate) fn bit_position(&self) -> u64 {
+        self.position * 8 - u64::from(self.count)
+    }
+
+    /// Try to have at least `n` bits available, stop early at the end of the stream.
+    fn refill(&mut self, n: u32) -> io::Result<()> {
+        while self.count < n {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(());
+            }
+
+            let take = buf.len().min(((64 - self.count) / 8) as usize);
+            for &byte in &buf[..take] {
+                self.bits |= u64::from(byte) << self.count;
+                self.count += 8;
+            }
+            self.inner.consume(take);
+            self.position += take as u64;
+        }
+
+        Ok(())
+    }
+
+    fn consume(&mut self, n: u32) -> io::Result<()> {
+        if n > self.count {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "deflate stream ends in the middle of a block",
+            ));
+        }
+        self.bits >>= n;
+        self.count -= n;
+
+        Ok(())
+    }
+
+    pub(crate) fn read_bits(&mut self, n: u32) -> io::Result<u32> {
+        self.refill(n)?;
+        let value = (self.bits & ((1 << n) - 1)) as u32;
+        self.consume(n)?;
+
+        Ok(value)
+    }
+
+    /// Drop the bits left in the current byte.
+    pub(crate) fn align(&mut self) {
+        let extra = self.count % 8;
+        self.bits >>= extra;
+        self.count -= extra;
+    }
+
+    /// Read bytes, the stream must be aligned.
+    pub(crate) fn read_bytes(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        for byte in buf.iter_mut() {
+            *byte = self.read_bits(8)? as u8;
+        }
+
+        Ok(())
+    }
+
+    /// True if nothing is left, the stream must be aligned.
+    pub(crate) fn at_end(&mut self) -> io::Result<bool> {
+        self.refill(8)?;
+        Ok(self.count == 0)
+    }
+
+    /// Append `len` bytes to `out`, the stream must be aligned.
+    fn copy_bytes(&mut self, out: &mut Vec<u8>, mut len: usize) -> io::Result<()> {
+        while len > 0 && self.count > 0 {
+            out.push(self.read_bits(8)? as u8);
+            len -= 1;
+        }
+        while len > 0 {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "deflate stream ends in the middle of a stored block",
+                ));
+            }
+
+            let take = buf.len().min(len);
+            out.extend_from_slice(&buf[..take]);
+            self.inner.consume(take);
+            self.position += take as u64;
+            len -= take;
+        }
+
+        Ok(())
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> io::Result<u16> {
+        self.refill(MAX_BITS as u32)?;
+
+        let entry = huffman.table[(self.bits & ((1 << TABLE_BITS) - 1)) as usize];
+        if entry.1 != 0 {
+            self.consume(u32::from(entry.1))?;
+            return Ok(entry.0);
+        }
+
+        // canonical decoding, one bit at a time, for codes longer than the table
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= ((self.bits >> (len - 1)) & 1) as i32;
+            let count = i32::from(huffman.counts[len]);
+            if code - count < first {
+                self.consume(len as u32)?;
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        // not enough bits left to tell if the code is invalid or truncated
+        self.consume(MAX_BITS as u32)?;
+        Err(corrupt("invalid huffman code"))
+    }
+}
+
+impl<R: BufRead + Seek> BitReader<R> {
+    /// Move to `bits` bits after the start of byte `offset`.
+    pub(crate) fn seek(&mut self, offset: u64, bits: u8) -> io::Result<()> {
+        self.inner.seek(io::SeekFrom::Start(offset))?;
+        self.bits = 0;
+        self.count = 0;
+        self.position = offset;
+
+        if bits > 0 {
+            self.read_bits(u32::from(bits))?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+    // (symbol, length) indexed by the next TABLE_BITS bits, length 0 for longer codes
+    table: Vec<(u16, u8)>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(corrupt("over-subscribed huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; offsets[MAX_BITS + 1] as usize];
+        let mut next = offsets;
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[next[len as usize] as usize] = symbol as u16;
+                next[len as usize] += 1;
+            }
+        }
+
+        let mut table = vec![(0u16, 0u8); 1 << TABLE_BITS];
+        let mut code = 0u32;
+        let mut index = 0usize;
+        for len in 1..=MAX_BITS as u32 {
+            for _ in 0..counts[len as usize] {
+                if len <= TABLE_BITS {
+                    let reversed = code.reverse_bits() >> (32 - len);
+                    for fill in (reversed as usize..1 << TABLE_BITS).step_by(1 << len) {
+                        table[fill] = (symbols[index], len as u8);
+                    }
+                }
+                code += 1;
+                index += 1;
+            }
+            code <<= 1;
+        }
+
+        Ok(Huffman {
+            counts,
+            symbols,
+            table,
+        })
+    }
+}
+
+fn fixed_tables() -> io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_tables<R: BufRead>(input: &mut BitReader<R>) -> io::Result<(Huffman, Huffman)> {
+    let nlen = input.read_bits(5)? as usize + 257;
+    let ndist = input.read_bits(5)? as usize + 1;
+    let ncode = input.read_bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(corrupt("too many length or distance codes"));
+    }
+
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..ncode] {
+        lengths[index] = input.read_bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut index = 0;
+    while index < nlen + ndist {
+        let (value, repeat) = match input.decode(&code_lengths)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => match index {
+                0 => return Err(corrupt("repeat with no previous length")),
+                _ => (lengths[index - 1], 3 + input.read_bits(2)? as usize),
+            },
+            17 => (0, 3 + input.read_bits(3)? as usize),
+            _ => (0, 11 + input.read_bits(7)? as usize),
+        };
+        if index + repeat > nlen + ndist {
+            return Err(corrupt("too many code lengths"));
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(corrupt("no end of block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..])?,
+    ))
+}
+
+/// Decode one block and append its data to `out`, which must hold the previous output (at least
+/// the last [WINDOW_SIZE] bytes). Return true if this was the last block of the stream.
+pub(crate) fn inflate_block<R: BufRead>(
+    input: &mut BitReader<R>,
+    out: &mut Vec<u8>,
+) -> io::Result<bool> {
+    let last = input.read_bits(1)? == 1;
+
+    let (lengths, distances) = match input.read_bits(2)? {
+        0 => {
+            input.align();
+            let len = input.read_bits(16)?;
+            if len != !input.read_bits(16)? & 0xffff {
+                return Err(corrupt("stored block length mismatch"));
+            }
+            input.copy_bytes(out, len as usize)?;
+            return Ok(last);
+        }
+        1 => fixed_tables()?,
+        2 => dynamic_tables(input)?,
+        _ => return Err(corrupt("invalid block type")),
+    };
+
+    loop {
+        let symbol = input.decode(&lengths)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(last),
+            257..=285 => {
+                let symbol = symbol - 257;
+                let len = LENGTH_BASE[symbol] as usize
+                    + input.read_bits(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+
+                let symbol = input.decode(&distances)? as usize;
+                if symbol >= 30 {
+                    return Err(corrupt("invalid distance code"));
+                }
+                let dist = DIST_BASE[symbol] as usize
+                    + input.read_bits(u32::from(DIST_EXTRA[symbol]))? as usize;
+                if dist > out.len() {
+                    return Err(corrupt("distance too far back"));
+                }
+
+                let start = out.len() - dist;
+                if dist >= len {
+                    out.extend_from_within(start..start + len);
+                } else {
+                    for i in 0..len {
+                        out.push(out[start + i]);
+                    }
+                }
+            }
+            _ => return Err(corrupt("invalid length code")),
+        }
+    }
+}
@@ -1,5 +1,22 @@
 /* declare mod */
+#[cfg(feature = "bgz")]
+pub mod bgzf;
+#[cfg(feature = "bz2")]
+pub mod bz2;
 pub mod compression;
+pub mod emulated;
+#[cfg(feature = "gz")]
+mod inflate;
+#[cfg(feature = "bgz")]
+pub mod tabix;
+#[cfg(feature = "lzma")]
+pub mod xz;
+#[cfg(feature = "gz")]
+pub mod zran;
+#[cfg(feature = "zstd")]
+pub mod zstd_seekable;
+
+pub use compression::Writer;
 
 /* standard use */
 use std::io;
@@ -7,72 +24,263 @@ use std::path::Path;
 
 /* project use */
 use crate::error::Error;
+use crate::format::Format;
 use crate::level::Level;
 
 /// Similar as [sniff](crate::sniff) but from seekable format
 ///
-/// Only [compression::Format] are support
+/// Every format is reported, including the ones [get_reader] can't seek, such as plain Gzip
+/// or Zstd files without a seek table.
 pub fn sniff<'a>(
     mut in_stream: Box<dyn compression::ReadSeek + 'a>,
-) -> Result<(Box<dyn compression::ReadSeek + 'a>, compression::Format), Error> {
-    let first_bytes = crate::utils::get_first_bytes(&mut in_stream)?;
+) -> Result<(Box<dyn compression::ReadSeek + 'a>, Format), Error> {
+    let format = compression::sniff_format(&mut in_stream)?;
 
-    match compression::bytes2type(first_bytes) {
-        e @ compression::Format::BGzip => Ok((in_stream, e)),
-        _ => Ok((in_stream, compression::Format::No)),
-    }
+    Ok((in_stream, format))
 }
 
-/// Similar as [get_reader](crate::sniff) but from seekable format
+/// Similar as [get_reader](crate::get_reader) but from seekable format
+///
+/// BGzip streams are returned as a [BgzfReader](bgzf::BgzfReader) and Zstd streams in the
+/// seekable format as a [SeekableReader](zstd_seekable::SeekableReader), seeking is done on
+/// uncompressed offsets. Zstd files without a seek table, plain Gzip and the other formats
+/// without random access return [Error::UnsupportedFormat].
+/// Lzma (xz) files are returned as a [XzReader](xz::XzReader), they must have several blocks.
+/// Bzip files are returned as a [Bzip2Reader](bz2::Bzip2Reader), which scans the whole file for
+/// blocks on open.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "bgz")] {
+/// use std::io::{Read, Seek, Write};
+///
+/// let mut buffer = std::io::Cursor::new(Vec::new());
+/// {
+///     let mut writer = niffler::seek::get_writer(Box::new(&mut buffer), niffler::Format::BGzip, niffler::Level::Six)?;
+///     writer.write_all(b"I'm compress in bgzip\n")?;
+///     writer.finish()?;
+/// }
+/// buffer.set_position(0);
+///
+/// let (mut reader, compression) = niffler::seek::get_reader(Box::new(buffer))?;
+/// reader.seek(std::io::SeekFrom::Start(4))?;
+///
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents)?;
 ///
-/// Warning actualy this function work only for not compressed file
+/// assert_eq!(compression, niffler::Format::BGzip);
+/// assert_eq!(contents, "compress in bgzip\n");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
 pub fn get_reader<'a>(
     in_stream: Box<dyn compression::ReadSeek + 'a>,
-) -> Result<(Box<dyn compression::ReadSeek + 'a>, compression::Format), Error> {
+) -> Result<(Box<dyn compression::ReadSeek + 'a>, Format), Error> {
     // check compression
-    let (in_stream, compression) = sniff(in_stream)?;
+    let (mut in_stream, compression) = sniff(in_stream)?;
 
     // return readable and compression status
     match compression {
-        compression::Format::No => Ok((in_stream, compression::Format::No)),
-        _ => Err(Error::FeatureDisabled),
+        Format::BGzip => compression::new_bgzf_decoder(in_stream),
+        Format::Zstd if compression::has_seek_table(&mut in_stream)? => {
+            compression::new_zstd_decoder(in_stream)
+        }
+        Format::Lzma => compression::new_lzma_decoder(in_stream),
+        Format::Bzip => compression::new_bz2_decoder(in_stream),
+        Format::No => Ok((in_stream, Format::No)),
+        format @ (Format::Gzip
+        | Format::Zstd
+        | Format::Lz4
+        | Format::Snappy
+        | Format::Brotli
+        | Format::Zlib
+        | Format::Deflate
+        | Format::UnixCompress) => Err(Error::UnsupportedFormat(format)),
     }
 }
 
 /// Similar as [get_writer](crate::get_writer) but from seekable format
 ///
-/// Warning actualy this function work only for not compressed file
+/// BGzip streams are written by a [BgzfWriter](bgzf::BgzfWriter) and Zstd streams in the
+/// seekable format by a [SeekableWriter](zstd_seekable::SeekableWriter), the returned [Writer]
+/// only support `Seek` to get the current uncompressed position. Call [finish](Writer::finish)
+/// to get the errors raised while the end of the stream is written.
+/// [Format::Gzip] returns an error, write [Format::BGzip] to get a seekable gzip file.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "bgz")] {
+/// use std::io::{Read, Seek, Write};
+/// use niffler::Format;
+///
+/// let mut buffer = std::io::Cursor::new(Vec::new());
+/// {
+///     let mut writer = niffler::seek::get_writer(Box::new(&mut buffer), Format::BGzip, niffler::Level::Six)?;
+///     writer.write_all(b"I'm compress in bgzip\n")?;
+///     writer.finish()?;
+/// }
+/// buffer.set_position(0);
+///
+/// let (mut reader, compression) = niffler::seek::get_reader(Box::new(buffer))?;
+/// reader.seek(std::io::SeekFrom::End(-6))?;
+///
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents)?;
+///
+/// assert_eq!(compression, Format::BGzip);
+/// assert_eq!(contents, "bgzip\n");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
 pub fn get_writer<'a>(
     out_stream: Box<dyn compression::WriteSeek + 'a>,
-    format: compression::Format,
-    _level: Level,
-) -> Result<Box<dyn compression::WriteSeek + 'a>, Error> {
+    format: Format,
+    level: Level,
+) -> Result<compression::Writer<'a>, Error> {
     match format {
-        compression::Format::No => Ok(Box::new(out_stream)),
-        _ => Err(Error::FeatureDisabled),
+        Format::BGzip => compression::new_bgzf_encoder(out_stream, level),
+        Format::Zstd => compression::new_zstd_encoder(out_stream, level),
+        Format::Lzma => compression::new_lzma_encoder(out_stream, level),
+        Format::Bzip => compression::new_bz2_encoder(out_stream, level),
+        Format::No => Ok(compression::Writer::new(out_stream)),
+        format @ (Format::Gzip
+        | Format::Lz4
+        | Format::Snappy
+        | Format::Brotli
+        | Format::Zlib
+        | Format::Deflate
+        | Format::UnixCompress) => Err(Error::UnsupportedFormat(format)),
     }
 }
 
-/// Similar as [from_path](crate::from_path) but from seekable format
+/// Same as [get_writer] but set the amount of uncompressed data in each independently
+/// compressed part of the file: the zstd frame size or the BGZF block size (at most
+/// [DEFAULT_BLOCK_SIZE](bgzf::DEFAULT_BLOCK_SIZE)). Smaller frames give faster seeks and a
+/// worse compression ratio.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "zstd")] {
+/// use std::io::{Read, Seek, Write};
+/// use niffler::Format;
+///
+/// let mut buffer = std::io::Cursor::new(Vec::new());
+/// {
+///     let mut writer = niffler::seek::get_writer_with_frame_size(Box::new(&mut buffer), Format::Zstd, niffler::Level::Three, 64 * 1024)?;
+///     for i in 0..100_000 {
+///         writeln!(writer, "line {i:05}")?;
+///     }
+///     writer.finish()?;
+/// }
+/// buffer.set_position(0);
+///
+/// let (mut reader, compression) = niffler::seek::get_reader(Box::new(buffer))?;
+/// reader.seek(std::io::SeekFrom::Start(11 * 50_000))?;
+///
+/// let mut line = [0u8; 10];
+/// reader.read_exact(&mut line)?;
+///
+/// assert_eq!(compression, Format::Zstd);
+/// assert_eq!(&line, b"line 50000");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub fn get_writer_with_frame_size<'a>(
+    out_stream: Box<dyn compression::WriteSeek + 'a>,
+    format: Format,
+    level: Level,
+    frame_size: usize,
+) -> Result<compression::Writer<'a>, Error> {
+    match format {
+        Format::BGzip => {
+            compression::new_bgzf_encoder_with_block_size(out_stream, level, frame_size)
+        }
+        Format::Zstd => {
+            compression::new_zstd_encoder_with_frame_size(out_stream, level, frame_size)
+        }
+        Format::Lzma => {
+            compression::new_lzma_encoder_with_block_size(out_stream, level, frame_size)
+        }
+        Format::No => Ok(compression::Writer::new(out_stream)),
+        // bzip2 block size is set by the compression level
+        format @ (Format::Bzip
+        | Format::Gzip
+        | Format::Lz4
+        | Format::Snappy
+        | Format::Brotli
+        | Format::Zlib
+        | Format::Deflate
+        | Format::UnixCompress) => Err(Error::UnsupportedFormat(format)),
+    }
+}
+
+/// Same as [get_writer] but also write a `.gzi` index in `index_stream` as blocks are emitted,
+/// the file can be randomly accessed with [BgzfIndex::from_gzi](bgzf::BgzfIndex::from_gzi)
+/// (or `samtools faidx`) as soon as it's written.
 ///
-/// Warning actualy this function work only for not compressed file
+/// Only [Format::BGzip] support index.
+pub fn get_writer_with_index<'a>(
+    out_stream: Box<dyn compression::WriteSeek + 'a>,
+    index_stream: Box<dyn compression::WriteSeek + 'a>,
+    format: Format,
+    level: Level,
+) -> Result<compression::Writer<'a>, Error> {
+    match format {
+        Format::BGzip => compression::new_bgzf_encoder_with_index(out_stream, index_stream, level),
+        format @ (Format::Gzip
+        | Format::Zstd
+        | Format::Lzma
+        | Format::Bzip
+        | Format::Lz4
+        | Format::Snappy
+        | Format::Brotli
+        | Format::Zlib
+        | Format::Deflate
+        | Format::UnixCompress
+        | Format::No) => Err(Error::UnsupportedFormat(format)),
+    }
+}
+
+/// Similar as [from_path](crate::from_path) but from seekable format
 pub fn from_path<'a, P: AsRef<Path>>(
     path: P,
-) -> Result<(Box<dyn compression::ReadSeek + 'a>, compression::Format), Error> {
-    let readable = io::BufReader::new(std::fs::File::open(path)?);
-    get_reader(Box::new(readable))
+) -> Result<(Box<dyn compression::ReadSeek + 'a>, Format), Error> {
+    let path = path.as_ref();
+    let readable = io::BufReader::new(crate::utils::open(path)?);
+    get_reader(Box::new(readable)).map_err(|e| e.with_path(path))
 }
 
 /// Similar as [to_path](crate::to_path) but from seekable format
-///
-/// Warning actualy this function work only for not compressed file
 pub fn to_path<'a, P: AsRef<Path>>(
     path: P,
-    format: compression::Format,
+    format: Format,
     level: Level,
-) -> Result<Box<dyn compression::WriteSeek + 'a>, Error> {
-    let writable = io::BufWriter::new(std::fs::File::create(path)?);
-    get_writer(Box::new(writable), format, level)
+) -> Result<compression::Writer<'a>, Error> {
+    let path = path.as_ref();
+    let writable = io::BufWriter::new(crate::utils::create(path)?);
+    get_writer(Box::new(writable), format, level).map_err(|e| e.with_path(path))
+}
+
+/// Same as [to_path] but also write a `.gzi` index next to the file (`path` with a `.gzi` suffix).
+pub fn to_path_with_index<'a, P: AsRef<Path>>(
+    path: P,
+    format: Format,
+    level: Level,
+) -> Result<compression::Writer<'a>, Error> {
+    let path = path.as_ref();
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(".gzi");
+
+    let writable = io::BufWriter::new(crate::utils::create(path)?);
+    let index = io::BufWriter::new(crate::utils::create(Path::new(&index_path))?);
+    get_writer_with_index(Box::new(writable), Box::new(index), format, level)
+        .map_err(|e| e.with_path(path))
 }
 
 #[cfg(test)]
@@ -81,11 +289,430 @@ mod test {
     use super::*;
     use tempfile::NamedTempFile;
 
-    pub(crate) const SHORT_FILE: &'static [u8] = &[0o037, 0o213, 0o0, 0o0];
-    pub(crate) const BGZIP_FILE: &'static [u8] = &[
+    pub(crate) const SHORT_FILE: &[u8] = &[0o037, 0o213, 0o0, 0o0];
+    pub(crate) const BGZIP_FILE: &[u8] = &[
         0x1F, 0x8B, 0x8, 0x4, 0x0, 0x0, 0x0, 0x0, 0x0, 0xFF, 0x6, 0x0, 0x42, 0x43, 0x2, 0x0, 0x0,
     ];
-    pub(crate) const LOREM_IPSUM: &'static [u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. Ut ultricies scelerisque diam, a scelerisque enim sagittis at.";
+    pub(crate) const ZSTD_SEEKABLE_FILE: &[u8] = &[
+        0x28, 0xb5, 0x2f, 0xfd, 0x20, 0x0, 0x1, 0x0, 0x0, 0x5e, 0x2a, 0x4d, 0x18, 0x11, 0x0, 0x0,
+        0x0, 0x9, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x0, 0xb1, 0xea, 0x92,
+        0x8f,
+    ];
+    pub(crate) const LZMA_FILE: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x0, 0x0, 0x1, 0x69, 0x22, 0xde, 0x36, 0x0, 0x0, 0x0, 0x0,
+        0x1c, 0xdf, 0x44, 0x21, 0x90, 0x42, 0x99, 0xd, 0x1, 0x0, 0x0, 0x0, 0x0, 0x1, 0x59, 0x5a,
+    ];
+    pub(crate) const BZIP_FILE: &[u8] = &[
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0x19, 0x93, 0x9b, 0x6b, 0x0,
+        0x0, 0x0, 0x1, 0x0, 0x20, 0x0, 0x20, 0x0, 0x21, 0x18, 0x46, 0x82, 0xee, 0x48, 0xa7, 0xa,
+        0x12, 0x3, 0x32, 0x73, 0x6d, 0x60,
+    ];
+    pub(crate) const LOREM_IPSUM: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. Ut ultricies scelerisque diam, a scelerisque enim sagittis at.";
+
+    mod compress_uncompress {
+        use super::*;
+        use std::io::{Read, Seek, Write};
+
+        #[test]
+        fn magic_in_plain_text() {
+            for input in [&b"BZh9 is not a bzip2 file"[..], b"x^2 + y^2 = z^2\n"] {
+                let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+                ofile.write_all(input).unwrap();
+
+                let rfile = ofile.reopen().expect("Can't create tmpfile");
+
+                let (mut reader, compression) =
+                    get_reader(Box::new(rfile)).expect("Error in read file");
+                assert_eq!(compression, Format::No);
+
+                let mut buffer = Vec::new();
+                reader.read_to_end(&mut buffer).unwrap();
+                assert_eq!(buffer, input);
+            }
+        }
+
+        #[test]
+        fn short_no_compression() {
+            for input in [&b""[..], b"a", b"abc\n", &LOREM_IPSUM[..16]] {
+                let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+                ofile.write_all(input).unwrap();
+
+                let (mut reader, compression) =
+                    get_reader(Box::new(ofile.reopen().unwrap())).expect("Error in read file");
+                assert_eq!(compression, Format::No);
+
+                let mut buffer = Vec::new();
+                reader
+                    .read_to_end(&mut buffer)
+                    .expect("Error during reading");
+                assert_eq!(buffer, input);
+            }
+        }
+
+        #[test]
+        fn no_compression() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            ofile.write_all(LOREM_IPSUM).unwrap();
+
+            let (mut reader, compression) =
+                from_path(ofile.path()).expect("Error reading from tmpfile");
+            assert_eq!(compression, Format::No);
+
+            reader.seek(io::SeekFrom::Start(6)).unwrap();
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(&LOREM_IPSUM[6..], buffer.as_slice());
+        }
+
+        /// A stream which fails once `0` bytes are left, as a full disk.
+        struct Full(usize);
+
+        impl io::Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0 == 0 {
+                    return Err(io::Error::other("no space left on device"));
+                }
+                let len = buf.len().min(self.0);
+                self.0 -= len;
+                Ok(len)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        impl io::Seek for Full {
+            fn seek(&mut self, _: io::SeekFrom) -> io::Result<u64> {
+                Ok(0)
+            }
+        }
+
+        #[test]
+        fn finish() {
+            let mut writer = get_writer(
+                Box::new(io::Cursor::new(Vec::new())),
+                Format::No,
+                Level::One,
+            )
+            .unwrap();
+            writer.write_all(LOREM_IPSUM).unwrap();
+            assert_eq!(writer.stream_position().unwrap(), LOREM_IPSUM.len() as u64);
+            writer.finish().expect("Error during finish");
+
+            let mut writer = get_writer(Box::new(Full(10)), Format::No, Level::One).unwrap();
+            assert!(writer.write_all(LOREM_IPSUM).is_err());
+        }
+
+        #[test]
+        #[allow(clippy::single_element_loop)]
+        fn finish_error() {
+            for format in [
+                #[cfg(feature = "bgz")]
+                Format::BGzip,
+                #[cfg(feature = "zstd")]
+                Format::Zstd,
+                #[cfg(feature = "lzma")]
+                Format::Lzma,
+                #[cfg(feature = "bz2")]
+                Format::Bzip,
+            ] {
+                let mut writer = get_writer(Box::new(Full(20)), format, Level::One).unwrap();
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Data is buffered by the encoder");
+                assert!(writer.finish().is_err(), "{format:?} end can't be written");
+            }
+        }
+
+        #[cfg(feature = "bgz")]
+        #[test]
+        fn bgzip() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            {
+                let mut writer = to_path(ofile.path(), Format::BGzip, Level::Six)
+                    .expect("Error creating bgzip file");
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let (mut reader, compression) =
+                from_path(ofile.path()).expect("Error reading from tmpfile");
+            assert_eq!(compression, Format::BGzip);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+
+            reader.seek(io::SeekFrom::Start(12)).unwrap();
+            buffer.clear();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(&LOREM_IPSUM[12..], buffer.as_slice());
+        }
+
+        #[cfg(feature = "bgz")]
+        #[test]
+        fn bgzip_with_index() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            let mut data = Vec::new();
+            for _ in 0..2000 {
+                data.extend_from_slice(LOREM_IPSUM);
+            }
+
+            {
+                let mut writer = to_path_with_index(ofile.path(), Format::BGzip, Level::One)
+                    .expect("Error creating bgzip file");
+                writer.write_all(&data).expect("Error during write of data");
+            }
+
+            let mut rfile = ofile.reopen().expect("Can't reopen tmpfile");
+            let index = bgzf::BgzfIndex::build(&mut rfile).unwrap();
+            assert!(index.blocks().len() > 3);
+
+            let mut gzi_path = ofile.path().as_os_str().to_owned();
+            gzi_path.push(".gzi");
+            let gzi = std::fs::read(&gzi_path).expect("Can't read gzi index");
+            std::fs::remove_file(&gzi_path).unwrap();
+
+            let mut expected = Vec::new();
+            index.write_gzi(&mut expected).unwrap();
+            assert_eq!(gzi, expected);
+            assert_eq!(
+                bgzf::BgzfIndex::from_gzi(&gzi[..], &mut rfile).unwrap(),
+                index
+            );
+
+            assert!(get_writer_with_index(
+                Box::new(io::Cursor::new(vec![])),
+                Box::new(io::Cursor::new(vec![])),
+                Format::No,
+                Level::One
+            )
+            .is_err());
+        }
+
+        #[cfg(feature = "zstd")]
+        #[test]
+        fn zstd() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            {
+                let mut writer = to_path(ofile.path(), Format::Zstd, Level::Six)
+                    .expect("Error creating zstd file");
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let (mut reader, compression) =
+                from_path(ofile.path()).expect("Error reading from tmpfile");
+            assert_eq!(compression, Format::Zstd);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+
+            reader.seek(io::SeekFrom::Start(12)).unwrap();
+            buffer.clear();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(&LOREM_IPSUM[12..], buffer.as_slice());
+        }
+
+        #[cfg(all(feature = "bgz", feature = "zstd"))]
+        #[test]
+        fn frame_size() {
+            for format in [Format::BGzip, Format::Zstd] {
+                let mut buffer = io::Cursor::new(Vec::new());
+                {
+                    let mut writer =
+                        get_writer_with_frame_size(Box::new(&mut buffer), format, Level::One, 10)
+                            .expect("Error creating writer");
+                    writer
+                        .write_all(LOREM_IPSUM)
+                        .expect("Error during write of data");
+                }
+
+                let data = buffer.into_inner();
+                let frames = match format {
+                    Format::BGzip => {
+                        crate::seek::bgzf::BgzfIndex::build(&mut io::Cursor::new(&data))
+                            .unwrap()
+                            .blocks()
+                            .iter()
+                            .filter(|b| b.uncompressed_size > 0)
+                            .count()
+                    }
+                    _ => crate::seek::zstd_seekable::SeekTable::from_reader(&mut io::Cursor::new(
+                        &data,
+                    ))
+                    .unwrap()
+                    .frames()
+                    .len(),
+                };
+                assert_eq!(frames, LOREM_IPSUM.len().div_ceil(10));
+
+                let (mut reader, compression) =
+                    get_reader(Box::new(io::Cursor::new(data))).expect("Error reading");
+                assert_eq!(compression, format);
+                reader.seek(io::SeekFrom::Start(25)).unwrap();
+                let mut buffer = Vec::new();
+                reader
+                    .read_to_end(&mut buffer)
+                    .expect("Error during reading");
+                assert_eq!(&LOREM_IPSUM[25..], buffer.as_slice());
+
+                assert!(get_writer_with_frame_size(
+                    Box::new(io::Cursor::new(vec![])),
+                    format,
+                    Level::One,
+                    0
+                )
+                .is_err());
+            }
+        }
+
+        #[cfg(feature = "lzma")]
+        #[test]
+        fn lzma() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            {
+                let writer = io::BufWriter::new(ofile.reopen().unwrap());
+                let mut writer =
+                    get_writer_with_frame_size(Box::new(writer), Format::Lzma, Level::Six, 32)
+                        .expect("Error creating xz file");
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let (mut reader, compression) =
+                from_path(ofile.path()).expect("Error reading from tmpfile");
+            assert_eq!(compression, Format::Lzma);
+
+            reader.seek(io::SeekFrom::Start(70)).unwrap();
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(&LOREM_IPSUM[70..], buffer.as_slice());
+
+            {
+                let mut writer = to_path(ofile.path(), Format::Lzma, Level::Six)
+                    .expect("Error creating xz file");
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+            assert!(
+                from_path(ofile.path()).is_err(),
+                "single block xz files can't be randomly accessed"
+            );
+        }
+
+        #[cfg(feature = "bz2")]
+        #[test]
+        fn bzip() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            {
+                let mut writer = to_path(ofile.path(), Format::Bzip, Level::Six)
+                    .expect("Error creating bzip2 file");
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let (mut reader, compression) =
+                from_path(ofile.path()).expect("Error reading from tmpfile");
+            assert_eq!(compression, Format::Bzip);
+
+            reader.seek(io::SeekFrom::Start(12)).unwrap();
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(&LOREM_IPSUM[12..], buffer.as_slice());
+
+            assert!(matches!(
+                get_writer_with_frame_size(
+                    Box::new(io::Cursor::new(vec![])),
+                    Format::Bzip,
+                    Level::One,
+                    1024
+                ),
+                Err(Error::UnsupportedFormat(Format::Bzip))
+            ));
+        }
+
+        #[test]
+        fn gzip_unsupported() {
+            let buffer = io::Cursor::new(Vec::new());
+
+            assert!(matches!(
+                get_writer(Box::new(buffer), Format::Gzip, Level::Six),
+                Err(Error::UnsupportedFormat(Format::Gzip))
+            ));
+        }
+
+        #[cfg(not(feature = "bz2"))]
+        #[test]
+        fn no_bzip_feature() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            ofile.write_all(BZIP_FILE).unwrap();
+
+            assert!(
+                from_path(ofile.path()).is_err(),
+                "bz2 disabled, this assertion should fail"
+            );
+        }
+
+        #[cfg(not(feature = "lzma"))]
+        #[test]
+        fn no_lzma_feature() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            ofile.write_all(LZMA_FILE).unwrap();
+
+            assert!(
+                from_path(ofile.path()).is_err(),
+                "lzma disabled, this assertion should fail"
+            );
+        }
+
+        #[cfg(not(feature = "zstd"))]
+        #[test]
+        fn no_zstd_feature() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            ofile.write_all(ZSTD_SEEKABLE_FILE).unwrap();
+
+            assert!(
+                from_path(ofile.path()).is_err(),
+                "zstd disabled, this assertion should fail"
+            );
+        }
+
+        #[cfg(not(feature = "bgz"))]
+        #[test]
+        fn no_bgzip_feature() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            ofile.write_all(BGZIP_FILE).unwrap();
+
+            assert!(
+                from_path(ofile.path()).is_err(),
+                "bgz disabled, this assertion should fail"
+            );
+        }
+    }
 
     mod compression_format_detection {
         use super::*;
@@ -100,7 +727,85 @@ mod test {
             let rfile = ofile.reopen().expect("Can't create tmpfile");
 
             let (_, compression) = sniff(Box::new(rfile)).expect("Error in read file");
-            assert_eq!(compression, compression::Format::BGzip);
+            assert_eq!(compression, Format::BGzip);
+        }
+
+        #[test]
+        fn zstd() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            ofile.write_all(ZSTD_SEEKABLE_FILE).unwrap();
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+
+            let (_, compression) = sniff(Box::new(rfile)).expect("Error in read file");
+            assert_eq!(compression, Format::Zstd);
+        }
+
+        #[test]
+        fn zstd_without_seek_table() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            ofile.write_all(&ZSTD_SEEKABLE_FILE[..17]).unwrap();
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+
+            let (_, compression) = sniff(Box::new(rfile)).expect("Error in read file");
+            assert_eq!(compression, Format::Zstd);
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+            assert!(matches!(
+                get_reader(Box::new(rfile)),
+                Err(Error::UnsupportedFormat(Format::Zstd))
+            ));
+        }
+
+        #[test]
+        fn unseekable_formats() {
+            // `printf "I'm compress in gzip\n" | gzip` and `printf 'abababababababab\n' | compress`
+            let gzip: &[u8] = &[
+                0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xf3, 0x54, 0xcf, 0x55,
+                0x48, 0xce, 0xcf, 0x2d, 0x28, 0x4a, 0x2d, 0x2e, 0x56, 0xc8, 0xcc, 0x53, 0x48, 0xaf,
+                0xca, 0x2c, 0xe0, 0x02, 0x00, 0x45, 0x7c, 0xf4, 0x10, 0x15, 0x00, 0x00, 0x00,
+            ];
+            let unix_compress: &[u8] = &[
+                0x1f, 0x9d, 0x90, 0x61, 0xc4, 0x4, 0x1c, 0x28, 0xb0, 0x20, 0x41, 0x5,
+            ];
+
+            for (input, format) in [(gzip, Format::Gzip), (unix_compress, Format::UnixCompress)] {
+                let (_, compression) =
+                    sniff(Box::new(io::Cursor::new(input))).expect("Error in read file");
+                assert_eq!(compression, format);
+
+                assert!(matches!(
+                    get_reader(Box::new(io::Cursor::new(input))),
+                    Err(Error::UnsupportedFormat(f)) if f == format
+                ));
+            }
+        }
+
+        #[test]
+        fn lzma() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            ofile.write_all(LZMA_FILE).unwrap();
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+
+            let (_, compression) = sniff(Box::new(rfile)).expect("Error in read file");
+            assert_eq!(compression, Format::Lzma);
+        }
+
+        #[test]
+        fn bzip() {
+            let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            ofile.write_all(BZIP_FILE).unwrap();
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+
+            let (_, compression) = sniff(Box::new(rfile)).expect("Error in read file");
+            assert_eq!(compression, Format::Bzip);
         }
 
         #[test]
@@ -115,6 +820,25 @@ mod test {
             assert!(result.is_err());
         }
 
+        #[test]
+        fn short_no_compression() {
+            for input in [&b""[..], b"a", b"abc\n", &LOREM_IPSUM[..16]] {
+                let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+                ofile.write_all(input).unwrap();
+
+                let (mut reader, compression) =
+                    get_reader(Box::new(ofile.reopen().unwrap())).expect("Error in read file");
+                assert_eq!(compression, Format::No);
+
+                let mut buffer = Vec::new();
+                reader
+                    .read_to_end(&mut buffer)
+                    .expect("Error during reading");
+                assert_eq!(buffer, input);
+            }
+        }
+
         #[test]
         fn no_compression() {
             let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
@@ -124,7 +848,24 @@ mod test {
             let rfile = ofile.reopen().expect("Can't create tmpfile");
 
             let (_, compression) = sniff(Box::new(rfile)).expect("Error in read file");
-            assert_eq!(compression, compression::Format::No);
+            assert_eq!(compression, Format::No);
+        }
+
+        #[test]
+        #[allow(deprecated)]
+        fn deprecated_format() {
+            for old in [
+                compression::Format::BGzip,
+                compression::Format::Zstd,
+                compression::Format::Lzma,
+                compression::Format::Bzip,
+                compression::Format::No,
+            ] {
+                let format = Format::from(old);
+                assert_eq!(compression::Format::try_from(format).unwrap(), old);
+            }
+
+            assert!(compression::Format::try_from(Format::Gzip).is_err());
         }
     }
 }
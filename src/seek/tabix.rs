@@ -0,0 +1,990 @@
+//! Region queries on BGZF compressed text files indexed by `tabix` (`.tbi`) or `bcftools`/`tabix -C` (`.csi`).
+//!
+//! Index chunks are stored as virtual offsets, they are converted to uncompressed offsets with the
+//! [BgzfIndex] of the data file, so queries work on any reader returned by
+//! [seek::from_path](crate::seek::from_path).
+//!
+//! # Example
+//! ```no_run
+//! # fn main() -> Result<(), niffler::Error> {
+//! let mut reader = niffler::seek::tabix::from_path("variants.vcf.gz")?;
+//!
+//! for line in reader.query(&"chr1:10,000-20,000".parse()?)? {
+//!     println!("{}", line?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+/* standard use */
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, Read, Seek};
+use std::path::Path;
+
+/* project use */
+use crate::error::Error;
+use crate::format::Format;
+use crate::seek::bgzf::{BgzfIndex, BgzfReader};
+use crate::seek::compression::ReadSeek;
+
+const TBI_MAGIC: &[u8; 4] = b"TBI\x01";
+const CSI_MAGIC: &[u8; 4] = b"CSI\x01";
+const TBI_MIN_SHIFT: u32 = 14;
+const TBI_DEPTH: u32 = 5;
+/// Deepest csi index htslib can handle, it counts bins in an `int`.
+const CSI_MAX_DEPTH: u32 = 9;
+
+/// Tabix preset for the columns of the indexed file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Columns are given by `col_seq`, `col_beg` and `col_end`.
+    Generic,
+    /// SAM, the end is computed from the CIGAR.
+    Sam,
+    /// VCF, the end is computed from REF or the END info field.
+    Vcf,
+}
+
+/// A genomic region, stored 0-based and half-open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// Name of the reference sequence.
+    pub name: String,
+    /// 0-based start of the region.
+    pub start: u64,
+    /// 0-based, exclusive end of the region.
+    pub end: u64,
+}
+
+impl Region {
+    /// Create a region from 0-based, half-open coordinates.
+    pub fn new<S: Into<String>>(name: S, start: u64, end: u64) -> Self {
+        Region {
+            name: name.into(),
+            start,
+            end,
+        }
+    }
+}
+
+impl std::str::FromStr for Region {
+    type Err = Error;
+
+    /// Parse a `chrom`, `chrom:start` or `chrom:start-end` region, with 1-based inclusive
+    /// coordinates as used by samtools and tabix. Thousands separators (`,`) are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidRegion(s.to_string());
+        let parse = |v: &str| v.replace(',', "").parse::<u64>().map_err(|_| invalid());
+
+        let (name, range) = match s.rsplit_once(':') {
+            Some((name, range)) => (name, Some(range)),
+            None => (s, None),
+        };
+        if name.is_empty() {
+            return Err(invalid());
+        }
+
+        let (start, end) = match range {
+            None => (0, u64::MAX),
+            Some(range) => match range.split_once('-') {
+                Some((start, "")) => (parse(start)?.saturating_sub(1), u64::MAX),
+                Some((start, end)) => (parse(start)?.saturating_sub(1), parse(end)?),
+                None => (parse(range)?.saturating_sub(1), u64::MAX),
+            },
+        };
+        if start >= end {
+            return Err(invalid());
+        }
+
+        Ok(Region::new(name, start, end))
+    }
+}
+
+/// Part of the compressed file, as a pair of virtual offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Chunk {
+    /// Virtual offset of the first record.
+    pub begin: u64,
+    /// Virtual offset after the last record.
+    pub end: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Reference {
+    bins: HashMap<u32, Vec<Chunk>>,
+    // smallest offset of each bin (csi) or of each 16 kbp window (tbi linear index)
+    min_offsets: HashMap<u32, u64>,
+    linear: Vec<u64>,
+}
+
+/// A `.tbi` or `.csi` index.
+#[derive(Debug, Clone)]
+pub struct TabixIndex {
+    min_shift: u32,
+    depth: u32,
+    preset: Preset,
+    zero_based: bool,
+    col_seq: usize,
+    col_beg: usize,
+    col_end: usize,
+    meta: u8,
+    skip: usize,
+    names: Vec<String>,
+    references: Vec<Reference>,
+}
+
+impl TabixIndex {
+    /// Read an index, the format (`.tbi` or `.csi`) is detected from its magic number.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Error> {
+        let mut reader = bgzip::BGZFReader::new(reader).map_err(|e| e.into_io_error())?;
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        let index = match &magic {
+            TBI_MAGIC => Self::read_tbi(&mut reader),
+            CSI_MAGIC => Self::read_csi(&mut reader),
+            _ => Err(corrupt()),
+        };
+
+        index.map_err(|e| at_offset(e, reader.bgzf_pos() >> 16))
+    }
+
+    /// Read an index from a file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        Self::from_reader(io::BufReader::new(crate::utils::open(path)?))
+            .map_err(|e| e.with_path(path))
+    }
+
+    /// Names of the reference sequences in the index.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Column layout of the indexed file.
+    pub fn preset(&self) -> Preset {
+        self.preset
+    }
+
+    /// Character starting header lines.
+    pub fn meta_char(&self) -> char {
+        self.meta as char
+    }
+
+    /// Number of lines to skip at the start of the file.
+    pub fn skip_lines(&self) -> usize {
+        self.skip
+    }
+
+    /// Chunks that may hold records overlapping `region`, sorted and merged.
+    pub fn chunks(&self, region: &Region) -> Vec<Chunk> {
+        let reference = match self.names.iter().position(|n| n == &region.name) {
+            Some(id) => &self.references[id],
+            None => return Vec::new(),
+        };
+
+        let max = 1u64 << (self.min_shift + self.depth * 3);
+        let start = region.start.min(max - 1);
+        let end = region.end.min(max);
+        let min_offset = self.min_offset(reference, start);
+
+        // a wide region of a deep csi index overlaps far more bins than the index holds
+        let mut chunks: Vec<Chunk> = reference
+            .bins
+            .iter()
+            .filter(|(bin, _)| bin_overlaps(**bin, start, end, self.min_shift, self.depth))
+            .flat_map(|(_, chunks)| chunks)
+            .filter(|c| c.end > min_offset)
+            .copied()
+            .collect();
+        chunks.sort_unstable();
+
+        let mut merged: Vec<Chunk> = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            match merged.last_mut() {
+                Some(last) if chunk.begin <= last.end => last.end = last.end.max(chunk.end),
+                _ => merged.push(chunk),
+            }
+        }
+
+        merged
+    }
+
+    fn min_offset(&self, reference: &Reference, start: u64) -> u64 {
+        if !reference.linear.is_empty() {
+            let window = (start >> TBI_MIN_SHIFT) as usize;
+            return reference.linear[window.min(reference.linear.len() - 1)];
+        }
+
+        // csi: use the offset of the deepest bin holding start
+        let mut bin = reg2bin(start, start + 1, self.min_shift, self.depth);
+        loop {
+            if let Some(offset) = reference.min_offsets.get(&bin) {
+                return *offset;
+            }
+            if bin == 0 {
+                return 0;
+            }
+            bin = (bin - 1) >> 3;
+        }
+    }
+
+    fn read_tbi<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let n_ref = read_count(reader)?;
+        let mut index = Self::read_header(reader, TBI_MIN_SHIFT, TBI_DEPTH)?;
+
+        for _ in 0..n_ref {
+            let mut reference = Self::read_bins(reader, index.pseudo_bin(), false)?;
+            let n_intv = read_count(reader)?;
+            reference.linear = (0..n_intv)
+                .map(|_| read_u64(reader))
+                .collect::<Result<_, _>>()?;
+            index.references.push(reference);
+        }
+
+        index.check()
+    }
+
+    fn read_csi<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let min_shift = u32::try_from(read_i32(reader)?).ok();
+        let depth = u32::try_from(read_i32(reader)?)
+            .ok()
+            .filter(|depth| *depth <= CSI_MAX_DEPTH);
+        let (min_shift, depth) = match (min_shift, depth) {
+            (Some(min_shift), Some(depth))
+                if min_shift
+                    .checked_add(depth * 3)
+                    .is_some_and(|shift| shift <= 63) =>
+            {
+                (min_shift, depth)
+            }
+            _ => return Err(corrupt()),
+        };
+
+        let l_aux = read_count(reader)?;
+        if l_aux < 28 {
+            // csi index without tabix header, only text files can be queried
+            return Err(corrupt());
+        }
+        let aux = read_bytes(reader, l_aux)?;
+        let mut index = Self::read_header(&mut &aux[..], min_shift, depth)?;
+
+        let n_ref = read_count(reader)?;
+        for _ in 0..n_ref {
+            let reference = Self::read_bins(reader, index.pseudo_bin(), true)?;
+            index.references.push(reference);
+        }
+
+        index.check()
+    }
+
+    fn read_header<R: Read>(reader: &mut R, min_shift: u32, depth: u32) -> Result<Self, Error> {
+        let format = read_i32(reader)?;
+        let col_seq = read_count(reader)?;
+        let col_beg = read_count(reader)?;
+        let col_end = read_count(reader)?;
+        let meta = read_i32(reader)? as u8;
+        let skip = read_count(reader)?;
+
+        let l_nm = read_count(reader)?;
+        let names = read_bytes(reader, l_nm)?;
+        let names = names
+            .split(|c| *c == 0)
+            .filter(|n| !n.is_empty())
+            .map(|n| String::from_utf8_lossy(n).into_owned())
+            .collect();
+
+        let preset = match format & 0xffff {
+            0 => Preset::Generic,
+            1 => Preset::Sam,
+            2 => Preset::Vcf,
+            _ => return Err(corrupt()),
+        };
+
+        Ok(TabixIndex {
+            min_shift,
+            depth,
+            preset,
+            zero_based: format & 0x10000 != 0,
+            col_seq,
+            col_beg,
+            col_end,
+            meta,
+            skip,
+            names,
+            references: Vec::new(),
+        })
+    }
+
+    fn read_bins<R: Read>(reader: &mut R, pseudo_bin: u32, csi: bool) -> Result<Reference, Error> {
+        let mut reference = Reference::default();
+
+        let n_bin = read_count(reader)?;
+        for _ in 0..n_bin {
+            let bin = read_u32(reader)?;
+            let min_offset = if csi { Some(read_u64(reader)?) } else { None };
+            let n_chunk = read_count(reader)?;
+            let chunks = (0..n_chunk)
+                .map(|_| {
+                    Ok(Chunk {
+                        begin: read_u64(reader)?,
+                        end: read_u64(reader)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            // the pseudo-bin only holds statistics
+            if bin != pseudo_bin {
+                if let Some(offset) = min_offset {
+                    reference.min_offsets.insert(bin, offset);
+                }
+                reference.bins.insert(bin, chunks);
+            }
+        }
+
+        Ok(reference)
+    }
+
+    fn check(self) -> Result<Self, Error> {
+        if self.names.len() != self.references.len() || self.col_seq == 0 || self.col_beg == 0 {
+            return Err(corrupt());
+        }
+
+        Ok(self)
+    }
+
+    fn pseudo_bin(&self) -> u32 {
+        (((1u64 << ((self.depth + 1) * 3)) - 1) / 7 + 1) as u32
+    }
+
+    /// Name and 0-based, half-open interval of a record, `None` for header lines.
+    fn record_interval<'l>(&self, line: &'l str) -> Result<Option<(&'l str, u64, u64)>, Error> {
+        if line.is_empty() || line.as_bytes()[0] == self.meta {
+            return Ok(None);
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let field = |col: usize| fields.get(col - 1).copied().ok_or_else(corrupt);
+        let number = |col: usize| field(col)?.parse::<u64>().map_err(|_| corrupt());
+
+        let name = field(self.col_seq)?;
+        let mut start = number(self.col_beg)?;
+        if !self.zero_based {
+            start = start.saturating_sub(1);
+        }
+
+        let end = match self.preset {
+            Preset::Generic if self.col_end != 0 && self.col_end != self.col_beg => {
+                number(self.col_end)?
+            }
+            Preset::Generic => start + 1,
+            Preset::Vcf => {
+                let info_end = fields
+                    .get(7)
+                    .and_then(|info| info.split(';').find_map(|kv| kv.strip_prefix("END=")))
+                    .and_then(|end| end.parse::<u64>().ok());
+                match info_end {
+                    Some(end) => end,
+                    None => start + field(4)?.len().max(1) as u64,
+                }
+            }
+            Preset::Sam => start + cigar_reference_len(field(6)?).max(1),
+        };
+
+        Ok(Some((name, start, end.max(start + 1))))
+    }
+}
+
+fn cigar_reference_len(cigar: &str) -> u64 {
+    let mut len = 0;
+    let mut value = 0;
+    for c in cigar.bytes() {
+        match c {
+            b'0'..=b'9' => value = value * 10 + u64::from(c - b'0'),
+            b'M' | b'D' | b'N' | b'=' | b'X' => {
+                len += value;
+                value = 0;
+            }
+            _ => value = 0,
+        }
+    }
+    len
+}
+
+/// Bin of the smallest interval holding `[start, end)`.
+pub fn reg2bin(start: u64, end: u64, min_shift: u32, depth: u32) -> u32 {
+    let end = end - 1;
+    let mut shift = min_shift;
+    let mut first = ((1u64 << (depth * 3)) - 1) / 7;
+    for level in (1..=depth).rev() {
+        if start >> shift == end >> shift {
+            return (first + (start >> shift)) as u32;
+        }
+        shift += 3;
+        first -= 1 << ((level - 1) * 3);
+    }
+    0
+}
+
+/// Whether `bin` overlaps `[start, end)`, numbers past the deepest level aren't bins.
+fn bin_overlaps(bin: u32, start: u64, end: u64, min_shift: u32, depth: u32) -> bool {
+    let mut first = 0u64;
+    for level in 0..=depth {
+        let count = 1u64 << (level * 3);
+        if u64::from(bin) < first + count {
+            let shift = min_shift + (depth - level) * 3;
+            let offset = u64::from(bin) - first;
+            return offset << shift < end && (offset + 1) << shift > start;
+        }
+        first += count;
+    }
+    false
+}
+
+/// Bins overlapping `[start, end)`.
+#[cfg(test)]
+fn reg2bins(start: u64, end: u64, min_shift: u32, depth: u32) -> Vec<u32> {
+    let end = end - 1;
+    let mut bins = Vec::new();
+    let mut shift = min_shift + depth * 3;
+    let mut first = 0u64;
+    for level in 0..=depth {
+        bins.extend((first + (start >> shift)..=first + (end >> shift)).map(|b| b as u32));
+        shift = shift.saturating_sub(3);
+        first += 1 << (level * 3);
+    }
+    bins
+}
+
+/// A BGZF file with its tabix index.
+pub struct IndexedReader<R: ReadSeek> {
+    reader: io::BufReader<R>,
+    index: TabixIndex,
+    blocks: BgzfIndex,
+}
+
+/// Open a BGZF file as [seek::from_path](crate::seek::from_path) does and load the `.tbi` or
+/// `.csi` index next to it.
+pub fn from_path<'a, P: AsRef<Path>>(
+    path: P,
+) -> Result<IndexedReader<Box<dyn ReadSeek + 'a>>, Error> {
+    let path = path.as_ref();
+
+    let readable = io::BufReader::new(crate::utils::open(path)?);
+    let (in_stream, format) =
+        crate::seek::sniff(Box::new(readable)).map_err(|e| e.with_path(path))?;
+    if format != Format::BGzip {
+        return Err(Error::UnsupportedFormat(format).with_path(path));
+    }
+    // the block index is built once, for the reader and the queries
+    let reader = BgzfReader::new(in_stream).map_err(|e| e.with_path(path))?;
+    let blocks = reader.index().clone();
+    let reader: Box<dyn ReadSeek + 'a> = Box::new(reader);
+
+    let mut index = None;
+    for ext in [".tbi", ".csi"] {
+        let mut index_path = path.as_os_str().to_owned();
+        index_path.push(ext);
+        if Path::new(&index_path).exists() {
+            index = Some(TabixIndex::from_path(index_path)?);
+            break;
+        }
+    }
+
+    match index {
+        Some(index) => Ok(IndexedReader::new(reader, index, blocks)),
+        // no .tbi or .csi index next to the file
+        None => Err(Error::from(io::Error::from(io::ErrorKind::NotFound)).with_path(path)),
+    }
+}
+
+impl<R: ReadSeek> IndexedReader<R> {
+    /// Query `reader`, a stream of uncompressed data, with `index`.
+    /// `blocks` is the block index of the compressed file.
+    pub fn new(reader: R, index: TabixIndex, blocks: BgzfIndex) -> Self {
+        IndexedReader {
+            reader: io::BufReader::new(reader),
+            index,
+            blocks,
+        }
+    }
+
+    /// The tabix index.
+    pub fn index(&self) -> &TabixIndex {
+        &self.index
+    }
+
+    /// Iterate over lines overlapping `region`.
+    pub fn query(&mut self, region: &Region) -> Result<Query<'_, R>, Error> {
+        let chunks = self
+            .index
+            .chunks(region)
+            .into_iter()
+            .map(|c| {
+                Ok((
+                    self.blocks.from_virtual(c.begin)?,
+                    self.blocks.from_virtual(c.end)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Query {
+            reader: &mut self.reader,
+            index: &self.index,
+            blocks: &self.blocks,
+            region: region.clone(),
+            chunks: chunks.into_iter(),
+            pos: 0,
+            end: 0,
+            done: false,
+        })
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+/// Iterator over lines overlapping a region, returned by [IndexedReader::query].
+pub struct Query<'r, R: ReadSeek> {
+    reader: &'r mut io::BufReader<R>,
+    index: &'r TabixIndex,
+    blocks: &'r BgzfIndex,
+    region: Region,
+    chunks: std::vec::IntoIter<(u64, u64)>,
+    pos: u64,
+    end: u64,
+    done: bool,
+}
+
+impl<R: ReadSeek> Query<'_, R> {
+    fn next_line(&mut self) -> Result<Option<String>, Error> {
+        let mut line = String::new();
+        loop {
+            if self.pos >= self.end {
+                match self.chunks.next() {
+                    Some((begin, end)) => {
+                        self.pos = self.reader.seek(io::SeekFrom::Start(begin))?;
+                        self.end = end;
+                        continue;
+                    }
+                    None => return Ok(None),
+                }
+            }
+
+            line.clear();
+            let len = self.reader.read_line(&mut line)?;
+            if len == 0 {
+                return Ok(None);
+            }
+            let line_start = self.pos;
+            self.pos += len as u64;
+
+            let record = line.trim_end_matches(['\n', '\r']);
+            let interval = self
+                .index
+                .record_interval(record)
+                .map_err(|e| at_offset(e, self.blocks.to_virtual(line_start) >> 16))?;
+            if let Some((name, start, end)) = interval {
+                if name != self.region.name {
+                    continue;
+                }
+                if start >= self.region.end {
+                    // records are sorted, nothing after this one can overlap
+                    return Ok(None);
+                }
+                if end > self.region.start {
+                    line.truncate(record.len());
+                    return Ok(Some(line));
+                }
+            }
+        }
+    }
+}
+
+impl<R: ReadSeek> Iterator for Query<'_, R> {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let next = self.next_line().transpose();
+        if !matches!(next, Some(Ok(_))) {
+            self.done = true;
+        }
+        next
+    }
+}
+
+/// An invalid index or record, its compressed offset is set by [at_offset].
+fn corrupt() -> Error {
+    Error::Corrupt {
+        format: Format::BGzip,
+        offset: 0,
+    }
+}
+
+/// Set the offset of the block a [corrupt] error was raised in.
+fn at_offset(error: Error, offset: u64) -> Error {
+    match error {
+        Error::Corrupt { format, .. } => Error::Corrupt { format, offset },
+        error => error,
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_i32<R: Read>(reader: &mut R) -> Result<i32, Error> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn read_count<R: Read>(reader: &mut R) -> Result<usize, Error> {
+    usize::try_from(read_i32(reader)?).map_err(|_| corrupt())
+}
+
+/// Read `len` bytes, the buffer grows with the data as lengths in the index aren't trusted.
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(buf)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::level::Level;
+    use crate::seek::bgzf::BgzfWriter;
+    use std::io::Write;
+
+    const VCF: &str = "##fileformat=VCFv4.2
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO
+chr1\t100\t.\tA\tT\t.\t.\t.
+chr1\t20000\t.\tACGT\tA\t.\t.\t.
+chr1\t20010\t.\tN\t<DEL>\t.\t.\tSVTYPE=DEL;END=90000
+chr1\t50000\t.\tC\tG\t.\t.\t.
+chr1\t100000\t.\tG\tC\t.\t.\t.
+chr2\t1\t.\tT\tA\t.\t.\t.
+chr2\t500000\t.\tT\tA\t.\t.\t.
+";
+
+    /// Compress VCF in small blocks and index every record in its own chunk
+    fn indexed_vcf(csi: bool) -> (Vec<u8>, Vec<u8>) {
+        let mut writer = BgzfWriter::with_block_size(Vec::new(), Level::One, 64).unwrap();
+        let mut records: Vec<(String, u64, u64, Chunk)> = Vec::new();
+        for line in VCF.lines() {
+            let begin = writer.virtual_offset();
+            writeln!(writer, "{line}").unwrap();
+            writer.flush().unwrap();
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let f: Vec<&str> = line.split('\t').collect();
+            let start = f[1].parse::<u64>().unwrap() - 1;
+            let end = f[7]
+                .strip_prefix("SVTYPE=DEL;END=")
+                .map(|e| e.parse().unwrap())
+                .unwrap_or(start + f[3].len() as u64);
+            let end_offset = writer.virtual_offset();
+            records.push((
+                f[0].to_string(),
+                start,
+                end,
+                Chunk {
+                    begin,
+                    end: end_offset,
+                },
+            ));
+        }
+        let data = writer.finish().unwrap();
+
+        let names = ["chr1", "chr2"];
+        let mut header = Vec::new();
+        for v in [2i32, 1, 2, 0, b'#' as i32, 0, 10] {
+            header.extend_from_slice(&v.to_le_bytes());
+        }
+        header.extend_from_slice(b"chr1\0chr2\0");
+
+        let mut raw = Vec::new();
+        if csi {
+            raw.extend_from_slice(CSI_MAGIC);
+            for v in [14i32, 5, header.len() as i32] {
+                raw.extend_from_slice(&v.to_le_bytes());
+            }
+            raw.extend_from_slice(&header);
+            raw.extend_from_slice(&(names.len() as i32).to_le_bytes());
+        } else {
+            raw.extend_from_slice(TBI_MAGIC);
+            raw.extend_from_slice(&(names.len() as i32).to_le_bytes());
+            raw.extend_from_slice(&header);
+        }
+
+        for name in names {
+            let records: Vec<_> = records.iter().filter(|r| r.0 == name).collect();
+            raw.extend_from_slice(&(records.len() as i32).to_le_bytes());
+            for (_, start, end, chunk) in &records {
+                raw.extend_from_slice(&reg2bin(*start, *end, 14, 5).to_le_bytes());
+                if csi {
+                    raw.extend_from_slice(&chunk.begin.to_le_bytes());
+                }
+                raw.extend_from_slice(&1i32.to_le_bytes());
+                raw.extend_from_slice(&chunk.begin.to_le_bytes());
+                raw.extend_from_slice(&chunk.end.to_le_bytes());
+            }
+            if !csi {
+                // no linear index, every window starts at the first record
+                raw.extend_from_slice(&1i32.to_le_bytes());
+                raw.extend_from_slice(&records[0].3.begin.to_le_bytes());
+            }
+        }
+
+        let mut index = BgzfWriter::new(Vec::new(), Level::One);
+        index.write_all(&raw).unwrap();
+        (data, index.finish().unwrap())
+    }
+
+    fn query(csi: bool, region: &str) -> Vec<String> {
+        let (data, index) = indexed_vcf(csi);
+        let mut file = io::Cursor::new(data);
+        let blocks = BgzfIndex::build(&mut file).unwrap();
+        let (reader, format) = crate::seek::get_reader(Box::new(file)).unwrap();
+        assert_eq!(format, Format::BGzip);
+
+        let index = TabixIndex::from_reader(&index[..]).unwrap();
+        assert_eq!(index.names(), &["chr1", "chr2"]);
+        assert_eq!(index.preset(), Preset::Vcf);
+
+        let mut reader = IndexedReader::new(reader, index, blocks);
+        reader
+            .query(&region.parse().unwrap())
+            .unwrap()
+            .map(|l| l.unwrap().split('\t').take(2).collect::<Vec<_>>().join(":"))
+            .collect()
+    }
+
+    #[test]
+    fn region() {
+        assert_eq!(
+            "chr1:1,000-2000".parse::<Region>().unwrap(),
+            Region::new("chr1", 999, 2000)
+        );
+        assert_eq!(
+            "chrUn:1".parse::<Region>().unwrap(),
+            Region::new("chrUn", 0, u64::MAX)
+        );
+        assert_eq!(
+            "HLA-A*01:01:1-10".parse::<Region>().unwrap(),
+            Region::new("HLA-A*01:01", 0, 10)
+        );
+        assert!(matches!(
+            "chr1:20-10".parse::<Region>(),
+            Err(Error::InvalidRegion(region)) if region == "chr1:20-10"
+        ));
+        assert!(":1-10".parse::<Region>().is_err());
+        assert!("chr1:a-b".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn bins() {
+        assert_eq!(reg2bin(0, 1, 14, 5), 4681);
+        assert_eq!(reg2bin(0, 1 << 29, 14, 5), 0);
+        assert_eq!(reg2bin(16384, 16385, 14, 5), 4682);
+        assert_eq!(reg2bins(0, 1, 14, 5), vec![0, 1, 9, 73, 585, 4681]);
+    }
+
+    #[test]
+    fn tbi() {
+        assert_eq!(query(false, "chr1:100-100"), vec!["chr1:100"]);
+        assert_eq!(query(false, "chr1:101-19999"), Vec::<String>::new());
+        assert_eq!(
+            query(false, "chr1:20003-60000"),
+            vec!["chr1:20000", "chr1:20010", "chr1:50000"]
+        );
+        assert_eq!(query(false, "chr1:80000-90000"), vec!["chr1:20010"]);
+        assert_eq!(query(false, "chr2"), vec!["chr2:1", "chr2:500000"]);
+        assert_eq!(query(false, "chr3"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn csi() {
+        assert_eq!(query(true, "chr1:100-100"), vec!["chr1:100"]);
+        assert_eq!(
+            query(true, "chr1:20003-60000"),
+            vec!["chr1:20000", "chr1:20010", "chr1:50000"]
+        );
+        assert_eq!(query(true, "chr2:2-500000"), vec!["chr2:500000"]);
+    }
+
+    // bgzip file of 2 data blocks, indexed by the htslib algorithm: pseudo-bins, linear index
+    // and small bins merged in their parent, the csi index has 6 levels as `tabix -C`
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data");
+
+    /// Lines of the fixture overlapping `region`, found by reading the whole file.
+    fn scan(region: &Region) -> Vec<String> {
+        let mut reader = crate::seek::from_path(format!("{FIXTURES}/variants.vcf.gz"))
+            .unwrap()
+            .0;
+        let mut data = String::new();
+        reader.read_to_string(&mut data).unwrap();
+
+        data.lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter(|line| {
+                let f: Vec<&str> = line.split('\t').collect();
+                let start = f[1].parse::<u64>().unwrap() - 1;
+                let end = f[7]
+                    .split(';')
+                    .find_map(|kv| kv.strip_prefix("END="))
+                    .map_or(start + f[3].len() as u64, |end| end.parse().unwrap());
+                f[0] == region.name && start < region.end && end > region.start
+            })
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn htslib_index() {
+        let data = std::fs::read(format!("{FIXTURES}/variants.vcf.gz")).unwrap();
+        let blocks = BgzfIndex::build(&mut io::Cursor::new(&data)).unwrap();
+        // two data blocks and the EOF marker
+        assert_eq!(blocks.blocks().len(), 3);
+
+        for ext in ["tbi", "csi"] {
+            let (reader, _) =
+                crate::seek::get_reader(Box::new(io::Cursor::new(data.clone()))).unwrap();
+
+            let index = TabixIndex::from_path(format!("{FIXTURES}/variants.vcf.gz.{ext}")).unwrap();
+            assert_eq!(index.names(), &["chr1", "chr2", "chrM"]);
+            assert_eq!(index.preset(), Preset::Vcf);
+
+            let mut reader = IndexedReader::new(reader, index, blocks.clone());
+            let mut regions: Vec<Region> = (0..140_000)
+                .step_by(7_919)
+                .map(|start| Region::new("chr1", start, start + 3_000))
+                .collect();
+            regions.extend(
+                [
+                    "chr1",
+                    "chr1:80,000-80,001",
+                    "chr2:100,000-250,000",
+                    "chrM",
+                    "chrX",
+                ]
+                .iter()
+                .map(|r| r.parse().unwrap()),
+            );
+
+            for region in regions {
+                let lines: Vec<String> = reader
+                    .query(&region)
+                    .unwrap()
+                    .collect::<Result<_, _>>()
+                    .unwrap();
+                assert_eq!(lines, scan(&region), "{ext} {region:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn csi_levels() {
+        let csi = |min_shift: i32, depth: i32| {
+            let mut raw = CSI_MAGIC.to_vec();
+            for v in [min_shift, depth, 0] {
+                raw.extend_from_slice(&v.to_le_bytes());
+            }
+            let mut index = BgzfWriter::new(Vec::new(), Level::One);
+            index.write_all(&raw).unwrap();
+            TabixIndex::from_reader(&index.finish().unwrap()[..])
+        };
+
+        for (min_shift, depth) in [(0, 21), (14, 10), (-1, 5), (14, -1), (i32::MAX, 9), (50, 5)] {
+            assert!(
+                matches!(
+                    csi(min_shift, depth),
+                    Err(Error::Corrupt {
+                        format: Format::BGzip,
+                        ..
+                    })
+                ),
+                "{min_shift} {depth}"
+            );
+        }
+        // the header is valid, the tabix header is missing
+        assert!(matches!(csi(0, 9), Err(Error::Corrupt { .. })));
+    }
+
+    #[test]
+    fn huge_lengths() {
+        let read = |raw: &[u8]| {
+            let mut index = BgzfWriter::new(Vec::new(), Level::One);
+            index.write_all(raw).unwrap();
+            TabixIndex::from_reader(&index.finish().unwrap()[..])
+        };
+        let eof = |result: Result<TabixIndex, Error>| matches!(result, Err(Error::IOError(e)) if e.kind() == io::ErrorKind::UnexpectedEof);
+
+        // names length of a tbi index
+        let mut raw = TBI_MAGIC.to_vec();
+        for v in [1i32, 2, 1, 2, 0, b'#' as i32, 0, i32::MAX] {
+            raw.extend_from_slice(&v.to_le_bytes());
+        }
+        raw.extend_from_slice(b"chr1\0");
+        assert!(eof(read(&raw)));
+
+        // auxiliary data length of a csi index
+        let mut raw = CSI_MAGIC.to_vec();
+        for v in [14i32, 5, i32::MAX] {
+            raw.extend_from_slice(&v.to_le_bytes());
+        }
+        assert!(eof(read(&raw)));
+    }
+
+    #[test]
+    fn path() {
+        let (data, index) = indexed_vcf(false);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("variants.vcf.gz");
+        std::fs::write(&path, data).unwrap();
+
+        match from_path(&path) {
+            Err(Error::WithPath {
+                path: index,
+                source,
+            }) => {
+                assert_eq!(index, path);
+                assert_eq!(io::Error::from(*source).kind(), io::ErrorKind::NotFound);
+            }
+            _ => panic!("the index is missing"),
+        }
+
+        std::fs::write(dir.path().join("variants.vcf.gz.tbi"), index).unwrap();
+        let mut reader = from_path(&path).unwrap();
+        let lines: Vec<String> = reader
+            .query(&"chr1:50000".parse().unwrap())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("chr1\t20010\t"));
+        assert!(lines[2].starts_with("chr1\t100000\t"));
+    }
+}
@@ -0,0 +1,748 @@
+//! Block level random access to [xz](https://tukaani.org/xz/xz-file-format.txt) files.
+//!
+//! An xz stream ends with an index listing the compressed and uncompressed size of each block.
+//! [XzReader] reads the index of every stream in the file and seeks by decoding only the block
+//! holding the target offset. Files must have been written with several blocks
+//! (`xz --block-size`, `pixz`, multi-threaded `xz -T`, or [XzWriter]), a single block file can
+//! only be decoded from its start.
+//!
+//! [XzWriter] writes a new stream every [DEFAULT_BLOCK_SIZE] bytes, concatenated streams are
+//! read by any xz decoder.
+
+/* standard use */
+use std::io;
+use std::io::{BufRead, Read, Seek, Write};
+
+/* project use */
+use crate::error::Error;
+use crate::format::Format;
+use crate::level::Level;
+
+/// Amount of uncompressed data written in each block by [XzWriter].
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+const MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const FOOTER_MAGIC: [u8; 2] = [b'Y', b'Z'];
+const HEADER_SIZE: u64 = 12;
+
+/// Position of one block in the compressed and uncompressed stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    /// Offset of the block header in the compressed file.
+    pub compressed_offset: u64,
+    /// Offset of the first byte of the block in the uncompressed stream.
+    pub uncompressed_offset: u64,
+    /// Size of the block header, compressed data and check, without padding.
+    pub unpadded_size: u64,
+    /// Size of the uncompressed data of the block.
+    pub uncompressed_size: u64,
+    // stream flags of the stream holding the block
+    flags: [u8; 2],
+}
+
+/// Block index of an xz file, read from the index of each stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XzIndex {
+    blocks: Vec<Block>,
+}
+
+impl XzIndex {
+    /// Read the index of each stream, starting from the end of `reader`.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self, Error> {
+        let mut end = reader.seek(io::SeekFrom::End(0))?;
+
+        let mut streams = Vec::new();
+        while end > 0 {
+            end = skip_padding(reader, end)?;
+            let (start, blocks) = read_stream(reader, end)?;
+            streams.push(blocks);
+            end = start;
+        }
+
+        let mut blocks: Vec<Block> = streams.into_iter().rev().flatten().collect();
+        let mut uncompressed_offset = 0u64;
+        for block in blocks.iter_mut() {
+            block.uncompressed_offset = uncompressed_offset;
+            uncompressed_offset = uncompressed_offset
+                .checked_add(block.uncompressed_size)
+                .ok_or_else(|| corrupt("uncompressed size overflows"))?;
+        }
+
+        reader.seek(io::SeekFrom::Start(0))?;
+
+        Ok(XzIndex { blocks })
+    }
+
+    /// Blocks of the file, in order.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Length of the uncompressed stream.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.blocks
+            .last()
+            .map(|b| b.uncompressed_offset + b.uncompressed_size)
+            .unwrap_or(0)
+    }
+
+    /// Index of the block holding uncompressed offset `pos`.
+    fn block_for(&self, pos: u64) -> Option<usize> {
+        let i = self
+            .blocks
+            .partition_point(|b| b.uncompressed_offset + b.uncompressed_size <= pos);
+        (i < self.blocks.len()).then_some(i)
+    }
+}
+
+/// Move `end` before the stream padding (null bytes, by group of four).
+fn skip_padding<R: Read + Seek>(reader: &mut R, mut end: u64) -> io::Result<u64> {
+    let mut word = [0u8; 4];
+    while end >= 4 {
+        reader.seek(io::SeekFrom::Start(end - 4))?;
+        reader.read_exact(&mut word)?;
+        if word != [0; 4] {
+            break;
+        }
+        end -= 4;
+    }
+
+    Ok(end)
+}
+
+/// Read the stream ending at `end`, return its start and its blocks.
+fn read_stream<R: Read + Seek>(reader: &mut R, end: u64) -> io::Result<(u64, Vec<Block>)> {
+    if end < 2 * HEADER_SIZE {
+        return Err(Error::Truncated {
+            format: Format::Lzma,
+        }
+        .into());
+    }
+
+    let mut footer = [0u8; HEADER_SIZE as usize];
+    reader.seek(io::SeekFrom::Start(end - HEADER_SIZE))?;
+    reader.read_exact(&mut footer)?;
+    if footer[10..] != FOOTER_MAGIC || read_u32(&footer[..4]) != crc32(&footer[4..10]) {
+        return Err(corrupt("invalid stream footer"));
+    }
+    let flags = [footer[8], footer[9]];
+
+    let index_size = (u64::from(read_u32(&footer[4..8])) + 1) * 4;
+    if end < 2 * HEADER_SIZE + index_size {
+        return Err(corrupt("index is larger than the file"));
+    }
+    let index_start = end - HEADER_SIZE - index_size;
+    let mut index = vec![0u8; index_size as usize];
+    reader.seek(io::SeekFrom::Start(index_start))?;
+    reader.read_exact(&mut index)?;
+
+    // the index is only protected by its CRC, sizes can be anything
+    let records = read_index(&index)?;
+    let blocks_size = records
+        .iter()
+        .try_fold(0u64, |size, (unpadded, _)| {
+            size.checked_add(padded(*unpadded))
+        })
+        .filter(|size| *size <= index_start - HEADER_SIZE)
+        .ok_or_else(|| corrupt("blocks are larger than the file"))?;
+    let start = index_start - blocks_size - HEADER_SIZE;
+
+    let mut header = [0u8; HEADER_SIZE as usize];
+    reader.seek(io::SeekFrom::Start(start))?;
+    reader.read_exact(&mut header)?;
+    if header[..6] != MAGIC || header[6..8] != flags || read_u32(&header[8..]) != crc32(&flags) {
+        return Err(corrupt("invalid stream header"));
+    }
+
+    let mut compressed_offset = start + HEADER_SIZE;
+    let blocks = records
+        .into_iter()
+        .map(|(unpadded_size, uncompressed_size)| {
+            let block = Block {
+                compressed_offset,
+                uncompressed_offset: 0,
+                unpadded_size,
+                uncompressed_size,
+                flags,
+            };
+            compressed_offset += padded(unpadded_size);
+            block
+        })
+        .collect();
+
+    Ok((start, blocks))
+}
+
+/// Parse an index, return the unpadded and uncompressed size of each block.
+fn read_index(index: &[u8]) -> io::Result<Vec<(u64, u64)>> {
+    let (data, crc) = index.split_at(index.len() - 4);
+    if data.first() != Some(&0) || read_u32(crc) != crc32(data) {
+        return Err(corrupt("invalid index"));
+    }
+
+    let mut pos = 1;
+    let count = read_varint(data, &mut pos)?;
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let unpadded = read_varint(data, &mut pos)?;
+        let uncompressed = read_varint(data, &mut pos)?;
+        records.push((unpadded, uncompressed));
+    }
+    if data[pos..].iter().any(|b| *b != 0) || data.len() - pos >= 4 {
+        return Err(corrupt("invalid index padding"));
+    }
+
+    Ok(records)
+}
+
+/// Build a stream holding only `block`, so it can be decoded by liblzma.
+fn single_block_stream(block: &Block, data: &[u8]) -> Vec<u8> {
+    let mut stream = Vec::with_capacity(data.len() + 64);
+    stream.extend_from_slice(&MAGIC);
+    stream.extend_from_slice(&block.flags);
+    stream.extend_from_slice(&crc32(&block.flags).to_le_bytes());
+    stream.extend_from_slice(data);
+
+    let index_start = stream.len();
+    stream.extend_from_slice(&[0, 1]);
+    write_varint(&mut stream, block.unpadded_size);
+    write_varint(&mut stream, block.uncompressed_size);
+    while (stream.len() - index_start) % 4 != 0 {
+        stream.push(0);
+    }
+    let crc = crc32(&stream[index_start..]);
+    stream.extend_from_slice(&crc.to_le_bytes());
+
+    let backward_size = ((stream.len() - index_start) / 4 - 1) as u32;
+    let mut footer = backward_size.to_le_bytes().to_vec();
+    footer.extend_from_slice(&block.flags);
+    stream.extend_from_slice(&crc32(&footer).to_le_bytes());
+    stream.extend_from_slice(&footer);
+    stream.extend_from_slice(&FOOTER_MAGIC);
+
+    stream
+}
+
+/// An xz reader with [Seek] support over uncompressed offsets.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "lzma")] {
+/// use std::io::{Read, Seek, Write};
+/// use niffler::seek::xz::{XzReader, XzWriter};
+///
+/// let mut writer = XzWriter::with_block_size(Vec::new(), niffler::Level::Six, 4)?;
+/// writer.write_all(b"hello xz")?;
+///
+/// let mut reader = XzReader::new(std::io::Cursor::new(writer.finish()?))?;
+/// reader.seek(std::io::SeekFrom::Start(6))?;
+///
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents)?;
+/// assert_eq!(contents, "xz");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct XzReader<R: Read + Seek> {
+    reader: R,
+    index: XzIndex,
+    compressed: Vec<u8>,
+    buffer: Vec<u8>,
+    current_block: Option<usize>,
+    pos: u64,
+}
+
+impl<R: Read + Seek> XzReader<R> {
+    /// Open an xz stream, the block index is read from the end of the stream.
+    ///
+    /// Fails with [Error::UnsupportedFormat] on files with a single block, seeking would decode
+    /// the whole file.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let index = XzIndex::from_reader(&mut reader)?;
+        Self::with_index(reader, index)
+    }
+
+    /// Open an xz stream with an already read index.
+    pub fn with_index(reader: R, index: XzIndex) -> Result<Self, Error> {
+        if index.blocks.len() == 1 {
+            return Err(Error::UnsupportedFormat(Format::Lzma));
+        }
+
+        Ok(XzReader {
+            reader,
+            index,
+            compressed: Vec::new(),
+            buffer: Vec::new(),
+            current_block: None,
+            pos: 0,
+        })
+    }
+
+    /// Block index of the stream.
+    pub fn index(&self) -> &XzIndex {
+        &self.index
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn load_block(&mut self, i: usize) -> io::Result<()> {
+        if self.current_block == Some(i) {
+            return Ok(());
+        }
+
+        let block = self.index.blocks[i];
+        self.current_block = None;
+        self.reader
+            .seek(io::SeekFrom::Start(block.compressed_offset))?;
+        self.compressed
+            .resize(padded(block.unpadded_size) as usize, 0);
+        self.reader.read_exact(&mut self.compressed)?;
+
+        // the buffer grows with the decoded data, the size in the index isn't trusted
+        self.buffer.clear();
+        let stream = single_block_stream(&block, &self.compressed);
+        liblzma::read::XzDecoder::new(&stream[..])
+            .take(block.uncompressed_size.saturating_add(1))
+            .read_to_end(&mut self.buffer)?;
+        if self.buffer.len() as u64 != block.uncompressed_size {
+            return Err(corrupt(&format!("block {i} size doesn't match the index")));
+        }
+        self.current_block = Some(i);
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> BufRead for XzReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let i = match self.index.block_for(self.pos) {
+            Some(i) => i,
+            None => return Ok(&[]),
+        };
+        self.load_block(i)?;
+
+        let start = (self.pos - self.index.blocks[i].uncompressed_offset) as usize;
+        Ok(&self.buffer[start..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<R: Read + Seek> Read for XzReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for XzReader<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(p) => {
+                self.pos = p;
+                return Ok(p);
+            }
+            io::SeekFrom::Current(p) => (self.pos, p),
+            io::SeekFrom::End(p) => (self.index.uncompressed_len(), p),
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(p) => {
+                self.pos = p;
+                Ok(p)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// An xz writer producing files [XzReader] can seek in.
+///
+/// Data is cut in blocks of [DEFAULT_BLOCK_SIZE] bytes, each written as an xz stream, the end
+/// of the last stream is written by [finish](XzWriter::finish) or when the writer is dropped.
+/// Errors on drop are ignored, call `finish` to get them. Flushing the writer ends the current
+/// block.
+///
+/// [Seek] is only implemented to report the current uncompressed position
+/// (`SeekFrom::Current(0)`), any other move returns an error.
+pub struct XzWriter<W: Write> {
+    writer: Option<W>,
+    preset: u32,
+    block_size: usize,
+    buffer: Vec<u8>,
+    compressed: Vec<u8>,
+    uncompressed_pos: u64,
+}
+
+impl<W: Write> XzWriter<W> {
+    /// Create an xz writer with the given compression level (at most [Level::Nine]).
+    pub fn new(writer: W, level: Level) -> Self {
+        XzWriter::build(writer, level, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Create an xz writer, blocks hold at most `block_size` bytes of uncompressed data.
+    pub fn with_block_size(writer: W, level: Level, block_size: usize) -> Result<Self, Error> {
+        check_block_size(block_size)?;
+        Ok(XzWriter::build(writer, level, block_size))
+    }
+
+    fn build(writer: W, level: Level, block_size: usize) -> Self {
+        XzWriter {
+            writer: Some(writer),
+            preset: u32::from(level).min(9),
+            block_size,
+            buffer: Vec::new(),
+            compressed: Vec::new(),
+            uncompressed_pos: 0,
+        }
+    }
+
+    /// Change the amount of uncompressed data stored in each following block.
+    pub fn set_block_size(&mut self, block_size: usize) -> Result<(), Error> {
+        check_block_size(block_size)?;
+        self.block_size = block_size;
+        Ok(())
+    }
+
+    /// Number of uncompressed bytes written so far.
+    pub fn position(&self) -> u64 {
+        self.uncompressed_pos + self.buffer.len() as u64
+    }
+
+    /// Write the pending block, then return the underlying stream.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.try_finish()?;
+        Ok(self.writer.take().expect("writer is only taken by finish"))
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+
+        self.write_block()?;
+        self.writer
+            .as_mut()
+            .expect("writer is only taken by finish")
+            .flush()
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.compressed.clear();
+        let mut encoder = liblzma::write::XzEncoder::new(&mut self.compressed, self.preset);
+        encoder.write_all(&self.buffer)?;
+        encoder.finish()?;
+
+        self.writer
+            .as_mut()
+            .expect("writer is only taken by finish")
+            .write_all(&self.compressed)?;
+        self.uncompressed_pos += self.buffer.len() as u64;
+        self.buffer.clear();
+
+        Ok(())
+    }
+}
+
+fn check_block_size(block_size: usize) -> Result<(), Error> {
+    if block_size == 0 {
+        return Err(Error::InvalidBlockSize {
+            format: Format::Lzma,
+            size: block_size,
+        });
+    }
+
+    Ok(())
+}
+
+impl<W: Write> Write for XzWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() >= self.block_size {
+            self.write_block()?;
+        }
+
+        let len = buf.len().min(self.block_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Seek for XzWriter<W> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let current = self.position();
+        match pos {
+            io::SeekFrom::Current(0) => Ok(current),
+            io::SeekFrom::Start(p) if p == current => Ok(current),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "xz writer can only report its position",
+            )),
+        }
+    }
+}
+
+impl<W: Write> Drop for XzWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.try_finish();
+    }
+}
+
+fn padded(size: u64) -> u64 {
+    size.div_ceil(4) * 4
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> io::Result<u64> {
+    let mut value = 0u64;
+    for i in 0..9 {
+        let byte = *data.get(*pos).ok_or_else(|| corrupt("truncated index"))?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(corrupt("invalid integer in index"))
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        CRC32_TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn corrupt(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid xz file: {msg}"),
+    )
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().expect("slice of 4 bytes"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // LOREM_IPSUM compressed by `xz --block-size=40 -C crc32`
+    const MULTI_BLOCK: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x0, 0x0, 0x1, 0x69, 0x22, 0xde, 0x36, 0x2, 0x0, 0x21, 0x1,
+        0x16, 0x0, 0x0, 0x0, 0x74, 0x2f, 0xe5, 0xa3, 0x1, 0x0, 0x27, 0x4c, 0x6f, 0x72, 0x65, 0x6d,
+        0x20, 0x69, 0x70, 0x73, 0x75, 0x6d, 0x20, 0x64, 0x6f, 0x6c, 0x6f, 0x72, 0x20, 0x73, 0x69,
+        0x74, 0x20, 0x61, 0x6d, 0x65, 0x74, 0x2c, 0x20, 0x63, 0x6f, 0x6e, 0x73, 0x65, 0x63, 0x74,
+        0x65, 0x74, 0x75, 0x72, 0x20, 0x0, 0xb7, 0xfb, 0xd5, 0x7a, 0x2, 0x0, 0x21, 0x1, 0x16, 0x0,
+        0x0, 0x0, 0x74, 0x2f, 0xe5, 0xa3, 0x1, 0x0, 0x27, 0x61, 0x64, 0x69, 0x70, 0x69, 0x73, 0x63,
+        0x69, 0x6e, 0x67, 0x20, 0x65, 0x6c, 0x69, 0x74, 0x2e, 0x20, 0x55, 0x74, 0x20, 0x75, 0x6c,
+        0x74, 0x72, 0x69, 0x63, 0x69, 0x65, 0x73, 0x20, 0x73, 0x63, 0x65, 0x6c, 0x65, 0x72, 0x69,
+        0x73, 0x71, 0x75, 0x0, 0x8b, 0x4b, 0xba, 0xae, 0x2, 0x0, 0x21, 0x1, 0x16, 0x0, 0x0, 0x0,
+        0x74, 0x2f, 0xe5, 0xa3, 0x1, 0x0, 0x26, 0x65, 0x20, 0x64, 0x69, 0x61, 0x6d, 0x2c, 0x20,
+        0x61, 0x20, 0x73, 0x63, 0x65, 0x6c, 0x65, 0x72, 0x69, 0x73, 0x71, 0x75, 0x65, 0x20, 0x65,
+        0x6e, 0x69, 0x6d, 0x20, 0x73, 0x61, 0x67, 0x69, 0x74, 0x74, 0x69, 0x73, 0x20, 0x61, 0x74,
+        0x2e, 0x0, 0x0, 0x94, 0xcc, 0x12, 0x1f, 0x0, 0x3, 0x3c, 0x28, 0x3c, 0x28, 0x3b, 0x27, 0xf5,
+        0x6c, 0xd8, 0xc3, 0x3e, 0x30, 0xd, 0x8b, 0x2, 0x0, 0x0, 0x0, 0x0, 0x1, 0x59, 0x5a,
+    ];
+    const LOREM_IPSUM: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. Ut ultricies scelerisque diam, a scelerisque enim sagittis at.";
+
+    fn data() -> Vec<u8> {
+        (0..20_000u32)
+            .flat_map(|i| format!("line {i}\n").into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn multi_block() {
+        let index = XzIndex::from_reader(&mut io::Cursor::new(MULTI_BLOCK)).unwrap();
+        assert_eq!(index.blocks().len(), 3);
+        assert_eq!(index.blocks()[1].uncompressed_offset, 40);
+        assert_eq!(index.uncompressed_len(), LOREM_IPSUM.len() as u64);
+
+        let mut reader = XzReader::new(io::Cursor::new(MULTI_BLOCK)).unwrap();
+        for target in [100, 3, 40, 39, 80] {
+            reader.seek(io::SeekFrom::Start(target)).unwrap();
+            let mut buffer = Vec::new();
+            reader.read_to_end(&mut buffer).unwrap();
+            assert_eq!(buffer, &LOREM_IPSUM[target as usize..]);
+        }
+
+        // stream padding
+        let mut padded = MULTI_BLOCK.to_vec();
+        padded.extend_from_slice(&[0; 8]);
+        let mut reader = XzReader::new(io::Cursor::new(padded)).unwrap();
+        reader.seek(io::SeekFrom::End(-3)).unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, b"at.");
+    }
+
+    #[test]
+    fn writer() {
+        let data = data();
+        let mut writer = XzWriter::with_block_size(Vec::new(), Level::One, 10_000).unwrap();
+        writer.write_all(&data).unwrap();
+        assert_eq!(writer.stream_position().unwrap(), data.len() as u64);
+        assert!(writer.seek(io::SeekFrom::Start(0)).is_err());
+        assert!(writer.set_block_size(0).is_err());
+        let compressed = writer.finish().unwrap();
+
+        let mut decoded = Vec::new();
+        liblzma::read::XzDecoder::new_multi_decoder(&compressed[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+
+        let mut reader = XzReader::new(io::Cursor::new(compressed)).unwrap();
+        assert_eq!(reader.index().blocks().len(), data.len().div_ceil(10_000));
+
+        let len = data.len() as u64;
+        for target in [len - 10, 5, len / 2, 9_999, 10_000, len / 3 + 7, 0] {
+            reader.seek(io::SeekFrom::Start(target)).unwrap();
+            let mut buffer = vec![0u8; 10];
+            reader.read_exact(&mut buffer).unwrap();
+            assert_eq!(buffer, &data[target as usize..target as usize + 10]);
+        }
+        reader.seek(io::SeekFrom::Start(len + 10)).unwrap();
+        assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+    }
+
+    #[test]
+    fn single_block() {
+        let mut encoder = liblzma::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(LOREM_IPSUM).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let index = XzIndex::from_reader(&mut io::Cursor::new(&compressed)).unwrap();
+        assert_eq!(index.blocks().len(), 1);
+
+        assert!(matches!(
+            XzReader::new(io::Cursor::new(compressed)),
+            Err(Error::UnsupportedFormat(Format::Lzma))
+        ));
+    }
+
+    #[test]
+    fn corrupted() {
+        for position in [0, 30, 180, MULTI_BLOCK.len() - 1] {
+            let mut corrupted = MULTI_BLOCK.to_vec();
+            corrupted[position] ^= 0x4;
+
+            let result = XzReader::new(io::Cursor::new(corrupted))
+                .and_then(|mut r| Ok(r.read_to_end(&mut Vec::new())?));
+            assert!(result.is_err(), "corruption at {position} not detected");
+        }
+
+        assert!(XzIndex::from_reader(&mut io::Cursor::new(&MULTI_BLOCK[..100])).is_err());
+    }
+
+    /// MULTI_BLOCK with another index, with valid CRCs.
+    fn with_records(records: &[(u64, u64)]) -> Vec<u8> {
+        // index of 12 bytes and footer
+        let mut file = MULTI_BLOCK[..MULTI_BLOCK.len() - 24].to_vec();
+
+        let index_start = file.len();
+        file.push(0);
+        write_varint(&mut file, records.len() as u64);
+        for (unpadded, uncompressed) in records {
+            write_varint(&mut file, *unpadded);
+            write_varint(&mut file, *uncompressed);
+        }
+        while (file.len() - index_start) % 4 != 0 {
+            file.push(0);
+        }
+        let crc = crc32(&file[index_start..]);
+        file.extend_from_slice(&crc.to_le_bytes());
+
+        let mut footer = (((file.len() - index_start) / 4 - 1) as u32)
+            .to_le_bytes()
+            .to_vec();
+        footer.extend_from_slice(&MULTI_BLOCK[6..8]);
+        file.extend_from_slice(&crc32(&footer).to_le_bytes());
+        file.extend_from_slice(&footer);
+        file.extend_from_slice(&FOOTER_MAGIC);
+
+        file
+    }
+
+    #[test]
+    fn crafted_index() {
+        // largest size an index can hold
+        let huge = u64::MAX >> 1;
+        assert_eq!(
+            with_records(&[(0x3c, 40), (0x3c, 40), (0x3b, 39)]),
+            MULTI_BLOCK
+        );
+
+        for records in [
+            // sizes overflow once summed
+            [(0x3c, huge), (0x3c, huge), (0x3b, huge)],
+            [(huge, 40), (huge, 40), (huge, 39)],
+            // a block larger than the file
+            [(0x3c, 40), (0x1000, 40), (0x3b, 39)],
+        ] {
+            let result = XzIndex::from_reader(&mut io::Cursor::new(with_records(&records)));
+            assert!(result.is_err(), "{records:?}");
+        }
+
+        // the decoded block is smaller than the index says, nothing is allocated up front
+        let file = with_records(&[(0x3c, 40), (0x3c, 40), (0x3b, huge)]);
+        let mut reader = XzReader::new(io::Cursor::new(file)).unwrap();
+        reader.seek(io::SeekFrom::Start(80)).unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,758 @@
+//! Random access to ordinary gzip files with an access point index, like zlib's
+//! [zran](https://github.com/madler/zlib/blob/develop/examples/zran.c) example.
+//!
+//! A gzip stream can only be decoded from its start, so [GzipIndex::build] decodes it once
+//! and records an access point every `span` bytes of uncompressed data: the position of a
+//! deflate block in the compressed file (to the bit) and the 32 KiB of data preceding it.
+//! [ZranReader] then implements [Seek] by restarting decompression at the closest access point.
+//!
+//! The index can be saved next to the gzip file (`.gzidx`) to avoid decoding the whole file
+//! each time it's opened.
+//!
+//! # Example
+//! ```
+//! # fn main() -> Result<(), niffler::Error> {
+//! # #[cfg(feature = "gz")] {
+//! use std::io::{Read, Seek, Write};
+//!
+//! let text: String = (0..100_000).map(|i| format!("line {i:05}\n")).collect();
+//! let mut writer = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+//! writer.write_all(text.as_bytes())?;
+//! let file = std::io::Cursor::new(writer.finish()?);
+//!
+//! let mut reader = niffler::seek::zran::get_reader(Box::new(file), None)?;
+//! reader.seek(std::io::SeekFrom::Start(50_000 * 11))?;
+//!
+//! let mut line = [0u8; 10];
+//! reader.read_exact(&mut line)?;
+//! assert_eq!(&line, b"line 50000");
+//! # }
+//! # Ok(())
+//! # }
+//! ```
+
+/* standard use */
+use std::io;
+use std::io::{BufRead, Read, Seek, Write};
+use std::path::Path;
+
+/* project use */
+use crate::basic::compression::Format;
+use crate::error::Error;
+use crate::seek::compression::ReadSeek;
+use crate::seek::inflate::{corrupt, inflate_block, BitReader, WINDOW_SIZE};
+
+/// Default distance between two access points, 1 MiB of uncompressed data.
+pub const DEFAULT_SPAN: u64 = 1 << 20;
+
+/// Suffix of the index file saved next to the gzip file.
+pub const INDEX_SUFFIX: &str = ".gzidx";
+
+const INDEX_MAGIC: &[u8; 8] = b"GZIDX\x00\x00\x01";
+
+/// A position where decompression can restart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessPoint {
+    /// Offset in the uncompressed stream.
+    pub uncompressed_offset: u64,
+    /// Offset of the byte holding the first bit of the deflate block in the compressed file.
+    pub compressed_offset: u64,
+    /// Number of bits of this byte used by the previous block.
+    pub bits: u8,
+    window: Vec<u8>,
+}
+
+impl AccessPoint {
+    /// Uncompressed data preceding the access point, at most 32 KiB.
+    pub fn window(&self) -> &[u8] {
+        &self.window
+    }
+}
+
+/// Access point index of a gzip file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GzipIndex {
+    span: u64,
+    uncompressed_len: u64,
+    points: Vec<AccessPoint>,
+}
+
+impl GzipIndex {
+    /// Decode a gzip stream and record an access point every `span` bytes of uncompressed data.
+    pub fn build<R: Read>(reader: R, span: u64) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(io::BufReader::new(reader));
+        let mut points = Vec::new();
+        let mut last = 0;
+
+        loop {
+            let position = decoder.uncompressed_position();
+            if decoder.at_block_boundary() && position - last >= span.max(1) {
+                let bit_position = decoder.input.bit_position();
+                points.push(AccessPoint {
+                    uncompressed_offset: position,
+                    compressed_offset: bit_position / 8,
+                    bits: (bit_position % 8) as u8,
+                    window: decoder.window().to_vec(),
+                });
+                last = position;
+            }
+
+            decoder.start = decoder.out.len();
+            if !decoder.fill()? {
+                break;
+            }
+        }
+
+        Ok(GzipIndex {
+            span,
+            uncompressed_len: decoder.uncompressed_position(),
+            points,
+        })
+    }
+
+    /// Read an index written by [write_to](GzipIndex::write_to).
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(corrupt_index(0));
+        }
+
+        let span = read_u64(&mut reader)?;
+        let uncompressed_len = read_u64(&mut reader)?;
+        let count = read_u64(&mut reader)?;
+
+        let mut points = Vec::new();
+        for _ in 0..count {
+            let uncompressed_offset = read_u64(&mut reader)?;
+            let compressed_offset = read_u64(&mut reader)?;
+            let mut bits = [0u8; 1];
+            reader.read_exact(&mut bits)?;
+            let len = read_u64(&mut reader)?;
+
+            let mut window = Vec::with_capacity(WINDOW_SIZE);
+            flate2::read::DeflateDecoder::new((&mut reader).take(len))
+                .take(WINDOW_SIZE as u64 + 1)
+                .read_to_end(&mut window)?;
+
+            // points are sorted, inside the uncompressed data and preceded by their window
+            let previous = points
+                .last()
+                .map(|p: &AccessPoint| (p.uncompressed_offset, p.compressed_offset, p.bits));
+            if bits[0] > 7
+                || window.len() > WINDOW_SIZE
+                || window.len() as u64 > uncompressed_offset
+                || uncompressed_offset > uncompressed_len
+                || previous.is_some_and(|(uncompressed, compressed, previous_bits)| {
+                    uncompressed >= uncompressed_offset
+                        || (compressed, previous_bits) >= (compressed_offset, bits[0])
+                })
+            {
+                return Err(corrupt_index(compressed_offset));
+            }
+
+            points.push(AccessPoint {
+                uncompressed_offset,
+                compressed_offset,
+                bits: bits[0],
+                window,
+            });
+        }
+
+        Ok(GzipIndex {
+            span,
+            uncompressed_len,
+            points,
+        })
+    }
+
+    /// Read an index from a file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        Self::from_reader(io::BufReader::new(crate::utils::open(path)?))
+            .map_err(|e| e.with_path(path))
+    }
+
+    /// Write the index, windows are compressed with deflate.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&self.span.to_le_bytes())?;
+        writer.write_all(&self.uncompressed_len.to_le_bytes())?;
+        writer.write_all(&(self.points.len() as u64).to_le_bytes())?;
+
+        for point in &self.points {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&point.window)?;
+            let window = encoder.finish()?;
+
+            writer.write_all(&point.uncompressed_offset.to_le_bytes())?;
+            writer.write_all(&point.compressed_offset.to_le_bytes())?;
+            writer.write_all(&[point.bits])?;
+            writer.write_all(&(window.len() as u64).to_le_bytes())?;
+            writer.write_all(&window)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the index to a file.
+    pub fn to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut writer = io::BufWriter::new(crate::utils::create(path)?);
+        self.write_to(&mut writer)
+            .and_then(|()| Ok(writer.flush()?))
+            .map_err(|e| e.with_path(path))
+    }
+
+    /// Distance between access points used to build the index.
+    pub fn span(&self) -> u64 {
+        self.span
+    }
+
+    /// Length of the uncompressed stream.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.uncompressed_len
+    }
+
+    /// Access points, sorted by offset. The start of the file is not included.
+    pub fn points(&self) -> &[AccessPoint] {
+        &self.points
+    }
+
+    fn point_before(&self, pos: u64) -> Option<&AccessPoint> {
+        match self
+            .points
+            .partition_point(|p| p.uncompressed_offset <= pos)
+        {
+            0 => None,
+            n => Some(&self.points[n - 1]),
+        }
+    }
+}
+
+/// Seekable reader over the uncompressed data of a gzip file.
+///
+/// Seeking moves to the closest [AccessPoint] before the target and decodes up to it, short
+/// forward seeks keep decoding from the current position.
+pub struct ZranReader<R: Read + Seek> {
+    decoder: Decoder<io::BufReader<R>>,
+    index: GzipIndex,
+    pos: u64,
+}
+
+impl<R: Read + Seek> ZranReader<R> {
+    /// Build an index with [DEFAULT_SPAN] and create a reader.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let index = GzipIndex::build(&mut reader, DEFAULT_SPAN)?;
+        reader.seek(io::SeekFrom::Start(0))?;
+
+        Ok(Self::with_index(reader, index))
+    }
+
+    /// Create a reader with an existing index, `reader` must be at the start of the file.
+    pub fn with_index(reader: R, index: GzipIndex) -> Self {
+        ZranReader {
+            decoder: Decoder::new(io::BufReader::new(reader)),
+            index,
+            pos: 0,
+        }
+    }
+
+    /// The access point index.
+    pub fn index(&self) -> &GzipIndex {
+        &self.index
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> R {
+        self.decoder.input.into_inner().into_inner()
+    }
+
+    fn move_to(&mut self, pos: u64) -> io::Result<()> {
+        let decoded_end = self.decoder.uncompressed_position();
+        let point = self.index.point_before(pos);
+
+        let restart = match point {
+            _ if pos < self.decoder.offset => true,
+            Some(point) => point.uncompressed_offset > decoded_end,
+            None => false,
+        };
+        if restart {
+            match point {
+                Some(point) => self.decoder.resume(point)?,
+                None => self.decoder.restart()?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> BufRead for ZranReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos < self.decoder.offset
+            || self.pos >= self.decoder.uncompressed_position() + self.index.span.max(1)
+        {
+            self.move_to(self.pos)?;
+        }
+
+        while self.pos >= self.decoder.uncompressed_position() {
+            self.decoder.start = self.decoder.out.len();
+            if !self.decoder.fill()? {
+                return Ok(&[]);
+            }
+        }
+
+        self.decoder.start = (self.pos - self.decoder.offset) as usize;
+        Ok(&self.decoder.out[self.decoder.start..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<R: Read + Seek> Read for ZranReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for ZranReader<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(p) => {
+                self.pos = p;
+                return Ok(p);
+            }
+            io::SeekFrom::Current(p) => (self.pos, p),
+            io::SeekFrom::End(p) => (self.index.uncompressed_len, p),
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(p) => {
+                self.pos = p;
+                Ok(p)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Check `in_stream` is a gzip file and return a seekable reader over its uncompressed data.
+///
+/// If `index` is `None` the index is built with [DEFAULT_SPAN], which decodes the whole file.
+pub fn get_reader<'a>(
+    mut in_stream: Box<dyn ReadSeek + 'a>,
+    index: Option<GzipIndex>,
+) -> Result<Box<dyn ReadSeek + 'a>, Error> {
+    match crate::seek::compression::sniff_format(&mut in_stream)? {
+        Format::Gzip | Format::BGzip => (),
+        format => return Err(Error::UnsupportedFormat(format)),
+    }
+
+    match index {
+        Some(index) => {
+            let len = in_stream.seek(io::SeekFrom::End(0))?;
+            in_stream.seek(io::SeekFrom::Start(0))?;
+            if let Some(point) = index.points.last().filter(|p| p.compressed_offset >= len) {
+                // the index points past the end of this file
+                return Err(corrupt_index(point.compressed_offset));
+            }
+
+            Ok(Box::new(ZranReader::with_index(in_stream, index)))
+        }
+        None => Ok(Box::new(ZranReader::new(in_stream)?)),
+    }
+}
+
+/// Open a gzip file with the index saved next to it (`path` with a [INDEX_SUFFIX] suffix),
+/// the index is built in memory if this file doesn't exist.
+pub fn from_path<'a, P: AsRef<Path>>(path: P) -> Result<Box<dyn ReadSeek + 'a>, Error> {
+    let index_path = index_path(path.as_ref());
+    let index = if index_path.exists() {
+        Some(GzipIndex::from_path(index_path)?)
+    } else {
+        None
+    };
+
+    let path = path.as_ref();
+    let readable = io::BufReader::new(crate::utils::open(path)?);
+    get_reader(Box::new(readable), index).map_err(|e| e.with_path(path))
+}
+
+/// Build the index of a gzip file with an access point every `span` bytes and save it next to
+/// the file, where [from_path] will find it.
+pub fn write_index<P: AsRef<Path>>(path: P, span: u64) -> Result<GzipIndex, Error> {
+    let path = path.as_ref();
+    let index = GzipIndex::build(io::BufReader::new(crate::utils::open(path)?), span)
+        .map_err(|e| e.with_path(path))?;
+    index.to_path(index_path(path))?;
+
+    Ok(index)
+}
+
+fn index_path(path: &Path) -> std::path::PathBuf {
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(INDEX_SUFFIX);
+    index_path.into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Header,
+    Blocks,
+    Trailer,
+    Done,
+}
+
+/// Decode gzip members, block by block.
+struct Decoder<R> {
+    input: BitReader<R>,
+    state: State,
+    members: u64,
+    // decoded data, with at least WINDOW_SIZE bytes of history before `start`
+    out: Vec<u8>,
+    start: usize,
+    // uncompressed offset of out[0]
+    offset: u64,
+    // only checked if the member is decoded from its start
+    crc: Option<flate2::Crc>,
+}
+
+impl<R: BufRead> Decoder<R> {
+    fn new(input: R) -> Self {
+        Decoder {
+            input: BitReader::new(input, 0),
+            state: State::Header,
+            members: 0,
+            out: Vec::new(),
+            start: 0,
+            offset: 0,
+            crc: None,
+        }
+    }
+
+    fn uncompressed_position(&self) -> u64 {
+        self.offset + self.out.len() as u64
+    }
+
+    fn at_block_boundary(&self) -> bool {
+        self.state == State::Blocks
+    }
+
+    fn window(&self) -> &[u8] {
+        &self.out[self.out.len().saturating_sub(WINDOW_SIZE)..]
+    }
+
+    /// Decode data after the end of `out`, return false at the end of the file.
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.start > 2 * WINDOW_SIZE {
+            let cut = self.start - WINDOW_SIZE;
+            self.out.drain(..cut);
+            self.start -= cut;
+            self.offset += cut as u64;
+        }
+
+        loop {
+            match self.state {
+                State::Header => {
+                    if self.members > 0 && self.input.at_end()? {
+                        self.state = State::Done;
+                        continue;
+                    }
+                    self.read_header()?;
+                    self.members += 1;
+                    self.crc = Some(flate2::Crc::new());
+                    self.state = State::Blocks;
+                }
+                State::Blocks => {
+                    let before = self.out.len();
+                    if inflate_block(&mut self.input, &mut self.out)? {
+                        self.state = State::Trailer;
+                    }
+                    if let Some(crc) = self.crc.as_mut() {
+                        crc.update(&self.out[before..]);
+                    }
+                    if self.out.len() > before {
+                        return Ok(true);
+                    }
+                }
+                State::Trailer => {
+                    self.input.align();
+                    let mut trailer = [0u8; 8];
+                    self.input.read_bytes(&mut trailer)?;
+                    if let Some(crc) = self.crc.take() {
+                        if trailer[..4] != crc.sum().to_le_bytes()
+                            || trailer[4..] != crc.amount().to_le_bytes()
+                        {
+                            return Err(corrupt("gzip checksum mismatch"));
+                        }
+                    }
+                    self.state = State::Header;
+                }
+                State::Done => return Ok(false),
+            }
+        }
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let mut header = [0u8; 10];
+        self.input.read_bytes(&mut header)?;
+        if header[..3] != [0x1f, 0x8b, 0x08] {
+            return Err(corrupt("invalid gzip header"));
+        }
+
+        let flags = header[3];
+        if flags & 0x04 != 0 {
+            let len = self.input.read_bits(16)?;
+            for _ in 0..len {
+                self.input.read_bits(8)?;
+            }
+        }
+        for flag in [0x08, 0x10] {
+            if flags & flag != 0 {
+                while self.input.read_bits(8)? != 0 {}
+            }
+        }
+        if flags & 0x02 != 0 {
+            self.input.read_bits(16)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead + Seek> Decoder<R> {
+    fn restart(&mut self) -> io::Result<()> {
+        self.input.seek(0, 0)?;
+        self.state = State::Header;
+        self.members = 0;
+        self.out.clear();
+        self.start = 0;
+        self.offset = 0;
+
+        Ok(())
+    }
+
+    fn resume(&mut self, point: &AccessPoint) -> io::Result<()> {
+        let offset = point
+            .uncompressed_offset
+            .checked_sub(point.window.len() as u64)
+            .ok_or_else(|| corrupt_index(point.compressed_offset))?;
+
+        self.input.seek(point.compressed_offset, point.bits)?;
+        self.state = State::Blocks;
+        self.members = 1;
+        self.out.clear();
+        self.out.extend_from_slice(&point.window);
+        self.start = self.out.len();
+        self.offset = offset;
+        self.crc = None;
+
+        Ok(())
+    }
+}
+
+/// An access point index that doesn't describe the gzip file, at the offset of the faulty point.
+fn corrupt_index(offset: u64) -> Error {
+    Error::Corrupt {
+        format: Format::Gzip,
+        offset,
+    }
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn data() -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut state = 42u64;
+        for i in 0..60_000u64 {
+            // mix of repeated text and noise, to get all kinds of blocks
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            if i % 3 == 0 {
+                data.extend_from_slice(&state.to_le_bytes()[..3]);
+            } else {
+                data.extend_from_slice(format!("line {i}\n").as_bytes());
+            }
+        }
+        data
+    }
+
+    fn gzip(data: &[u8], level: u32) -> Vec<u8> {
+        let mut writer = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn check_seeks(reader: &mut ZranReader<io::Cursor<Vec<u8>>>, data: &[u8]) {
+        let len = data.len() as u64;
+        for target in [len - 10, 5, len / 2, 100_000, 3, len / 3 + 7, len - 1, 0] {
+            reader.seek(io::SeekFrom::Start(target)).unwrap();
+            let mut buffer = vec![0u8; 100.min((len - target) as usize)];
+            reader.read_exact(&mut buffer).unwrap();
+            assert_eq!(
+                buffer,
+                &data[target as usize..target as usize + buffer.len()]
+            );
+        }
+
+        reader.seek(io::SeekFrom::End(-4)).unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, &data[data.len() - 4..]);
+
+        reader.seek(io::SeekFrom::Start(len + 10)).unwrap();
+        assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+    }
+
+    #[test]
+    fn index() {
+        let data = data();
+        for level in [0, 1, 6, 9] {
+            let compressed = gzip(&data, level);
+
+            let index = GzipIndex::build(&compressed[..], 32 * 1024).unwrap();
+            assert_eq!(index.uncompressed_len(), data.len() as u64);
+            assert!(index.points().len() >= 2);
+            for point in index.points() {
+                let offset = point.uncompressed_offset as usize;
+                assert_eq!(point.window(), &data[offset - WINDOW_SIZE..offset]);
+            }
+
+            let mut reader = ZranReader::with_index(io::Cursor::new(compressed), index);
+            let mut buffer = Vec::new();
+            reader.read_to_end(&mut buffer).unwrap();
+            assert_eq!(buffer, data);
+
+            check_seeks(&mut reader, &data);
+        }
+    }
+
+    #[test]
+    fn multi_member() {
+        let data = data();
+        let (first, second) = data.split_at(data.len() / 3);
+        let mut compressed = gzip(first, 6);
+        compressed.extend(gzip(second, 1));
+
+        let mut reader = ZranReader::new(io::Cursor::new(compressed.clone())).unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, data);
+        check_seeks(&mut reader, &data);
+
+        let index = GzipIndex::build(&compressed[..], 10_000).unwrap();
+        let mut reader = ZranReader::with_index(io::Cursor::new(compressed), index);
+        check_seeks(&mut reader, &data);
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut compressed = gzip(&data(), 6);
+        let len = compressed.len();
+        compressed[len - 6] ^= 0xff;
+        assert!(GzipIndex::build(&compressed[..], DEFAULT_SPAN).is_err());
+
+        assert!(GzipIndex::build(&compressed[..len / 2], DEFAULT_SPAN).is_err());
+    }
+
+    #[test]
+    fn sidecar() {
+        let data = data();
+        let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+        ofile.write_all(&gzip(&data, 6)).unwrap();
+
+        let index = write_index(ofile.path(), 50_000).unwrap();
+        let index_path = index_path(ofile.path());
+        let saved = GzipIndex::from_path(&index_path).unwrap();
+        std::fs::remove_file(&index_path).unwrap();
+        assert_eq!(saved, index);
+        assert_eq!(saved.span(), 50_000);
+
+        let mut buffer = Vec::new();
+        saved.write_to(&mut buffer).unwrap();
+        buffer[0] = b'X';
+        assert!(GzipIndex::from_reader(&buffer[..]).is_err());
+
+        // the error names the index, not the gzip file
+        std::fs::write(&index_path, &buffer).unwrap();
+        assert!(matches!(
+            from_path(ofile.path()),
+            Err(Error::WithPath { path, .. }) if path == index_path
+        ));
+        std::fs::remove_file(&index_path).unwrap();
+
+        let mut reader = from_path(ofile.path()).unwrap();
+        reader.seek(io::SeekFrom::Start(300_000)).unwrap();
+        let mut buffer = [0u8; 64];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, &data[300_000..300_064]);
+    }
+
+    #[test]
+    fn corrupted_sidecar() {
+        let compressed = gzip(&data(), 6);
+        let index = GzipIndex::build(&compressed[..], 50_000).unwrap();
+        assert!(index.points().len() >= 3);
+
+        let mut unsorted = index.clone();
+        unsorted.points.swap(0, 1);
+        let mut past_end = index.clone();
+        past_end.uncompressed_len = index.points[1].uncompressed_offset - 1;
+        let mut short_window = index.clone();
+        short_window.points[0].uncompressed_offset = 10;
+        let mut same_block = index.clone();
+        same_block.points[1].compressed_offset = index.points[0].compressed_offset;
+        same_block.points[1].bits = index.points[0].bits;
+
+        for corrupted in [unsorted, past_end, short_window, same_block] {
+            let mut buffer = Vec::new();
+            corrupted.write_to(&mut buffer).unwrap();
+            assert!(matches!(
+                GzipIndex::from_reader(&buffer[..]),
+                Err(Error::Corrupt {
+                    format: Format::Gzip,
+                    ..
+                })
+            ));
+        }
+
+        // an index of another, larger file
+        let small = gzip(&data()[..100_000], 6);
+        assert!(matches!(
+            get_reader(Box::new(io::Cursor::new(small)), Some(index)),
+            Err(Error::Corrupt {
+                format: Format::Gzip,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn not_gzip() {
+        let result = get_reader(Box::new(io::Cursor::new(b"plain text file".to_vec())), None);
+        assert!(matches!(result, Err(Error::UnsupportedFormat(Format::No))));
+
+        let result = get_reader(Box::new(io::Cursor::new(vec![0x1f, 0x8b])), None);
+        assert!(matches!(result, Err(Error::FileTooShort)));
+    }
+}
@@ -0,0 +1,623 @@
+//! Random access to files in the zstd
+//! [seekable format](https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md).
+//!
+//! A seekable zstd file is a series of independent zstd frames followed by a skippable frame
+//! holding the seek table: the compressed and uncompressed size of each frame. The file can be
+//! read by any zstd decoder, [SeekableReader] uses the seek table to implement
+//! [Seek](std::io::Seek) over uncompressed offsets.
+//!
+//! [SeekableWriter] cuts data in frames of a configurable size and writes the seek table.
+
+/* standard use */
+use std::io;
+use std::io::{BufRead, Read, Seek};
+
+/* project use */
+use crate::error::Error;
+use crate::format::Format;
+use crate::level::Level;
+
+/// Magic number of the skippable frame holding the seek table.
+pub const SKIPPABLE_MAGIC: u32 = 0x184D2A5E;
+
+/// Magic number at the end of the seek table.
+pub const SEEKABLE_MAGIC: u32 = 0x8F92EAB1;
+
+/// Amount of uncompressed data written in each frame by [SeekableWriter].
+pub const DEFAULT_FRAME_SIZE: usize = 1 << 20;
+
+/// Largest frame size allowed by the format.
+pub const MAX_FRAME_SIZE: usize = 1 << 30;
+
+const FOOTER_SIZE: u64 = 9;
+const CHECKSUM_FLAG: u8 = 0x80;
+const RESERVED_FLAGS: u8 = 0x7c;
+
+/// Position of one frame in the compressed and uncompressed stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// Offset of the frame in the compressed file.
+    pub compressed_offset: u64,
+    /// Offset of the first byte of the frame in the uncompressed stream.
+    pub uncompressed_offset: u64,
+    /// Size of the compressed frame.
+    pub compressed_size: u32,
+    /// Size of the uncompressed data of the frame.
+    pub uncompressed_size: u32,
+}
+
+/// Seek table of a seekable zstd file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeekTable {
+    frames: Vec<Frame>,
+}
+
+impl SeekTable {
+    /// Read the seek table at the end of `reader`.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self, Error> {
+        let end = reader.seek(io::SeekFrom::End(0))?;
+        if end < FOOTER_SIZE {
+            return Err(Error::Truncated {
+                format: Format::Zstd,
+            });
+        }
+
+        let mut footer = [0u8; FOOTER_SIZE as usize];
+        reader.seek(io::SeekFrom::Start(end - FOOTER_SIZE))?;
+        reader.read_exact(&mut footer)?;
+
+        let count = u64::from(read_u32(&footer[..4]));
+        let descriptor = footer[4];
+        if read_u32(&footer[5..]) != SEEKABLE_MAGIC {
+            return Err(corrupt("no seek table at the end of the file").into());
+        }
+        if descriptor & RESERVED_FLAGS != 0 {
+            return Err(corrupt("reserved seek table flags are set").into());
+        }
+
+        let entry_size: u64 = if descriptor & CHECKSUM_FLAG != 0 {
+            12
+        } else {
+            8
+        };
+        let table_size = count * entry_size + FOOTER_SIZE;
+        if end < table_size + 8 {
+            return Err(corrupt("seek table is larger than the file").into());
+        }
+
+        let mut table = vec![0u8; table_size as usize + 8];
+        reader.seek(io::SeekFrom::Start(end - table_size - 8))?;
+        reader.read_exact(&mut table)?;
+        if read_u32(&table[..4]) != SKIPPABLE_MAGIC
+            || u64::from(read_u32(&table[4..8])) != table_size
+        {
+            return Err(corrupt("invalid seek table frame header").into());
+        }
+
+        let mut frames = Vec::with_capacity(count as usize);
+        let (mut compressed_offset, mut uncompressed_offset) = (0, 0);
+        let entries = &table[8..table.len() - FOOTER_SIZE as usize];
+        for entry in entries.chunks_exact(entry_size as usize) {
+            let frame = Frame {
+                compressed_offset,
+                uncompressed_offset,
+                compressed_size: read_u32(&entry[..4]),
+                uncompressed_size: read_u32(&entry[4..8]),
+            };
+            compressed_offset += u64::from(frame.compressed_size);
+            uncompressed_offset += u64::from(frame.uncompressed_size);
+            frames.push(frame);
+        }
+        if compressed_offset > end - table_size - 8 {
+            return Err(corrupt("seek table frames are larger than the file").into());
+        }
+
+        Ok(SeekTable { frames })
+    }
+
+    /// Write the seek table, as a skippable frame without checksums.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), Error> {
+        let table_size = self.frames.len() as u64 * 8 + FOOTER_SIZE;
+        let table_size = u32::try_from(table_size)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many frames"))?;
+
+        writer.write_all(&SKIPPABLE_MAGIC.to_le_bytes())?;
+        writer.write_all(&table_size.to_le_bytes())?;
+        for frame in &self.frames {
+            writer.write_all(&frame.compressed_size.to_le_bytes())?;
+            writer.write_all(&frame.uncompressed_size.to_le_bytes())?;
+        }
+        writer.write_all(&(self.frames.len() as u32).to_le_bytes())?;
+        writer.write_all(&[0])?;
+        writer.write_all(&SEEKABLE_MAGIC.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    /// Frames of the file, in order.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Length of the uncompressed stream.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.frames
+            .last()
+            .map(|f| f.uncompressed_offset + u64::from(f.uncompressed_size))
+            .unwrap_or(0)
+    }
+
+    /// Index of the frame holding uncompressed offset `pos`.
+    fn frame_for(&self, pos: u64) -> Option<usize> {
+        let i = self
+            .frames
+            .partition_point(|f| f.uncompressed_offset + u64::from(f.uncompressed_size) <= pos);
+        (i < self.frames.len()).then_some(i)
+    }
+
+    fn push(&mut self, compressed_size: u32, uncompressed_size: u32) {
+        let (compressed_offset, uncompressed_offset) = match self.frames.last() {
+            Some(f) => (
+                f.compressed_offset + u64::from(f.compressed_size),
+                f.uncompressed_offset + u64::from(f.uncompressed_size),
+            ),
+            None => (0, 0),
+        };
+
+        self.frames.push(Frame {
+            compressed_offset,
+            uncompressed_offset,
+            compressed_size,
+            uncompressed_size,
+        });
+    }
+}
+
+/// A seekable zstd reader with [Seek] support over uncompressed offsets.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "zstd")] {
+/// use std::io::{Read, Seek, Write};
+/// use niffler::seek::zstd_seekable::{SeekableReader, SeekableWriter};
+///
+/// let mut writer = SeekableWriter::new(Vec::new(), niffler::Level::Three);
+/// writer.write_all(b"hello zstd")?;
+///
+/// let mut reader = SeekableReader::new(std::io::Cursor::new(writer.finish()?))?;
+/// reader.seek(std::io::SeekFrom::Start(6))?;
+///
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents)?;
+/// assert_eq!(contents, "zstd");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct SeekableReader<R: Read + Seek> {
+    reader: R,
+    table: SeekTable,
+    decompressor: zstd::bulk::Decompressor<'static>,
+    compressed: Vec<u8>,
+    buffer: Vec<u8>,
+    current_frame: Option<usize>,
+    pos: u64,
+}
+
+impl<R: Read + Seek> SeekableReader<R> {
+    /// Open a seekable zstd stream, the seek table is read from the end of the stream.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let table = SeekTable::from_reader(&mut reader)?;
+        Self::with_table(reader, table)
+    }
+
+    /// Open a seekable zstd stream with an already read seek table.
+    pub fn with_table(reader: R, table: SeekTable) -> Result<Self, Error> {
+        Ok(SeekableReader {
+            reader,
+            table,
+            decompressor: zstd::bulk::Decompressor::new()?,
+            compressed: Vec::new(),
+            buffer: Vec::new(),
+            current_frame: None,
+            pos: 0,
+        })
+    }
+
+    /// Seek table of the stream.
+    pub fn seek_table(&self) -> &SeekTable {
+        &self.table
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn load_frame(&mut self, i: usize) -> io::Result<()> {
+        if self.current_frame == Some(i) {
+            return Ok(());
+        }
+
+        let frame = self.table.frames[i];
+        self.current_frame = None;
+        self.reader
+            .seek(io::SeekFrom::Start(frame.compressed_offset))?;
+        self.compressed.resize(frame.compressed_size as usize, 0);
+        self.reader.read_exact(&mut self.compressed)?;
+
+        self.buffer.clear();
+        self.buffer.reserve(frame.uncompressed_size as usize);
+        self.decompressor
+            .decompress_to_buffer(&self.compressed, &mut self.buffer)?;
+        if self.buffer.len() != frame.uncompressed_size as usize {
+            return Err(corrupt(&format!(
+                "frame {i} size doesn't match the seek table"
+            )));
+        }
+        self.current_frame = Some(i);
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> BufRead for SeekableReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let i = match self.table.frame_for(self.pos) {
+            Some(i) => i,
+            None => return Ok(&[]),
+        };
+        self.load_frame(i)?;
+
+        let start = (self.pos - self.table.frames[i].uncompressed_offset) as usize;
+        Ok(&self.buffer[start..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableReader<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(p) => {
+                self.pos = p;
+                return Ok(p);
+            }
+            io::SeekFrom::Current(p) => (self.pos, p),
+            io::SeekFrom::End(p) => (self.table.uncompressed_len(), p),
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(p) => {
+                self.pos = p;
+                Ok(p)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// A seekable zstd writer.
+///
+/// Data is cut in frames of [DEFAULT_FRAME_SIZE] bytes, each compressed independently, and
+/// the seek table is written by [finish](SeekableWriter::finish) or when the writer is
+/// dropped. Errors on drop are ignored, call `finish` to get them. Flushing the writer ends
+/// the current frame.
+///
+/// [Seek] is only implemented to report the current uncompressed position
+/// (`SeekFrom::Current(0)`), any other move returns an error.
+pub struct SeekableWriter<W: io::Write> {
+    writer: Option<W>,
+    compressor: Option<zstd::bulk::Compressor<'static>>,
+    level: i32,
+    frame_size: usize,
+    buffer: Vec<u8>,
+    compressed: Vec<u8>,
+    table: SeekTable,
+}
+
+impl<W: io::Write> SeekableWriter<W> {
+    /// Create a seekable zstd writer with the given compression level.
+    pub fn new(writer: W, level: Level) -> Self {
+        SeekableWriter::build(writer, level, DEFAULT_FRAME_SIZE)
+    }
+
+    /// Create a seekable zstd writer, frames hold at most `frame_size` bytes of uncompressed data.
+    pub fn with_frame_size(writer: W, level: Level, frame_size: usize) -> Result<Self, Error> {
+        check_frame_size(frame_size)?;
+        Ok(SeekableWriter::build(writer, level, frame_size))
+    }
+
+    fn build(writer: W, level: Level, frame_size: usize) -> Self {
+        SeekableWriter {
+            writer: Some(writer),
+            compressor: None,
+            level: level.into(),
+            frame_size,
+            buffer: Vec::new(),
+            compressed: Vec::new(),
+            table: SeekTable::default(),
+        }
+    }
+
+    /// Change the amount of uncompressed data stored in each following frame.
+    pub fn set_frame_size(&mut self, frame_size: usize) -> Result<(), Error> {
+        check_frame_size(frame_size)?;
+        self.frame_size = frame_size;
+        Ok(())
+    }
+
+    /// Number of uncompressed bytes written so far.
+    pub fn position(&self) -> u64 {
+        self.table.uncompressed_len() + self.buffer.len() as u64
+    }
+
+    /// Write the pending frame and the seek table, then return the underlying stream.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.try_finish()?;
+        Ok(self.writer.take().expect("writer is only taken by finish"))
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+
+        if self.table.frames().is_empty() {
+            // a frame of empty data, a lone seek table is a skippable frame detected as plain data
+            self.compress_frame()?;
+        } else {
+            self.write_frame()?;
+        }
+        let writer = self
+            .writer
+            .as_mut()
+            .expect("writer is only taken by finish");
+        self.table.write(&mut *writer).map_err(io::Error::other)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.compress_frame()
+    }
+
+    fn compress_frame(&mut self) -> io::Result<()> {
+        let compressor = match self.compressor.as_mut() {
+            Some(compressor) => compressor,
+            None => self
+                .compressor
+                .insert(zstd::bulk::Compressor::new(self.level)?),
+        };
+        self.compressed.clear();
+        self.compressed
+            .reserve(zstd::zstd_safe::compress_bound(self.buffer.len()));
+        compressor.compress_to_buffer(&self.buffer, &mut self.compressed)?;
+
+        self.writer
+            .as_mut()
+            .expect("writer is only taken by finish")
+            .write_all(&self.compressed)?;
+        self.table
+            .push(self.compressed.len() as u32, self.buffer.len() as u32);
+        self.buffer.clear();
+
+        Ok(())
+    }
+}
+
+fn check_frame_size(frame_size: usize) -> Result<(), Error> {
+    if frame_size == 0 || frame_size > MAX_FRAME_SIZE {
+        return Err(Error::InvalidBlockSize {
+            format: Format::Zstd,
+            size: frame_size,
+        });
+    }
+
+    Ok(())
+}
+
+impl<W: io::Write> io::Write for SeekableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() >= self.frame_size {
+            self.write_frame()?;
+        }
+
+        let len = buf.len().min(self.frame_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_frame()?;
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: io::Write> Seek for SeekableWriter<W> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let current = self.position();
+        match pos {
+            io::SeekFrom::Current(0) => Ok(current),
+            io::SeekFrom::Start(p) if p == current => Ok(current),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "seekable zstd writer can only report its position",
+            )),
+        }
+    }
+}
+
+impl<W: io::Write> Drop for SeekableWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.try_finish();
+    }
+}
+
+fn corrupt(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid seekable zstd file: {msg}"),
+    )
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().expect("slice of 4 bytes"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn data() -> Vec<u8> {
+        (0..50_000u32)
+            .flat_map(|i| format!("line {i}\n").into_bytes())
+            .collect()
+    }
+
+    fn seekable(data: &[u8], frame_size: usize) -> Vec<u8> {
+        let mut writer =
+            SeekableWriter::with_frame_size(Vec::new(), Level::One, frame_size).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn seek_table() {
+        let data = data();
+        let compressed = seekable(&data, 10_000);
+
+        let table = SeekTable::from_reader(&mut io::Cursor::new(&compressed)).unwrap();
+        assert_eq!(table.frames().len(), data.len().div_ceil(10_000));
+        assert_eq!(table.uncompressed_len(), data.len() as u64);
+        assert!(table.frames()[..table.frames().len() - 1]
+            .iter()
+            .all(|f| f.uncompressed_size == 10_000));
+
+        let mut written = Vec::new();
+        table.write(&mut written).unwrap();
+        assert!(compressed.ends_with(&written));
+
+        // any zstd decoder can read the file
+        assert_eq!(zstd::stream::decode_all(&compressed[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn seek() {
+        let data = data();
+        let mut reader = SeekableReader::new(io::Cursor::new(seekable(&data, 4096))).unwrap();
+
+        let len = data.len() as u64;
+        for target in [len - 10, 5, len / 2, 4095, 4096, 3, len / 3 + 7, 0] {
+            reader.seek(io::SeekFrom::Start(target)).unwrap();
+            let mut buffer = vec![0u8; 10];
+            reader.read_exact(&mut buffer).unwrap();
+            assert_eq!(buffer, &data[target as usize..target as usize + 10]);
+        }
+
+        reader.seek(io::SeekFrom::End(-4)).unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, &data[data.len() - 4..]);
+
+        reader.seek(io::SeekFrom::Start(len + 10)).unwrap();
+        assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+        assert!(reader
+            .seek(io::SeekFrom::Current(-(len as i64) - 20))
+            .is_err());
+    }
+
+    #[test]
+    fn writer() {
+        let mut writer = SeekableWriter::new(Vec::new(), Level::Three);
+        writer.write_all(b"first frame").unwrap();
+        writer.flush().unwrap();
+        writer.write_all(b"second").unwrap();
+        assert_eq!(writer.stream_position().unwrap(), 17);
+        assert!(writer.seek(io::SeekFrom::Start(0)).is_err());
+
+        assert!(writer.set_frame_size(0).is_err());
+        assert!(writer.set_frame_size(MAX_FRAME_SIZE + 1).is_err());
+
+        let compressed = writer.finish().unwrap();
+        let table = SeekTable::from_reader(&mut io::Cursor::new(&compressed)).unwrap();
+        assert_eq!(table.frames().len(), 2);
+        assert_eq!(table.frames()[1].uncompressed_offset, 11);
+    }
+
+    #[test]
+    fn empty() {
+        let empty = SeekableWriter::new(Vec::new(), Level::Three)
+            .finish()
+            .unwrap();
+        let mut reader = SeekableReader::new(io::Cursor::new(&empty)).unwrap();
+        assert_eq!(reader.seek_table().frames().len(), 1);
+        assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+
+        // a zstd frame before the seek table
+        let (mut reader, format) =
+            crate::seek::get_reader(Box::new(io::Cursor::new(&empty))).unwrap();
+        assert_eq!(format, Format::Zstd);
+        assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+
+        let (mut reader, format) = crate::get_reader(Box::new(&empty[..])).unwrap();
+        assert_eq!(format, Format::Zstd);
+        assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+    }
+
+    #[test]
+    fn corrupted() {
+        let compressed = seekable(&data(), 10_000);
+
+        let plain = zstd::stream::encode_all(&data()[..], 1).unwrap();
+        assert!(SeekTable::from_reader(&mut io::Cursor::new(&plain)).is_err());
+
+        let mut reserved = compressed.clone();
+        let flags = reserved.len() - 5;
+        reserved[flags] = 0x04;
+        assert!(SeekTable::from_reader(&mut io::Cursor::new(&reserved)).is_err());
+
+        let mut too_many = compressed.clone();
+        let count = too_many.len() - 9;
+        too_many[count] = 0xff;
+        assert!(SeekTable::from_reader(&mut io::Cursor::new(&too_many)).is_err());
+
+        // size of the last frame
+        let mut wrong_size = compressed.clone();
+        let last = wrong_size.len() - 9 - 8;
+        wrong_size[last] = wrong_size[last].wrapping_add(1);
+        assert!(SeekTable::from_reader(&mut io::Cursor::new(&wrong_size)).is_err());
+
+        let mut wrong_size = compressed;
+        wrong_size[last + 4] = wrong_size[last + 4].wrapping_add(1);
+        let mut reader = SeekableReader::new(io::Cursor::new(wrong_size)).unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+}
@@ -1,15 +1,316 @@
-/// `Format` represent a compression format of a file. Currently BGzip are supported.
+#![allow(clippy::unnecessary_wraps)]
+
+/* standard use */
+use std::io;
+
+/* crates use */
+use cfg_if::cfg_if;
+
+/* project use */
+use crate::error::Error;
+use crate::level::Level;
+use crate::seek::compression::{ReadSeek, WriteSeek};
+
+/// `Format` represent a compression format of a file. Currently BGzip, seekable Zstd,
+/// multi-block Lzma (xz) and Bzip are supported.
+#[deprecated(note = "use niffler::Format, shared by every module")]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Format {
     BGzip,
+    Zstd,
+    Lzma,
+    Bzip,
     No,
 }
 
-pub(crate) fn bytes2type(bytes: [u8; 17]) -> Format {
-    match bytes {
-        [0x1F, 0x8B, 0x8, 0x4, 0x0, 0x0, 0x0, 0x0, 0x0, 0xFF, 0x6, 0x0, 0x42, 0x43, 0x2, 0x0, 0x0] => {
-            Format::BGzip
+#[allow(deprecated)]
+impl From<Format> for crate::Format {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::BGzip => crate::Format::BGzip,
+            Format::Zstd => crate::Format::Zstd,
+            Format::Lzma => crate::Format::Lzma,
+            Format::Bzip => crate::Format::Bzip,
+            Format::No => crate::Format::No,
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl TryFrom<crate::Format> for Format {
+    type Error = Error;
+
+    /// Fail on the formats which can't be seeked: Gzip, Lz4, Snappy, Brotli, Zlib, Deflate and
+    /// UnixCompress.
+    fn try_from(format: crate::Format) -> Result<Self, Self::Error> {
+        match format {
+            crate::Format::BGzip => Ok(Format::BGzip),
+            crate::Format::Zstd => Ok(Format::Zstd),
+            crate::Format::Lzma => Ok(Format::Lzma),
+            crate::Format::Bzip => Ok(Format::Bzip),
+            crate::Format::No => Ok(Format::No),
+            crate::Format::Gzip
+            | crate::Format::Lz4
+            | crate::Format::Snappy
+            | crate::Format::Brotli
+            | crate::Format::Zlib
+            | crate::Format::Deflate
+            | crate::Format::UnixCompress => Err(Error::UnsupportedFormat(format)),
+        }
+    }
+}
+
+/// A writer returned by [get_writer](super::get_writer), it only support `Seek` to get the
+/// current uncompressed position. [finish](Writer::finish) writes the end of the compressed
+/// stream, as the seek table or the index, and reports errors.
+///
+/// Dropping a `Writer` also ends the compressed stream, but errors are lost.
+pub struct Writer<'a> {
+    encoder: Option<Box<dyn Finish<'a> + Send + 'a>>,
+}
+
+/// A compressed stream which can be ended.
+pub(crate) trait Finish<'a>: io::Write + io::Seek {
+    /// Write the end of the compressed stream, return the underlying stream.
+    fn finish(self: Box<Self>) -> Result<Box<dyn WriteSeek + Send + 'a>, Error>;
+}
+
+impl<'a> Writer<'a> {
+    pub(crate) fn new<E: Finish<'a> + Send + 'a>(encoder: E) -> Self {
+        Writer {
+            encoder: Some(Box::new(encoder)),
+        }
+    }
+
+    /// Write the end of the compressed stream, flush and return the underlying stream.
+    pub fn finish(mut self) -> Result<Box<dyn WriteSeek + Send + 'a>, Error> {
+        let mut out = self.encoder()?;
+        out.flush()?;
+
+        Ok(out)
+    }
+
+    fn encoder(&mut self) -> Result<Box<dyn WriteSeek + Send + 'a>, Error> {
+        match self.encoder.take() {
+            Some(encoder) => encoder.finish(),
+            None => Err(finished().into()),
+        }
+    }
+}
+
+impl io::Write for Writer<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder.write(buf),
+            None => Err(finished()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder.flush(),
+            None => Err(finished()),
+        }
+    }
+}
+
+impl io::Seek for Writer<'_> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder.seek(pos),
+            None => Err(finished()),
+        }
+    }
+}
+
+impl Drop for Writer<'_> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            let _ = encoder.finish().and_then(|mut out| Ok(out.flush()?));
+        }
+    }
+}
+
+fn finished() -> io::Error {
+    io::Error::other("compressed stream is already finished")
+}
+
+impl<'a> Finish<'a> for Box<dyn WriteSeek + Send + 'a> {
+    fn finish(self: Box<Self>) -> Result<Box<dyn WriteSeek + Send + 'a>, Error> {
+        Ok(*self)
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "bgz")] {
+        pub(crate) fn new_bgzf_decoder<'a>(
+            inp: Box<dyn ReadSeek + Send + 'a>,
+        ) -> Result<(Box<dyn ReadSeek + Send + 'a>, crate::Format), Error> {
+            Ok((
+                Box::new(crate::seek::bgzf::BgzfReader::new(inp)?),
+                crate::Format::BGzip,
+            ))
+        }
+
+        pub(crate) fn new_bgzf_encoder<'a>(out: Box<dyn WriteSeek + Send + 'a>, level: Level) -> Result<Writer<'a>, Error> {
+            Ok(Writer::new(crate::seek::bgzf::BgzfWriter::new(out, level)))
+        }
+
+        pub(crate) fn new_bgzf_encoder_with_block_size<'a>(
+            out: Box<dyn WriteSeek + Send + 'a>,
+            level: Level,
+            block_size: usize,
+        ) -> Result<Writer<'a>, Error> {
+            Ok(Writer::new(crate::seek::bgzf::BgzfWriter::with_block_size(out, level, block_size)?))
+        }
+
+        pub(crate) fn new_bgzf_encoder_with_index<'a>(
+            out: Box<dyn WriteSeek + Send + 'a>,
+            index: Box<dyn WriteSeek + Send + 'a>,
+            level: Level,
+        ) -> Result<Writer<'a>, Error> {
+            Ok(Writer::new(crate::seek::bgzf::BgzfWriter::with_gzi(out, index, level)?))
+        }
+
+        impl<'a, I: io::Write + io::Seek + 'a> Finish<'a> for crate::seek::bgzf::BgzfWriter<Box<dyn WriteSeek + Send + 'a>, I> {
+            fn finish(self: Box<Self>) -> Result<Box<dyn WriteSeek + Send + 'a>, Error> {
+                (*self).finish()
+            }
+        }
+    } else {
+        pub(crate) fn new_bgzf_encoder<'a>(_: Box<dyn WriteSeek + Send + 'a>, _: Level) -> Result<Writer<'a>, Error> {
+            Err(Error::FeatureDisabled(crate::Format::BGzip))
+        }
+
+        pub(crate) fn new_bgzf_encoder_with_block_size<'a>(_: Box<dyn WriteSeek + Send + 'a>, _: Level, _: usize) -> Result<Writer<'a>, Error> {
+            Err(Error::FeatureDisabled(crate::Format::BGzip))
+        }
+
+        pub(crate) fn new_bgzf_encoder_with_index<'a>(_: Box<dyn WriteSeek + Send + 'a>, _: Box<dyn WriteSeek + Send + 'a>, _: Level) -> Result<Writer<'a>, Error> {
+            Err(Error::FeatureDisabled(crate::Format::BGzip))
+        }
+
+        pub(crate) fn new_bgzf_decoder<'a>(_: Box<dyn ReadSeek + Send + 'a>) -> Result<(Box<dyn ReadSeek + Send + 'a>, crate::Format), Error> {
+            Err(Error::FeatureDisabled(crate::Format::BGzip))
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "zstd")] {
+        pub(crate) fn new_zstd_decoder<'a>(
+            inp: Box<dyn ReadSeek + Send + 'a>,
+        ) -> Result<(Box<dyn ReadSeek + Send + 'a>, crate::Format), Error> {
+            Ok((
+                Box::new(crate::seek::zstd_seekable::SeekableReader::new(inp)?),
+                crate::Format::Zstd,
+            ))
+        }
+
+        pub(crate) fn new_zstd_encoder<'a>(out: Box<dyn WriteSeek + Send + 'a>, level: Level) -> Result<Writer<'a>, Error> {
+            Ok(Writer::new(crate::seek::zstd_seekable::SeekableWriter::new(out, level)))
+        }
+
+        pub(crate) fn new_zstd_encoder_with_frame_size<'a>(
+            out: Box<dyn WriteSeek + Send + 'a>,
+            level: Level,
+            frame_size: usize,
+        ) -> Result<Writer<'a>, Error> {
+            Ok(Writer::new(crate::seek::zstd_seekable::SeekableWriter::with_frame_size(out, level, frame_size)?))
+        }
+
+        impl<'a> Finish<'a> for crate::seek::zstd_seekable::SeekableWriter<Box<dyn WriteSeek + Send + 'a>> {
+            fn finish(self: Box<Self>) -> Result<Box<dyn WriteSeek + Send + 'a>, Error> {
+                (*self).finish()
+            }
+        }
+    } else {
+        pub(crate) fn new_zstd_encoder<'a>(_: Box<dyn WriteSeek + Send + 'a>, _: Level) -> Result<Writer<'a>, Error> {
+            Err(Error::FeatureDisabled(crate::Format::Zstd))
+        }
+
+        pub(crate) fn new_zstd_encoder_with_frame_size<'a>(_: Box<dyn WriteSeek + Send + 'a>, _: Level, _: usize) -> Result<Writer<'a>, Error> {
+            Err(Error::FeatureDisabled(crate::Format::Zstd))
+        }
+
+        pub(crate) fn new_zstd_decoder<'a>(_: Box<dyn ReadSeek + Send + 'a>) -> Result<(Box<dyn ReadSeek + Send + 'a>, crate::Format), Error> {
+            Err(Error::FeatureDisabled(crate::Format::Zstd))
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "lzma")] {
+        pub(crate) fn new_lzma_decoder<'a>(
+            inp: Box<dyn ReadSeek + Send + 'a>,
+        ) -> Result<(Box<dyn ReadSeek + Send + 'a>, crate::Format), Error> {
+            Ok((
+                Box::new(crate::seek::xz::XzReader::new(inp)?),
+                crate::Format::Lzma,
+            ))
+        }
+
+        pub(crate) fn new_lzma_encoder<'a>(out: Box<dyn WriteSeek + Send + 'a>, level: Level) -> Result<Writer<'a>, Error> {
+            level.xz_preset()?;
+            Ok(Writer::new(crate::seek::xz::XzWriter::new(out, level)))
+        }
+
+        pub(crate) fn new_lzma_encoder_with_block_size<'a>(
+            out: Box<dyn WriteSeek + Send + 'a>,
+            level: Level,
+            block_size: usize,
+        ) -> Result<Writer<'a>, Error> {
+            level.xz_preset()?;
+            Ok(Writer::new(crate::seek::xz::XzWriter::with_block_size(out, level, block_size)?))
+        }
+
+        impl<'a> Finish<'a> for crate::seek::xz::XzWriter<Box<dyn WriteSeek + Send + 'a>> {
+            fn finish(self: Box<Self>) -> Result<Box<dyn WriteSeek + Send + 'a>, Error> {
+                (*self).finish()
+            }
+        }
+    } else {
+        pub(crate) fn new_lzma_encoder<'a>(_: Box<dyn WriteSeek + Send + 'a>, _: Level) -> Result<Writer<'a>, Error> {
+            Err(Error::FeatureDisabled(crate::Format::Lzma))
+        }
+
+        pub(crate) fn new_lzma_encoder_with_block_size<'a>(_: Box<dyn WriteSeek + Send + 'a>, _: Level, _: usize) -> Result<Writer<'a>, Error> {
+            Err(Error::FeatureDisabled(crate::Format::Lzma))
+        }
+
+        pub(crate) fn new_lzma_decoder<'a>(_: Box<dyn ReadSeek + Send + 'a>) -> Result<(Box<dyn ReadSeek + Send + 'a>, crate::Format), Error> {
+            Err(Error::FeatureDisabled(crate::Format::Lzma))
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "bz2")] {
+        pub(crate) fn new_bz2_decoder<'a>(
+            inp: Box<dyn ReadSeek + Send + 'a>,
+        ) -> Result<(Box<dyn ReadSeek + Send + 'a>, crate::Format), Error> {
+            Ok((
+                Box::new(crate::seek::bz2::Bzip2Reader::new(inp)?),
+                crate::Format::Bzip,
+            ))
+        }
+
+        pub(crate) fn new_bz2_encoder<'a>(out: Box<dyn WriteSeek + Send + 'a>, level: Level) -> Result<Writer<'a>, Error> {
+            Ok(Writer::new(crate::seek::bz2::Bzip2Writer::new(out, level)))
+        }
+
+        impl<'a> Finish<'a> for crate::seek::bz2::Bzip2Writer<Box<dyn WriteSeek + Send + 'a>> {
+            fn finish(self: Box<Self>) -> Result<Box<dyn WriteSeek + Send + 'a>, Error> {
+                (*self).finish()
+            }
+        }
+    } else {
+        pub(crate) fn new_bz2_encoder<'a>(_: Box<dyn WriteSeek + Send + 'a>, _: Level) -> Result<Writer<'a>, Error> {
+            Err(Error::FeatureDisabled(crate::Format::Bzip))
+        }
+
+        pub(crate) fn new_bz2_decoder<'a>(_: Box<dyn ReadSeek + Send + 'a>) -> Result<(Box<dyn ReadSeek + Send + 'a>, crate::Format), Error> {
+            Err(Error::FeatureDisabled(crate::Format::Bzip))
         }
-        _ => Format::No,
     }
 }
@@ -0,0 +1,411 @@
+//! Positional reads shared between threads.
+//!
+//! [SharedReader] is `Sync`: one opened file can be read by many threads through
+//! [read_at](SharedReader::read_at), without any seek state. For BGZF files the block index is
+//! built once and decompressed blocks are kept in a cache shared by all threads, the lock on the
+//! underlying stream is only held while compressed bytes are read.
+
+/* standard use */
+#[cfg(feature = "bgz")]
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::io::{Read, Seek};
+use std::path::Path;
+#[cfg(feature = "bgz")]
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/* project use */
+use crate::error::Error;
+use crate::format::Format;
+
+/// Number of decompressed blocks kept by default, 4 MiB for BGZF.
+pub const DEFAULT_CACHE_SIZE: usize = 64;
+
+enum Kind {
+    #[cfg(feature = "bgz")]
+    BGzip {
+        index: crate::seek::bgzf::BgzfIndex,
+        cache: Mutex<BlockCache>,
+    },
+    No {
+        len: u64,
+    },
+}
+
+/// A reader with positional reads over uncompressed offsets, it can be shared between threads.
+///
+/// Only BGzip and uncompressed files are supported.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), niffler::Error> {
+/// # #[cfg(feature = "bgz")] {
+/// use std::io::Write;
+/// use niffler::seeksend::read_at::SharedReader;
+///
+/// let mut buffer = std::io::Cursor::new(Vec::new());
+/// {
+///     let mut writer = niffler::seeksend::get_writer(Box::new(&mut buffer), niffler::Format::BGzip, niffler::Level::Six)?;
+///     writer.write_all(b"chr1\t10\nchr2\t20\n")?;
+/// }
+/// buffer.set_position(0);
+///
+/// let reader = SharedReader::new(buffer)?;
+/// std::thread::scope(|scope| {
+///     for (offset, expected) in [(0, b"chr1"), (8, b"chr2")] {
+///         let reader = &reader;
+///         scope.spawn(move || {
+///             let mut chrom = [0u8; 4];
+///             reader.read_exact_at(offset, &mut chrom).unwrap();
+///             assert_eq!(&chrom, expected);
+///         });
+///     }
+/// });
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct SharedReader<R: Read + Seek + Send> {
+    reader: Mutex<R>,
+    kind: Kind,
+}
+
+impl<R: Read + Seek + Send> SharedReader<R> {
+    /// Detect the format of the stream and build the block index if needed.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let kind = match crate::seek::compression::sniff_format(&mut reader)? {
+            Format::BGzip => Self::bgzf(&mut reader)?,
+            Format::No => Kind::No {
+                len: reader.seek(io::SeekFrom::End(0))?,
+            },
+            // positional reads are only supported on BGzip and uncompressed files
+            format => return Err(Error::UnsupportedFormat(format)),
+        };
+
+        Ok(SharedReader {
+            reader: Mutex::new(reader),
+            kind,
+        })
+    }
+
+    /// Set the number of decompressed blocks kept in cache, at least one.
+    #[cfg_attr(not(feature = "bgz"), allow(unused_mut))]
+    pub fn with_cache_size(mut self, blocks: usize) -> Self {
+        #[cfg(feature = "bgz")]
+        if let Kind::BGzip { cache, .. } = &mut self.kind {
+            cache
+                .get_mut()
+                .unwrap_or_else(|e| e.into_inner())
+                .resize(blocks);
+        }
+        #[cfg(not(feature = "bgz"))]
+        let _ = blocks;
+
+        self
+    }
+
+    /// Compression format of the stream.
+    pub fn format(&self) -> Format {
+        match self.kind {
+            #[cfg(feature = "bgz")]
+            Kind::BGzip { .. } => Format::BGzip,
+            Kind::No { .. } => Format::No,
+        }
+    }
+
+    /// Length of the uncompressed stream.
+    pub fn len(&self) -> u64 {
+        match &self.kind {
+            #[cfg(feature = "bgz")]
+            Kind::BGzip { index, .. } => index.uncompressed_len(),
+            Kind::No { len } => *len,
+        }
+    }
+
+    /// Return true if the uncompressed stream is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read uncompressed data starting at `offset`, return the number of bytes read, which is
+    /// only smaller than `buf` at the end of the stream.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        match &self.kind {
+            #[cfg(feature = "bgz")]
+            Kind::BGzip { index, cache } => {
+                let mut read = 0;
+                while read < buf.len() {
+                    let pos = offset + read as u64;
+                    let i = match index.block_for(pos) {
+                        Some(i) => i,
+                        None => break,
+                    };
+                    let block = self.load_block(index, cache, i)?;
+
+                    // the block is shorter than in the index if the file changed since it was built
+                    let info = &index.blocks()[i];
+                    let start = (pos - info.uncompressed_offset) as usize;
+                    let len = block
+                        .len()
+                        .checked_sub(start)
+                        .filter(|len| *len > 0)
+                        .ok_or_else(|| {
+                            io::Error::from(Error::Corrupt {
+                                format: Format::BGzip,
+                                offset: info.compressed_offset,
+                            })
+                        })?
+                        .min(buf.len() - read);
+                    buf[read..read + len].copy_from_slice(&block[start..start + len]);
+                    read += len;
+                }
+
+                Ok(read)
+            }
+            Kind::No { len } => {
+                let available = len.saturating_sub(offset).min(buf.len() as u64) as usize;
+                let mut reader = self.lock()?;
+                reader.seek(io::SeekFrom::Start(offset))?;
+                reader.read_exact(&mut buf[..available])?;
+
+                Ok(available)
+            }
+        }
+    }
+
+    /// Read exactly `buf.len()` bytes of uncompressed data starting at `offset`.
+    pub fn read_exact_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        if self.read_at(offset, buf)? < buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, R>> {
+        self.reader
+            .lock()
+            .map_err(|_| io::Error::other("a thread panicked while reading"))
+    }
+
+    #[cfg(feature = "bgz")]
+    fn bgzf(reader: &mut R) -> Result<Kind, Error> {
+        Ok(Kind::BGzip {
+            index: crate::seek::bgzf::BgzfIndex::build(reader)?,
+            cache: Mutex::new(BlockCache::new(DEFAULT_CACHE_SIZE)),
+        })
+    }
+
+    #[cfg(not(feature = "bgz"))]
+    fn bgzf(_: &mut R) -> Result<Kind, Error> {
+        Err(Error::FeatureDisabled(Format::BGzip))
+    }
+
+    #[cfg(feature = "bgz")]
+    fn load_block(
+        &self,
+        index: &crate::seek::bgzf::BgzfIndex,
+        cache: &Mutex<BlockCache>,
+        i: usize,
+    ) -> io::Result<Arc<Vec<u8>>> {
+        let poisoned = |_| io::Error::other("a thread panicked while reading");
+        if let Some(block) = cache.lock().map_err(poisoned)?.get(i) {
+            return Ok(block);
+        }
+
+        let mut compressed = Vec::new();
+        {
+            let mut reader = self.lock()?;
+            reader.seek(io::SeekFrom::Start(index.blocks()[i].compressed_offset))?;
+            bgzip::read::load_block(&mut *reader, &mut compressed)
+                .map_err(|e| e.into_io_error())?;
+        }
+
+        let mut block = Vec::with_capacity(index.blocks()[i].uncompressed_size as usize);
+        bgzip::read::decompress_block(
+            &mut block,
+            &compressed,
+            &mut bgzip::deflate::Decompress::new(),
+        )
+        .map_err(|e| e.into_io_error())?;
+        let block = Arc::new(block);
+
+        cache
+            .lock()
+            .map_err(poisoned)?
+            .insert(i, Arc::clone(&block));
+
+        Ok(block)
+    }
+}
+
+/// Decompressed blocks, the oldest one is dropped when the cache is full.
+#[cfg(feature = "bgz")]
+struct BlockCache {
+    capacity: usize,
+    blocks: HashMap<usize, Arc<Vec<u8>>>,
+    order: VecDeque<usize>,
+}
+
+#[cfg(feature = "bgz")]
+impl BlockCache {
+    fn new(capacity: usize) -> Self {
+        BlockCache {
+            capacity: capacity.max(1),
+            blocks: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        self.evict();
+    }
+
+    fn get(&self, i: usize) -> Option<Arc<Vec<u8>>> {
+        self.blocks.get(&i).cloned()
+    }
+
+    fn insert(&mut self, i: usize, block: Arc<Vec<u8>>) {
+        // another thread may have loaded the same block
+        if self.blocks.insert(i, block).is_none() {
+            self.order.push_back(i);
+            self.evict();
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.blocks.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Open a file for positional reads shared between threads.
+pub fn from_path<P: AsRef<Path>>(path: P) -> Result<SharedReader<std::fs::File>, Error> {
+    let path = path.as_ref();
+    SharedReader::new(crate::utils::open(path)?).map_err(|e| e.with_path(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn data() -> Vec<u8> {
+        (0..50_000)
+            .flat_map(|i| format!("line {i:05}\n").into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn no_compression() {
+        let data = data();
+        let mut ofile = NamedTempFile::new().expect("Can't create tmpfile");
+        ofile.write_all(&data).unwrap();
+
+        let reader = from_path(ofile.path()).unwrap();
+        assert_eq!(reader.format(), Format::No);
+        assert_eq!(reader.len(), data.len() as u64);
+
+        let mut buffer = [0u8; 10];
+        reader.read_exact_at(11 * 1234, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"line 01234");
+
+        assert_eq!(
+            reader.read_at(data.len() as u64 - 4, &mut buffer).unwrap(),
+            4
+        );
+        assert_eq!(
+            reader.read_at(data.len() as u64 + 10, &mut buffer).unwrap(),
+            0
+        );
+        assert!(reader
+            .read_exact_at(data.len() as u64 - 4, &mut buffer)
+            .is_err());
+    }
+
+    #[cfg(feature = "bgz")]
+    #[test]
+    fn bgzip() {
+        let data = data();
+        let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+        {
+            let mut writer =
+                crate::seeksend::to_path(ofile.path(), Format::BGzip, crate::level::Level::One)
+                    .unwrap();
+            writer.write_all(&data).unwrap();
+        }
+
+        let reader = from_path(ofile.path()).unwrap().with_cache_size(2);
+        assert_eq!(reader.format(), Format::BGzip);
+        assert_eq!(reader.len(), data.len() as u64);
+
+        std::thread::scope(|scope| {
+            for t in 0..4u64 {
+                let (reader, data) = (&reader, &data);
+                scope.spawn(move || {
+                    for i in (t..50_000).step_by(997) {
+                        let offset = i * 11;
+                        let mut buffer = [0u8; 30];
+                        let read = reader.read_at(offset, &mut buffer).unwrap();
+                        let expected = &data[offset as usize..];
+                        assert_eq!(&buffer[..read], &expected[..read.min(expected.len())]);
+                        assert_eq!(read, expected.len().min(30));
+                    }
+                });
+            }
+        });
+
+        // read across blocks
+        let mut buffer = vec![0u8; 200_000];
+        reader.read_exact_at(100, &mut buffer).unwrap();
+        assert_eq!(buffer, &data[100..200_100]);
+    }
+
+    #[cfg(feature = "bgz")]
+    #[test]
+    fn changed_file() {
+        let write = |path: &Path, block_size: usize| {
+            let mut writer = crate::seek::bgzf::BgzfWriter::with_block_size(
+                crate::utils::create(path).unwrap(),
+                crate::level::Level::One,
+                block_size,
+            )
+            .unwrap();
+            writer.write_all(&data()).unwrap();
+            writer.finish().unwrap();
+        };
+        let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+        write(ofile.path(), 1000);
+        let reader = from_path(ofile.path()).unwrap();
+
+        // blocks of the new file are shorter than the indexed ones
+        write(ofile.path(), 100);
+        let error = reader.read_at(500, &mut [0u8; 10]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn unsupported() {
+        let mut zstd = vec![0x28, 0xb5, 0x2f, 0xfd];
+        zstd.extend_from_slice(&[0; 20]);
+
+        assert!(matches!(
+            SharedReader::new(io::Cursor::new(zstd)),
+            Err(Error::UnsupportedFormat(Format::Zstd))
+        ));
+    }
+}
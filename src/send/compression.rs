@@ -1,146 +1,249 @@
-#![allow(clippy::unnecessary_wraps)]
-
 /* standard use */
 use std::io;
-
-/* crates use */
-use cfg_if::cfg_if;
+use std::io::BufRead;
 
 /* project use */
 use crate::error::Error;
 use crate::level::Level;
 
-/* Format detection enum */
-/// `Format` represent a compression format of a file. Currently Gzip, Bzip, Lzma or No are supported.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum Format {
-    Gzip,
-    Bzip,
-    Lzma,
-    Zstd,
-    No,
+/* backward compatibility, Format is shared by every module */
+pub use crate::format::Format;
+
+/// The compressed stream given to a decoder, format detection peeks in its buffer.
+pub(crate) type Source<'a> = crate::utils::Peek<Box<dyn io::Read + Send + 'a>>;
+
+/// Size of the buffers of [NifflerReader], on both sides of the decoder.
+pub(crate) const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A reader returned by [get_reader](super::get_reader), it decompresses the stream
+/// transparently and implements [BufRead](std::io::BufRead).
+///
+/// A compressed stream ending before its end marker gives an error which converts to
+/// [Error::Truncated] with `niffler::Error::from`.
+///
+/// # Example
+/// ```
+/// use std::io::BufRead;
+/// # fn main() -> Result<(), niffler::Error> {
+///
+/// let (reader, _) = niffler::send::get_reader(Box::new(&b"first line\nsecond line\n"[..]))?;
+/// assert_eq!(reader.format(), niffler::send::compression::Format::No);
+///
+/// let lines = reader.lines().collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(lines, ["first line", "second line"]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct NifflerReader<'a> {
+    inner: Box<dyn Decompress<'a> + Send + 'a>,
+    format: Format,
 }
 
-impl Format {
-    /// An alias for Lzma, Format::Lzma == Format::Xz
-    #[allow(non_upper_case_globals)]
-    pub const Xz: Format = Format::Lzma;
+/// A decompressed stream which can give back its source.
+pub(crate) trait Decompress<'a>: io::BufRead {
+    /// The compressed stream was read to its end.
+    fn exhausted(&self) -> bool;
+
+    /// Number of bytes ignored after the compressed data.
+    fn trailing_bytes(&self) -> u64 {
+        0
+    }
+
+    /// Return the compressed stream.
+    fn into_source(self: Box<Self>) -> Source<'a>;
 }
 
-pub(crate) fn bytes2type(bytes: [u8; 5]) -> Format {
-    match bytes {
-        [0x1f, 0x8b, ..] => Format::Gzip,
-        [0x42, 0x5a, ..] => Format::Bzip,
-        [0xfd, 0x37, 0x7a, 0x58, 0x5a] => Format::Lzma,
-        [0x28, 0xb5, 0x2f, 0xfd, ..] => Format::Zstd,
-        _ => Format::No,
+impl<'a> Decompress<'a> for Source<'a> {
+    fn exhausted(&self) -> bool {
+        crate::utils::Peek::exhausted(self)
+    }
+
+    fn into_source(self: Box<Self>) -> Source<'a> {
+        *self
     }
 }
 
-cfg_if! {
-    if #[cfg(feature = "gz")] {
-        pub(crate) fn new_gz_encoder<'a>(out: Box<dyn io::Write + Send + 'a>, level: Level) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
-            Ok(Box::new(flate2::write::GzEncoder::new(
-        out,
-        level.into(),
-            )))
+impl<'a> NifflerReader<'a> {
+    pub(crate) fn new<D: Decompress<'a> + Send + 'a>(decoder: D, format: Format) -> Self {
+        NifflerReader {
+            inner: Box::new(decoder),
+            format,
         }
+    }
 
-        pub(crate) fn new_gz_decoder<'a>(
-            inp: Box<dyn io::Read + Send + 'a>,
-        ) -> Result<(Box<dyn io::Read + Send + 'a>, Format), Error> {
-            Ok((
-        Box::new(flate2::read::MultiGzDecoder::new(inp)),
-        Format::Gzip,
-            ))
-        }
-    } else {
-        pub(crate) fn new_gz_encoder<'a>(_: Box<dyn io::Write + Send + 'a>, _: Level) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
-            Err(Error::FeatureDisabled)
-        }
+    /// Compression format of the stream.
+    pub fn format(&self) -> Format {
+        self.format
+    }
 
-        pub(crate) fn new_gz_decoder<'a>(_: Box<dyn io::Read + Send + 'a>) -> Result<(Box<dyn io::Read + Send + 'a>, Format), Error> {
-            Err(Error::FeatureDisabled)
-        }
+    /// Number of bytes ignored after the compressed data by a reader returned by
+    /// [get_reader_lenient](super::get_reader_lenient), known once the end of the stream is
+    /// reached.
+    pub fn trailing_bytes(&self) -> u64 {
+        self.inner.trailing_bytes()
+    }
+
+    /// Return the original stream, for a compressed stream data read ahead by the decoder is
+    /// lost.
+    pub fn into_inner(self) -> Box<dyn io::Read + Send + 'a> {
+        Box::new(self.inner.into_source())
     }
 }
 
-cfg_if! {
-    if #[cfg(feature = "bz2")] {
-        pub(crate) fn new_bz2_encoder<'a>(out: Box<dyn io::Write + Send + 'a>, level: Level) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
-            Ok(Box::new(bzip2::write::BzEncoder::new(
-                out,
-                level.into(),
-            )))
-        }
+impl NifflerReader<'_> {
+    fn check_truncated(&self, error: io::Error) -> io::Error {
+        crate::utils::check_truncated(error, self.format, self.inner.exhausted())
+    }
+}
 
-        pub(crate) fn new_bz2_decoder<'a>(
-            inp: Box<dyn io::Read + Send + 'a>,
-        ) -> Result<(Box<dyn io::Read + Send + 'a>, Format), Error> {
-            Ok((
-                Box::new(bzip2::read::MultiBzDecoder::new(inp)),
-                Format::Bzip,
-            ))
-        }
-    } else {
-        pub(crate) fn new_bz2_encoder<'a>(_: Box<dyn io::Write + Send + 'a>, _: Level) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
-            Err(Error::FeatureDisabled)
-        }
+impl io::Read for NifflerReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).map_err(|e| self.check_truncated(e))
+    }
+}
 
-        pub(crate) fn new_bz2_decoder<'a>(_: Box<dyn io::Read + Send + 'a>) -> Result<(Box<dyn io::Read + Send + 'a>, Format), Error> {
-            Err(Error::FeatureDisabled)
+impl BufRead for NifflerReader<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if let Err(e) = self.inner.fill_buf().map(|_| ()) {
+            return Err(self.check_truncated(e));
         }
+
+        self.inner.fill_buf()
     }
-}
 
-cfg_if! {
-    if #[cfg(feature = "lzma")] {
-    pub(crate) fn new_lzma_encoder<'a>(out: Box<dyn io::Write + Send + 'a>, level: Level) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
-            Ok(Box::new(liblzma::write::XzEncoder::new(out, level.into())))
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
     }
+}
 
-    pub(crate) fn new_lzma_decoder<'a>(
-            inp: Box<dyn io::Read + Send + 'a>,
-    ) -> Result<(Box<dyn io::Read + Send + 'a>, Format), Error> {
-            Ok((
-        Box::new(liblzma::read::XzDecoder::new(inp)),
-        Format::Lzma,
-            ))
+impl<'a> Decompress<'a> for io::BufReader<crate::lenient::Lenient<Box<dyn io::Read + Send + 'a>>> {
+    fn exhausted(&self) -> bool {
+        self.get_ref().exhausted()
     }
-    } else {
-    pub(crate) fn new_lzma_encoder<'a>(_: Box<dyn io::Write + Send + 'a>, _: Level) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
-            Err(Error::FeatureDisabled)
+
+    fn trailing_bytes(&self) -> u64 {
+        self.get_ref().trailing_bytes()
     }
 
-    pub(crate) fn new_lzma_decoder<'a>(_: Box<dyn io::Read + Send + 'a>) -> Result<(Box<dyn io::Read + Send + 'a>, Format), Error> {
-            Err(Error::FeatureDisabled)
+    fn into_source(self: Box<Self>) -> Source<'a> {
+        self.into_inner()
+            .into_source()
+            .unwrap_or_else(|| crate::utils::Peek::with_capacity(0, Box::new(io::empty())))
     }
+}
+
+/// Decode member by member and ignore the data following the last complete member.
+pub(crate) fn new_lenient_decoder<'a>(
+    inp: Source<'a>,
+    format: Format,
+) -> Result<NifflerReader<'a>, Error> {
+    match format {
+        Format::No => Ok(NifflerReader::new(inp, Format::No)),
+        // framed snappy has no end marker, trailing data can't be told apart from chunks,
+        // the brotli decoder reads ahead, data following the stream can't be counted,
+        // .Z files have no end marker
+        Format::Snappy | Format::Brotli | Format::UnixCompress => new_decoder(inp, format),
+        format => Ok(NifflerReader::new(
+            io::BufReader::with_capacity(BUFFER_SIZE, crate::lenient::Lenient::new(inp, format)?),
+            format,
+        )),
     }
 }
 
-cfg_if! {
-    if #[cfg(feature = "zstd")] {
-        pub(crate) fn new_zstd_encoder<'a>(out: Box<dyn io::Write + Send + 'a>, level: Level) -> Result<Box<dyn io::Write +Send + 'a>, Error> {
-            Ok(Box::new(zstd::stream::write::Encoder::new(
-                        out,
-                        level.into(),
-            )?.auto_finish()))
+/// A writer returned by [get_writer](super::get_writer), [finish](Writer::finish) writes the end
+/// of the compressed stream and reports errors.
+///
+/// Dropping a `Writer` also ends the compressed stream, but errors are lost.
+pub struct Writer<'a> {
+    encoder: Option<Box<dyn Finish<'a> + Send + 'a>>,
+}
+
+/// A compressed stream which can be ended.
+pub(crate) trait Finish<'a>: io::Write {
+    /// Write the end of the compressed stream, return the underlying stream.
+    fn finish(self: Box<Self>) -> Result<Box<dyn io::Write + Send + 'a>, Error>;
+}
+
+impl<'a> Writer<'a> {
+    pub(crate) fn new<E: Finish<'a> + Send + 'a>(encoder: E) -> Self {
+        Writer {
+            encoder: Some(Box::new(encoder)),
+        }
+    }
+
+    /// Write the end of the compressed stream, flush and return the underlying stream.
+    pub fn finish(mut self) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
+        let mut out = self.encoder()?;
+        out.flush()?;
+
+        Ok(out)
+    }
+
+    fn encoder(&mut self) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
+        match self.encoder.take() {
+            Some(encoder) => encoder.finish(),
+            None => Err(finished().into()),
         }
+    }
+}
 
-        pub(crate) fn new_zstd_decoder<'a>(
-            inp: Box<dyn io::Read +Send + 'a>,
-        ) -> Result<(Box<dyn io::Read + Send + 'a>, Format), Error> {
-            Ok((Box::new(zstd::stream::read::Decoder::new(inp)?),
-                         Format::Zstd,
-            ))
+impl io::Write for Writer<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder.write(buf),
+            None => Err(finished()),
         }
-    } else {
-        pub(crate) fn new_zstd_encoder<'a>(_: Box<dyn io::Write +Send + 'a>, _: Level) -> Result<Box<dyn io::Write+Send  + 'a>, Error> {
-            Err(Error::FeatureDisabled)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder.flush(),
+            None => Err(finished()),
         }
+    }
+}
 
-        pub(crate) fn new_zstd_decoder<'a>(_: Box<dyn io::Read +Send + 'a>) -> Result<(Box<dyn io::Read+Send  + 'a>, Format), Error> {
-            Err(Error::FeatureDisabled)
+impl Drop for Writer<'_> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            let _ = encoder.finish().and_then(|mut out| Ok(out.flush()?));
         }
     }
 }
+
+fn finished() -> io::Error {
+    io::Error::other("compressed stream is already finished")
+}
+
+/// Boxed [Decoder](crate::generic::Decoder) of `format`.
+pub(crate) fn new_decoder<'a>(inp: Source<'a>, format: Format) -> Result<NifflerReader<'a>, Error> {
+    Ok(NifflerReader::new(
+        crate::generic::new_decoder(inp, format)?,
+        format,
+    ))
+}
+
+impl<'a> Decompress<'a> for crate::generic::Decoder<Box<dyn io::Read + Send + 'a>> {
+    fn exhausted(&self) -> bool {
+        self.source().exhausted()
+    }
+
+    fn into_source(self: Box<Self>) -> Source<'a> {
+        (*self).into_source()
+    }
+}
+
+/// Boxed [Encoder](crate::generic::Encoder) of `format`.
+pub(crate) fn new_encoder<'a>(
+    out: Box<dyn io::Write + Send + 'a>,
+    format: Format,
+    level: Level,
+) -> Result<Writer<'a>, Error> {
+    Ok(Writer::new(crate::generic::get_writer(out, format, level)?))
+}
+
+impl<'a> Finish<'a> for crate::generic::Encoder<Box<dyn io::Write + Send + 'a>> {
+    fn finish(self: Box<Self>) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
+        (*self).finish()
+    }
+}
@@ -1,8 +1,9 @@
 pub mod compression;
 
+pub use compression::{NifflerReader, Writer};
+
 /* standard use */
 use std::io;
-use std::io::Read;
 use std::path::Path;
 
 /* project use */
@@ -13,33 +14,53 @@ use crate::level::Level;
 pub fn sniff<'a>(
     in_stream: Box<dyn io::Read + Send + 'a>,
 ) -> Result<(Box<dyn io::Read + Send + 'a>, compression::Format), Error> {
-    let (first_bytes, in_stream) = crate::utils::get_first_five(in_stream)?;
-
-    let cursor = io::Cursor::new(first_bytes);
-    match compression::bytes2type(first_bytes) {
-        e @ compression::Format::Gzip
-        | e @ compression::Format::Bzip
-        | e @ compression::Format::Lzma
-        | e @ compression::Format::Zstd => Ok((Box::new(cursor.chain(in_stream)), e)),
-        _ => Ok((Box::new(cursor.chain(in_stream)), compression::Format::No)),
-    }
+    let (in_stream, compression) = sniff_source(in_stream)?;
+
+    Ok((Box::new(in_stream), compression))
+}
+
+fn sniff_source<'a>(
+    in_stream: Box<dyn io::Read + Send + 'a>,
+) -> Result<(compression::Source<'a>, compression::Format), Error> {
+    crate::generic::sniff_source(in_stream)
 }
 
 /// Similar to [get_reader](crate::get_reader) but readable stream is now sendable
 pub fn get_reader<'a>(
     in_stream: Box<dyn io::Read + Send + 'a>,
-) -> Result<(Box<dyn io::Read + Send + 'a>, compression::Format), Error> {
+) -> Result<(NifflerReader<'a>, compression::Format), Error> {
     // check compression
-    let (in_stream, compression) = sniff(in_stream)?;
+    let (in_stream, compression) = sniff_source(in_stream)?;
 
     // return readable and compression status
-    match compression {
-        compression::Format::Gzip => compression::new_gz_decoder(in_stream),
-        compression::Format::Bzip => compression::new_bz2_decoder(in_stream),
-        compression::Format::Lzma => compression::new_lzma_decoder(in_stream),
-        compression::Format::Zstd => compression::new_zstd_decoder(in_stream),
-        compression::Format::No => Ok((in_stream, compression::Format::No)),
-    }
+    Ok((
+        compression::new_decoder(in_stream, compression)?,
+        compression,
+    ))
+}
+
+/// Similar to [get_reader_with_format](crate::get_reader_with_format) but readable stream is now
+/// sendable
+pub fn get_reader_with_format<'a>(
+    in_stream: Box<dyn io::Read + Send + 'a>,
+    format: compression::Format,
+) -> Result<NifflerReader<'a>, Error> {
+    compression::new_decoder(
+        crate::utils::Peek::with_capacity(compression::BUFFER_SIZE, in_stream),
+        format,
+    )
+}
+
+/// Similar to [get_reader_lenient](crate::get_reader_lenient) but readable stream is now sendable
+pub fn get_reader_lenient<'a>(
+    in_stream: Box<dyn io::Read + Send + 'a>,
+) -> Result<(NifflerReader<'a>, compression::Format), Error> {
+    let (in_stream, compression) = sniff_source(in_stream)?;
+
+    Ok((
+        compression::new_lenient_decoder(in_stream, compression)?,
+        compression,
+    ))
 }
 
 /// Similar to [get_writer](crate::get_writer) but writable stream is now sendable
@@ -47,22 +68,42 @@ pub fn get_writer<'a>(
     out_stream: Box<dyn io::Write + Send + 'a>,
     format: compression::Format,
     level: Level,
-) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
-    match format {
-        compression::Format::Gzip => compression::new_gz_encoder(out_stream, level),
-        compression::Format::Bzip => compression::new_bz2_encoder(out_stream, level),
-        compression::Format::Lzma => compression::new_lzma_encoder(out_stream, level),
-        compression::Format::Zstd => compression::new_zstd_encoder(out_stream, level),
-        compression::Format::No => Ok(Box::new(out_stream)),
-    }
+) -> Result<compression::Writer<'a>, Error> {
+    compression::new_encoder(out_stream, format, level)
 }
 
 /// Similar to [from_path](crate::from_path) but readable stream is now sendable
 pub fn from_path<'a, P: AsRef<Path>>(
     path: P,
-) -> Result<(Box<dyn io::Read + Send + 'a>, compression::Format), Error> {
-    let readable = io::BufReader::new(std::fs::File::open(path)?);
-    get_reader(Box::new(readable))
+) -> Result<(NifflerReader<'a>, compression::Format), Error> {
+    let path = path.as_ref();
+    let readable = io::BufReader::new(crate::utils::open(path)?);
+    let result = sniff_source(Box::new(readable)).and_then(|(in_stream, compression)| {
+        let compression = crate::utils::path_format(path, compression);
+        Ok((
+            compression::new_decoder(in_stream, compression)?,
+            compression,
+        ))
+    });
+
+    result.map_err(|e| e.with_path(path))
+}
+
+/// Similar to [from_path_lenient](crate::from_path_lenient) but readable stream is now sendable
+pub fn from_path_lenient<'a, P: AsRef<Path>>(
+    path: P,
+) -> Result<(NifflerReader<'a>, compression::Format), Error> {
+    let path = path.as_ref();
+    let readable = io::BufReader::new(crate::utils::open(path)?);
+    let result = sniff_source(Box::new(readable)).and_then(|(in_stream, compression)| {
+        let compression = crate::utils::path_format(path, compression);
+        Ok((
+            compression::new_lenient_decoder(in_stream, compression)?,
+            compression,
+        ))
+    });
+
+    result.map_err(|e| e.with_path(path))
 }
 
 /// Similar to [to_path](crate::to_path) but writable stream is now sendable
@@ -70,25 +111,41 @@ pub fn to_path<'a, P: AsRef<Path>>(
     path: P,
     format: compression::Format,
     level: Level,
-) -> Result<Box<dyn io::Write + Send + 'a>, Error> {
-    let writable = io::BufWriter::new(std::fs::File::create(path)?);
-    get_writer(Box::new(writable), format, level)
+) -> Result<compression::Writer<'a>, Error> {
+    let path = path.as_ref();
+    let writable = io::BufWriter::new(crate::utils::create(path)?);
+    get_writer(Box::new(writable), format, level).map_err(|e| e.with_path(path))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::io::Read;
     use tempfile::NamedTempFile;
 
-    pub(crate) const SHORT_FILE: &'static [u8] = &[0o037, 0o213, 0o0, 0o0];
-    pub(crate) const GZIP_FILE: &'static [u8] = &[0o037, 0o213, 0o0, 0o0, 0o0];
-    pub(crate) const BZIP_FILE: &'static [u8] = &[0o102, 0o132, 0o0, 0o0, 0o0];
-    pub(crate) const LZMA_FILE: &'static [u8] = &[0o375, 0o067, 0o172, 0o130, 0o132];
-    pub(crate) const LOREM_IPSUM: &'static [u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. Ut ultricies scelerisque diam, a scelerisque enim sagittis at.";
-    pub(crate) const ZSTD_FILE: &'static [u8] = &[0x28, 0xb5, 0x2f, 0xfd, 0];
+    pub(crate) const SHORT_FILE: &[u8] = &[0x1f, 0x8b, 0x8, 0x0];
+    pub(crate) const GZIP_FILE: &[u8] = &[0x1f, 0x8b, 0x8, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x3];
+    pub(crate) const BGZIP_FILE: &[u8] = &[
+        0x1f, 0x8b, 0x8, 0x4, 0x0, 0x0, 0x0, 0x0, 0x0, 0xff, 0x6, 0x0, 0x42, 0x43, 0x2, 0x0, 0x1b,
+    ];
+    pub(crate) const BZIP_FILE: &[u8] =
+        &[0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+    pub(crate) const LZMA_FILE: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x0, 0x0, 0x4, 0xe6, 0xd6];
+    pub(crate) const LOREM_IPSUM: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. Ut ultricies scelerisque diam, a scelerisque enim sagittis at.";
+    pub(crate) const ZSTD_FILE: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd, 0x4, 0x58, 0x9, 0x0, 0x0, 0x78];
+    pub(crate) const LZ4_FILE: &[u8] = &[0x4, 0x22, 0x4d, 0x18, 0x64, 0x40, 0xa7, 0x78, 0x0, 0x0];
+    pub(crate) const SNAPPY_FILE: &[u8] =
+        &[0xff, 0x6, 0x0, 0x0, 0x73, 0x4e, 0x61, 0x50, 0x70, 0x59];
+    // a whole stream of empty data
+    pub(crate) const ZLIB_FILE: &[u8] = &[0x78, 0x9c, 0x3, 0x0, 0x0, 0x0, 0x0, 0x1];
+    // `printf 'abababababababab\n' | compress`
+    pub(crate) const UNIX_COMPRESS_FILE: &[u8] = &[
+        0x1f, 0x9d, 0x90, 0x61, 0xc4, 0x4, 0x1c, 0x28, 0xb0, 0x20, 0x41, 0x5,
+    ];
 
     mod compress_uncompress {
         use super::*;
+        use std::io::Write;
 
         #[test]
         fn no_compression() {
@@ -143,6 +200,136 @@ mod test {
             assert_eq!(LOREM_IPSUM, buffer.as_slice());
         }
 
+        /// A stream which fails once `0` bytes are left, as a full disk.
+        struct Full(usize);
+
+        impl io::Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0 == 0 {
+                    return Err(io::Error::other("no space left on device"));
+                }
+                let len = buf.len().min(self.0);
+                self.0 -= len;
+                Ok(len)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn finish() {
+            let mut buffer = Vec::new();
+            {
+                let mut writer =
+                    get_writer(Box::new(&mut buffer), compression::Format::No, Level::One).unwrap();
+                writer.write_all(LOREM_IPSUM).unwrap();
+                writer.finish().expect("Error during finish");
+            }
+            assert_eq!(buffer, LOREM_IPSUM);
+
+            let mut writer =
+                get_writer(Box::new(Full(10)), compression::Format::No, Level::One).unwrap();
+            assert!(writer.write_all(LOREM_IPSUM).is_err());
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn finish_error() {
+            let mut writer =
+                get_writer(Box::new(Full(20)), compression::Format::Gzip, Level::Six).unwrap();
+            writer
+                .write_all(LOREM_IPSUM)
+                .expect("Data is buffered by the encoder");
+            assert!(writer.finish().is_err(), "trailer can't be written");
+        }
+
+        #[test]
+        fn into_inner() {
+            let (reader, _) = get_reader(Box::new(LOREM_IPSUM)).unwrap();
+            assert_eq!(reader.format(), compression::Format::No);
+
+            // bytes read by format detection are given back
+            let mut buffer = Vec::new();
+            reader.into_inner().read_to_end(&mut buffer).unwrap();
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+
+            let (mut reader, _) = get_reader(Box::new(LOREM_IPSUM)).unwrap();
+            let mut word = [0u8; 6];
+            reader.read_exact(&mut word).unwrap();
+            assert_eq!(&word, b"Lorem ");
+
+            buffer.clear();
+            reader.into_inner().read_to_end(&mut buffer).unwrap();
+            assert_eq!(&LOREM_IPSUM[6..], buffer.as_slice());
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn buf_read() {
+            use std::io::BufRead;
+
+            let mut compressed = Vec::new();
+            {
+                let mut writer = get_writer(
+                    Box::new(&mut compressed),
+                    compression::Format::Gzip,
+                    Level::Six,
+                )
+                .unwrap();
+                writer.write_all(b"first line\nsecond line\n").unwrap();
+            }
+
+            let (reader, _) = get_reader(Box::new(io::Cursor::new(compressed))).unwrap();
+            assert_eq!(reader.format(), compression::Format::Gzip);
+
+            let lines = std::thread::spawn(move || reader.lines().collect::<Result<Vec<_>, _>>())
+                .join()
+                .unwrap()
+                .unwrap();
+            assert_eq!(lines, ["first line", "second line"]);
+        }
+
+        #[cfg(feature = "bgz")]
+        #[test]
+        fn bgzip() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            {
+                let wfile = ofile.reopen().expect("Can't create tmpfile");
+                let mut writer =
+                    get_writer(Box::new(wfile), compression::Format::BGzip, Level::Six).unwrap();
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+            let (mut reader, compression) =
+                get_reader(Box::new(rfile)).expect("Error reading from tmpfile");
+
+            assert_eq!(compression, compression::Format::BGzip);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+
+            let (_, compression) = crate::seek::from_path(ofile.path()).unwrap();
+            assert_eq!(compression, crate::Format::BGzip);
+        }
+
+        #[test]
+        #[cfg(not(feature = "bgz"))]
+        fn no_bgzip_feature() {
+            assert!(
+                get_writer(Box::new(vec![]), compression::Format::BGzip, Level::Six).is_err(),
+                "bgz disabled, this assertion should fail"
+            );
+        }
+
         #[test]
         #[cfg(not(feature = "bz2"))]
         fn no_bzip2_feature() {
@@ -152,7 +339,7 @@ mod test {
             );
 
             assert!(
-                get_reader(Box::new(&BZIP_FILE[..])).is_err(),
+                get_reader(Box::new(BZIP_FILE)).is_err(),
                 "bz2 disabled, this assertion should fail"
             );
         }
@@ -188,12 +375,15 @@ mod test {
         #[cfg(not(feature = "lzma"))]
         fn no_lzma_feature() {
             assert!(
-                get_writer(Box::new(vec![]), compression::Format::Lzma, Level::Six).is_err(),
+                matches!(
+                    get_writer(Box::new(vec![]), compression::Format::Lzma, Level::Six),
+                    Err(Error::FeatureDisabled(compression::Format::Lzma))
+                ),
                 "lzma disabled, this assertion should fail"
             );
 
             assert!(
-                get_reader(Box::new(&LZMA_FILE[..])).is_err(),
+                get_reader(Box::new(LZMA_FILE)).is_err(),
                 "lzma disabled, this assertion should fail"
             );
         }
@@ -225,6 +415,37 @@ mod test {
             assert_eq!(LOREM_IPSUM, buffer.as_slice());
         }
 
+        #[cfg(feature = "lzma")]
+        #[test]
+        fn lzma_invalid_level() {
+            assert!(matches!(
+                get_writer(Box::new(vec![]), compression::Format::Lzma, Level::Ten),
+                Err(Error::InvalidLevel {
+                    format: compression::Format::Lzma,
+                    level: Level::Ten,
+                })
+            ));
+        }
+
+        #[test]
+        fn missing_file() {
+            let dir = tempfile::tempdir().expect("Can't create tmpdir");
+            let path = dir.path().join("missing.gz");
+
+            match from_path(&path) {
+                Err(Error::WithPath {
+                    path: error_path,
+                    source,
+                }) => {
+                    assert_eq!(error_path, path);
+                    assert!(
+                        matches!(*source, Error::IOError(ref e) if e.kind() == io::ErrorKind::NotFound)
+                    );
+                }
+                _ => panic!("file doesn't exist, this assertion should fail"),
+            }
+        }
+
         #[test]
         #[cfg(not(feature = "zstd"))]
         fn no_zstd_feature() {
@@ -234,7 +455,7 @@ mod test {
             );
 
             assert!(
-                get_reader(Box::new(&ZSTD_FILE[..])).is_err(),
+                get_reader(Box::new(ZSTD_FILE)).is_err(),
                 "zstd disabled, this assertion should fail"
             );
         }
@@ -265,6 +486,594 @@ mod test {
                 .expect("Error during reading");
             assert_eq!(LOREM_IPSUM, buffer.as_slice());
         }
+
+        #[test]
+        #[cfg(not(feature = "lz4"))]
+        fn no_lz4_feature() {
+            assert!(
+                get_writer(Box::new(vec![]), compression::Format::Lz4, Level::Six).is_err(),
+                "lz4 disabled, this assertion should fail"
+            );
+
+            assert!(
+                get_reader(Box::new(LZ4_FILE)).is_err(),
+                "lz4 disabled, this assertion should fail"
+            );
+        }
+
+        #[cfg(feature = "lz4")]
+        #[test]
+        fn lz4() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            {
+                let wfile = ofile.reopen().expect("Can't create tmpfile");
+                let mut writer =
+                    get_writer(Box::new(wfile), compression::Format::Lz4, Level::Six).unwrap();
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+            let (mut reader, compression) =
+                get_reader(Box::new(rfile)).expect("Error reading from tmpfile");
+
+            assert_eq!(compression, compression::Format::Lz4);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+        }
+
+        #[test]
+        #[cfg(not(feature = "snappy"))]
+        fn no_snappy_feature() {
+            assert!(
+                get_writer(Box::new(vec![]), compression::Format::Snappy, Level::Six).is_err(),
+                "snappy disabled, this assertion should fail"
+            );
+
+            assert!(
+                get_reader(Box::new(SNAPPY_FILE)).is_err(),
+                "snappy disabled, this assertion should fail"
+            );
+        }
+
+        #[cfg(feature = "snappy")]
+        #[test]
+        fn snappy() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            {
+                let wfile = ofile.reopen().expect("Can't create tmpfile");
+                let mut writer =
+                    get_writer(Box::new(wfile), compression::Format::Snappy, Level::Six).unwrap();
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+            let (mut reader, compression) =
+                get_reader(Box::new(rfile)).expect("Error reading from tmpfile");
+
+            assert_eq!(compression, compression::Format::Snappy);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+        }
+
+        #[cfg(feature = "snappy")]
+        #[test]
+        fn snappy_checksum() {
+            let mut compressed = Vec::new();
+            let mut writer = get_writer(
+                Box::new(&mut compressed),
+                compression::Format::Snappy,
+                Level::Six,
+            )
+            .unwrap();
+            writer
+                .write_all(LOREM_IPSUM)
+                .expect("Error during write of data");
+            writer.finish().expect("Error during finish");
+
+            // masked CRC32C of the first chunk, after the stream identifier and the chunk header
+            compressed[14] ^= 0xff;
+
+            let (mut reader, _) = get_reader(Box::new(&compressed[..])).unwrap();
+            let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+            assert!(error.to_string().contains("checksum"), "{error}");
+        }
+
+        #[test]
+        #[cfg(not(feature = "brotli"))]
+        fn no_brotli_feature() {
+            assert!(
+                get_writer(Box::new(vec![]), compression::Format::Brotli, Level::Six).is_err(),
+                "brotli disabled, this assertion should fail"
+            );
+
+            assert!(
+                get_reader_with_format(
+                    Box::new(&[0x6b, 0x00, 0x03][..]),
+                    compression::Format::Brotli
+                )
+                .is_err(),
+                "brotli disabled, this assertion should fail"
+            );
+        }
+
+        #[cfg(feature = "brotli")]
+        #[test]
+        fn brotli() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            {
+                let wfile = ofile.reopen().expect("Can't create tmpfile");
+                let mut writer =
+                    get_writer(Box::new(wfile), compression::Format::Brotli, Level::Six).unwrap();
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+            let mut reader = get_reader_with_format(Box::new(rfile), compression::Format::Brotli)
+                .expect("Error reading from tmpfile");
+
+            assert_eq!(reader.format(), compression::Format::Brotli);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+        }
+
+        #[cfg(feature = "brotli")]
+        #[test]
+        fn brotli_extension() {
+            let ofile = tempfile::Builder::new()
+                .suffix(".br")
+                .tempfile()
+                .expect("Can't create tmpfile");
+
+            {
+                let mut writer =
+                    to_path(ofile.path(), compression::Format::Brotli, Level::Six).unwrap();
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+                writer.finish().expect("Error during finish");
+            }
+
+            let (mut reader, compression) = from_path(ofile.path()).unwrap();
+            assert_eq!(compression, compression::Format::Brotli);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+
+            // the magic number wins over the extension
+            #[cfg(feature = "gz")]
+            {
+                let mut writer =
+                    to_path(ofile.path(), compression::Format::No, Level::Six).unwrap();
+                writer
+                    .write_all(GZIP_FILE)
+                    .expect("Error during write of data");
+                writer.finish().expect("Error during finish");
+
+                let (_, compression) = from_path_lenient(ofile.path()).unwrap();
+                assert_eq!(compression, compression::Format::Gzip);
+            }
+        }
+
+        #[test]
+        fn unix_compress() {
+            let (mut reader, compression) =
+                get_reader(Box::new(UNIX_COMPRESS_FILE)).expect("Error reading .Z data");
+            assert_eq!(compression, compression::Format::UnixCompress);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(buffer, b"abababababababab\n");
+
+            // a code which isn't in the table yet
+            let corrupted = [&UNIX_COMPRESS_FILE[..4], &[0xff, 0xff]].concat();
+            let (mut reader, _) = get_reader(Box::new(&corrupted[..])).unwrap();
+            assert!(reader.read_to_end(&mut Vec::new()).is_err());
+
+            // decode only
+            assert!(matches!(
+                get_writer(
+                    Box::new(vec![]),
+                    compression::Format::UnixCompress,
+                    Level::Six
+                ),
+                Err(Error::UnsupportedFormat(compression::Format::UnixCompress))
+            ));
+        }
+
+        #[test]
+        #[cfg(not(feature = "gz"))]
+        fn no_zlib_feature() {
+            for format in [compression::Format::Zlib, compression::Format::Deflate] {
+                assert!(
+                    get_writer(Box::new(vec![]), format, Level::Six).is_err(),
+                    "gz disabled, this assertion should fail"
+                );
+            }
+
+            assert!(
+                get_reader(Box::new(ZLIB_FILE)).is_err(),
+                "gz disabled, this assertion should fail"
+            );
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn zlib() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+
+            {
+                let wfile = ofile.reopen().expect("Can't create tmpfile");
+                let mut writer =
+                    get_writer(Box::new(wfile), compression::Format::Zlib, Level::Six).unwrap();
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+            }
+
+            let rfile = ofile.reopen().expect("Can't create tmpfile");
+            let (mut reader, compression) =
+                get_reader(Box::new(rfile)).expect("Error reading from tmpfile");
+
+            assert_eq!(compression, compression::Format::Zlib);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn zlib_concatenated() {
+            let mut compressed = Vec::new();
+            for level in [Level::One, Level::Nine] {
+                let mut writer =
+                    get_writer(Box::new(&mut compressed), compression::Format::Zlib, level)
+                        .unwrap();
+                writer
+                    .write_all(LOREM_IPSUM)
+                    .expect("Error during write of data");
+                writer.finish().expect("Error during finish");
+            }
+
+            let (mut reader, _) = get_reader(Box::new(&compressed[..])).unwrap();
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(buffer, [LOREM_IPSUM, LOREM_IPSUM].concat());
+
+            let with_garbage = [&compressed[..], b"garbage"].concat();
+            let (mut reader, _) = get_reader(Box::new(&with_garbage[..])).unwrap();
+            assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn deflate() {
+            let mut compressed = Vec::new();
+            let mut writer = get_writer(
+                Box::new(&mut compressed),
+                compression::Format::Deflate,
+                Level::Six,
+            )
+            .unwrap();
+            writer
+                .write_all(LOREM_IPSUM)
+                .expect("Error during write of data");
+            writer.finish().expect("Error during finish");
+
+            let mut reader =
+                get_reader_with_format(Box::new(&compressed[..]), compression::Format::Deflate)
+                    .unwrap();
+            assert_eq!(reader.format(), compression::Format::Deflate);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(LOREM_IPSUM, buffer.as_slice());
+
+            let with_garbage = [&compressed[..], b"garbage"].concat();
+            let mut reader =
+                get_reader_with_format(Box::new(&with_garbage[..]), compression::Format::Deflate)
+                    .unwrap();
+            let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[cfg(any(
+        feature = "gz",
+        feature = "bgz",
+        feature = "bz2",
+        feature = "lzma",
+        feature = "zstd",
+        feature = "lz4",
+        feature = "snappy",
+        feature = "brotli"
+    ))]
+    mod truncated {
+        use super::*;
+        use std::io::Write;
+
+        fn compress(format: compression::Format) -> (Vec<u8>, Vec<u8>) {
+            let data: Vec<u8> = (0..200_000u32)
+                .flat_map(|i| format!("line {i}\n").into_bytes())
+                .collect();
+
+            let mut compressed = Vec::new();
+            let mut writer = get_writer(Box::new(&mut compressed), format, Level::One).unwrap();
+            writer.write_all(&data).expect("Error during write of data");
+            writer.finish().expect("Error during finish");
+
+            (data, compressed)
+        }
+
+        fn open(compressed: &[u8], format: compression::Format) -> NifflerReader<'_> {
+            match format {
+                // no magic number
+                compression::Format::Brotli | compression::Format::Deflate => {
+                    get_reader_with_format(Box::new(compressed), format).unwrap()
+                }
+                _ => get_reader(Box::new(compressed)).unwrap().0,
+            }
+        }
+
+        fn check(format: compression::Format) {
+            let (data, compressed) = compress(format);
+
+            let mut reader = open(&compressed, format);
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(buffer, data);
+
+            // 28 bytes is the BGZF EOF marker
+            for cut in [1, 8, 28, compressed.len() / 2] {
+                let mut reader = open(&compressed[..compressed.len() - cut], format);
+                let error = Error::from(reader.read_to_end(&mut Vec::new()).unwrap_err());
+
+                assert!(
+                    matches!(error, Error::Truncated { format: f } if f == format),
+                    "{format:?} cut by {cut} bytes: {error}"
+                );
+            }
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn gzip() {
+            check(compression::Format::Gzip);
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn zlib() {
+            check(compression::Format::Zlib);
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn deflate() {
+            check(compression::Format::Deflate);
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn corrupted_gzip() {
+            let (_, mut compressed) = compress(compression::Format::Gzip);
+            let middle = compressed.len() / 2;
+            compressed[middle] ^= 0xff;
+
+            let (mut reader, _) = get_reader(Box::new(&compressed[..])).unwrap();
+            let error = Error::from(reader.read_to_end(&mut Vec::new()).unwrap_err());
+
+            assert!(!matches!(error, Error::Truncated { .. }), "{error}");
+        }
+
+        #[cfg(feature = "bgz")]
+        #[test]
+        fn bgzip() {
+            check(compression::Format::BGzip);
+        }
+
+        #[cfg(feature = "bz2")]
+        #[test]
+        fn bzip() {
+            check(compression::Format::Bzip);
+        }
+
+        #[cfg(feature = "lzma")]
+        #[test]
+        fn lzma() {
+            check(compression::Format::Lzma);
+        }
+
+        #[cfg(feature = "zstd")]
+        #[test]
+        fn zstd() {
+            check(compression::Format::Zstd);
+        }
+
+        #[cfg(feature = "lz4")]
+        #[test]
+        fn lz4() {
+            check(compression::Format::Lz4);
+        }
+
+        #[cfg(feature = "snappy")]
+        #[test]
+        fn snappy() {
+            check(compression::Format::Snappy);
+        }
+
+        #[cfg(feature = "brotli")]
+        #[test]
+        fn brotli() {
+            check(compression::Format::Brotli);
+        }
+    }
+
+    #[cfg(any(
+        feature = "gz",
+        feature = "bgz",
+        feature = "bz2",
+        feature = "lzma",
+        feature = "zstd",
+        feature = "lz4"
+    ))]
+    mod lenient {
+        use super::*;
+        use std::io::Write;
+
+        const GARBAGE: &[u8] = b"not a compressed member\n";
+
+        fn compress(format: compression::Format) -> Vec<u8> {
+            let mut compressed = Vec::new();
+            let mut writer = get_writer(Box::new(&mut compressed), format, Level::One).unwrap();
+            writer
+                .write_all(LOREM_IPSUM)
+                .expect("Error during write of data");
+            writer.finish().expect("Error during finish");
+
+            compressed
+        }
+
+        fn check(format: compression::Format) {
+            // two members followed by padding and garbage
+            let mut data = compress(format);
+            data.extend(compress(format));
+            let end = data.len();
+            data.extend([0; 512]);
+            data.extend(GARBAGE);
+
+            let (mut reader, _) = get_reader(Box::new(&data[..])).unwrap();
+            assert!(reader.read_to_end(&mut Vec::new()).is_err());
+
+            let (mut reader, compression) = get_reader_lenient(Box::new(&data[..])).unwrap();
+            assert_eq!(compression, format);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(buffer, [LOREM_IPSUM, LOREM_IPSUM].concat());
+            assert_eq!(reader.trailing_bytes(), (data.len() - end) as u64);
+
+            // without trailing data
+            let (mut reader, _) = get_reader_lenient(Box::new(&data[..end])).unwrap();
+            reader
+                .read_to_end(&mut Vec::new())
+                .expect("Error during reading");
+            assert_eq!(reader.trailing_bytes(), 0);
+
+            // truncated members are still reported
+            let (mut reader, _) = get_reader_lenient(Box::new(&data[..end - 8])).unwrap();
+            let error = Error::from(reader.read_to_end(&mut Vec::new()).unwrap_err());
+            assert!(
+                matches!(error, Error::Truncated { format: f } if f == format),
+                "{format:?}: {error}"
+            );
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn gzip() {
+            check(compression::Format::Gzip);
+        }
+
+        #[cfg(feature = "bgz")]
+        #[test]
+        fn bgzip() {
+            check(compression::Format::BGzip);
+        }
+
+        #[cfg(feature = "bz2")]
+        #[test]
+        fn bzip() {
+            check(compression::Format::Bzip);
+        }
+
+        #[cfg(feature = "lzma")]
+        #[test]
+        fn lzma() {
+            check(compression::Format::Lzma);
+        }
+
+        #[cfg(feature = "zstd")]
+        #[test]
+        fn zstd() {
+            check(compression::Format::Zstd);
+        }
+
+        #[cfg(feature = "lz4")]
+        #[test]
+        fn lz4() {
+            check(compression::Format::Lz4);
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn zlib() {
+            check(compression::Format::Zlib);
+        }
+
+        #[test]
+        fn uncompressed() {
+            let (mut reader, compression) = get_reader_lenient(Box::new(LOREM_IPSUM)).unwrap();
+            assert_eq!(compression, compression::Format::No);
+
+            let mut buffer = Vec::new();
+            reader
+                .read_to_end(&mut buffer)
+                .expect("Error during reading");
+            assert_eq!(buffer, LOREM_IPSUM);
+            assert_eq!(reader.trailing_bytes(), 0);
+        }
+
+        #[cfg(feature = "gz")]
+        #[test]
+        fn from_path() {
+            let ofile = NamedTempFile::new().expect("Can't create tmpfile");
+            let mut data = compress(compression::Format::Gzip);
+            data.extend([0; 16]);
+            std::fs::write(ofile.path(), &data).unwrap();
+
+            let (mut reader, _) = from_path_lenient(ofile.path()).unwrap();
+            reader
+                .read_to_end(&mut Vec::new())
+                .expect("Error during reading");
+            assert_eq!(reader.trailing_bytes(), 16);
+        }
     }
 
     mod compression_format_detection {
@@ -276,6 +1085,35 @@ mod test {
             assert_eq!(compression, compression::Format::Gzip);
         }
 
+        #[test]
+        fn bgzip() {
+            let (_, compression) = sniff(Box::new(BGZIP_FILE)).expect("Error in read file");
+            assert_eq!(compression, compression::Format::BGzip);
+        }
+
+        /// A stream which returns one byte per read, as a slow pipe.
+        struct Trickle(&'static [u8]);
+
+        impl io::Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let len = self.0.len().min(buf.len()).min(1);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        #[test]
+        fn short_reads() {
+            let (mut reader, compression) =
+                sniff(Box::new(Trickle(BGZIP_FILE))).expect("Error in read file");
+            assert_eq!(compression, compression::Format::BGzip);
+
+            let mut buffer = Vec::new();
+            reader.read_to_end(&mut buffer).unwrap();
+            assert_eq!(buffer, BGZIP_FILE);
+        }
+
         #[test]
         fn bzip() {
             let (_, compression) = sniff(Box::new(BZIP_FILE)).expect("Error in read file");
@@ -294,12 +1132,97 @@ mod test {
             assert_eq!(compression, compression::Format::Zstd);
         }
 
+        #[test]
+        fn lz4() {
+            let (_, compression) = sniff(Box::new(LZ4_FILE)).expect("Error in read file");
+            assert_eq!(compression, compression::Format::Lz4);
+        }
+
+        #[test]
+        fn snappy() {
+            let (_, compression) = sniff(Box::new(SNAPPY_FILE)).expect("Error in read file");
+            assert_eq!(compression, compression::Format::Snappy);
+        }
+
+        #[test]
+        fn zlib() {
+            let (_, compression) = sniff(Box::new(ZLIB_FILE)).expect("Error in read file");
+            assert_eq!(compression, compression::Format::Zlib);
+        }
+
+        #[test]
+        fn unix_compress() {
+            let (_, compression) = sniff(Box::new(UNIX_COMPRESS_FILE)).expect("Error in read file");
+            assert_eq!(compression, compression::Format::UnixCompress);
+        }
+
+        #[cfg(feature = "brotli")]
+        #[test]
+        fn brotli() {
+            use std::io::Write;
+
+            let mut compressed = Vec::new();
+            let mut writer = get_writer(
+                Box::new(&mut compressed),
+                compression::Format::Brotli,
+                Level::Six,
+            )
+            .unwrap();
+            writer.write_all(LOREM_IPSUM).unwrap();
+            writer.finish().unwrap();
+
+            // brotli has no magic number
+            let (_, compression) = sniff(Box::new(&compressed[..])).expect("Error in read file");
+            assert_eq!(compression, compression::Format::No);
+        }
+
         #[test]
         fn too_short() {
             let result = sniff(Box::new(SHORT_FILE));
             assert!(result.is_err());
         }
 
+        #[test]
+        fn magic_in_plain_text() {
+            for input in [
+                &b"BZh9 is not a bzip2 file"[..],
+                b"BZ2 locus\tchr1\t100\n",
+                &[0x1f, 0x8b, 0x9, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x3],
+                &[0x1f, 0x8b, 0x8, 0x80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x3],
+                &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x0, 0x1, 0x4, 0x0, 0x0],
+                &[0x28, 0xb5, 0x2f, 0xfd, 0x8, 0x0, 0x0, 0x0, 0x0, 0x0],
+                // zlib header checksum, but not the usual window size
+                b"X\t1000\t2000\n",
+                b"80,chr1\n",
+                // reserved deflate block type
+                &[0x78, 0x9c, 0x7, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0],
+                // zlib header of the middle levels, but not a deflate stream
+                b"x^2 + y^2 = z^2\n",
+                b"x^2+1\n",
+                b"x^2\tchr1\t100\n",
+                // .Z reserved flags, or more than 16 bits per code
+                &[0x1f, 0x9d, 0xb0, 0x61, 0xc4, 0x4],
+                &[0x1f, 0x9d, 0x91, 0x61, 0xc4, 0x4],
+            ] {
+                let (_, compression) = sniff(Box::new(input)).expect("Error in read file");
+                assert_eq!(compression, compression::Format::No);
+            }
+        }
+
+        #[test]
+        fn short_no_compression() {
+            for input in [&b""[..], b"a", b"abc\n", &LOREM_IPSUM[..4]] {
+                let (mut reader, compression) = sniff(Box::new(input)).expect("Error in read file");
+                assert_eq!(compression, compression::Format::No);
+
+                let mut buffer = Vec::new();
+                reader
+                    .read_to_end(&mut buffer)
+                    .expect("Error during reading");
+                assert_eq!(buffer, input);
+            }
+        }
+
         #[test]
         fn no_compression() {
             let (_, compression) = sniff(Box::new(LOREM_IPSUM)).expect("Error in read file");
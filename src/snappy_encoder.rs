@@ -0,0 +1,47 @@
+//! Encode snappy framed streams, [snap::write::FrameEncoder] writes nothing for empty data so the
+//! stream identifier is added on finish when no data was written.
+
+/* standard use */
+use std::io;
+use std::io::Write;
+
+/// First chunk of every snappy framed stream.
+const STREAM_IDENTIFIER: [u8; 10] = [0xff, 0x06, 0x00, 0x00, 0x73, 0x4e, 0x61, 0x50, 0x70, 0x59];
+
+/// A snappy encoder whose output is detected as snappy even without data.
+pub(crate) struct SnappyEncoder<W: Write> {
+    encoder: snap::write::FrameEncoder<W>,
+    empty: bool,
+}
+
+impl<W: Write> SnappyEncoder<W> {
+    pub(crate) fn new(out: W) -> Self {
+        SnappyEncoder {
+            encoder: snap::write::FrameEncoder::new(out),
+            empty: true,
+        }
+    }
+
+    /// Write the pending data and return the underlying stream.
+    pub(crate) fn finish(self) -> io::Result<W> {
+        let mut out = self.encoder.into_inner().map_err(|e| e.into_error())?;
+        if self.empty {
+            out.write_all(&STREAM_IDENTIFIER)?;
+        }
+
+        Ok(out)
+    }
+}
+
+impl<W: Write> Write for SnappyEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.encoder.write(buf)?;
+        self.empty &= written == 0;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
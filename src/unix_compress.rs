@@ -0,0 +1,222 @@
+//! Decode files written by Unix `compress` (`.Z`), an adaptive LZW with codes from 9 to 16 bits.
+//!
+//! As ncompress does, codes are read by groups of eight: when the code width changes or the table
+//! is cleared, the end of the current group is padding.
+
+/* standard use */
+use std::io;
+use std::io::BufRead;
+
+/// Code width at the start of the stream and after a clear.
+const INIT_BITS: u8 = 9;
+/// Widest code supported by `compress`.
+const MAX_BITS: u8 = 16;
+/// Largest code of the initial width, even if it's the widest one, as ncompress and gzip.
+const INIT_MAX_CODE: u32 = (1 << INIT_BITS) - 1;
+/// In block mode, this code clears the table.
+const CLEAR: u32 = 256;
+
+/// A decoder of the `compress` format, the stream has no end marker or checksum so a truncated
+/// stream can only be detected inside a code.
+pub(crate) struct LzwDecoder<R: BufRead> {
+    source: R,
+    // bits read from the source, not used yet
+    bits: u32,
+    bit_count: u8,
+
+    max_bits: u8,
+    block_mode: bool,
+    n_bits: u8,
+    max_code: u32,
+    // next code of the table
+    free_ent: u32,
+    // codes read since the width changed or the table was cleared
+    codes: u32,
+
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    old_code: Option<u32>,
+    fin_char: u8,
+
+    // decoded string of the last code, with the number of bytes already returned
+    string: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> LzwDecoder<R> {
+    /// Read the header of `source`.
+    pub(crate) fn new(mut source: R) -> io::Result<Self> {
+        let mut header = [0u8; 3];
+        io::Read::read_exact(&mut source, &mut header)?;
+
+        let max_bits = header[2] & 0x1f;
+        if header[..2] != [0x1f, 0x9d] || !(INIT_BITS..=MAX_BITS).contains(&max_bits) {
+            return Err(corrupt());
+        }
+        let block_mode = header[2] & 0x80 != 0;
+
+        Ok(LzwDecoder {
+            source,
+            bits: 0,
+            bit_count: 0,
+            max_bits,
+            block_mode,
+            n_bits: INIT_BITS,
+            max_code: INIT_MAX_CODE,
+            free_ent: if block_mode { CLEAR + 1 } else { CLEAR },
+            codes: 0,
+            prefix: vec![0; 1 << MAX_BITS],
+            suffix: (0..1u32 << MAX_BITS).map(|code| code as u8).collect(),
+            old_code: None,
+            fin_char: 0,
+            string: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.source
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.source
+    }
+
+    /// Next `n` bits of the source, `None` at the end of the stream. Bits of a partial code at the
+    /// end are ignored.
+    fn read_bits(&mut self, n: u8) -> io::Result<Option<u32>> {
+        while self.bit_count < n {
+            let Some(&byte) = self.source.fill_buf()?.first() else {
+                return Ok(None);
+            };
+            self.source.consume(1);
+
+            self.bits |= u32::from(byte) << self.bit_count;
+            self.bit_count += 8;
+        }
+
+        let value = self.bits & ((1 << n) - 1);
+        self.bits >>= n;
+        self.bit_count -= n;
+
+        Ok(Some(value))
+    }
+
+    /// Skip the codes left in the current group of eight codes.
+    fn skip_group(&mut self) -> io::Result<()> {
+        let left = (8 - self.codes % 8) % 8;
+        for _ in 0..left {
+            if self.read_bits(self.n_bits)?.is_none() {
+                break;
+            }
+        }
+        self.codes = 0;
+
+        Ok(())
+    }
+
+    /// Next code of the stream, the table is widened or cleared on the way.
+    fn next_code(&mut self) -> io::Result<Option<u32>> {
+        loop {
+            if self.free_ent > self.max_code {
+                self.skip_group()?;
+                self.n_bits += 1;
+                self.max_code = max_code(self.n_bits, self.max_bits);
+            }
+
+            let Some(code) = self.read_bits(self.n_bits)? else {
+                return Ok(None);
+            };
+            self.codes += 1;
+
+            if code == CLEAR && self.block_mode && self.old_code.is_some() {
+                self.skip_group()?;
+                // the entry following the next code is bogus, as the compressor does
+                self.free_ent = CLEAR;
+                self.n_bits = INIT_BITS;
+                self.max_code = INIT_MAX_CODE;
+                continue;
+            }
+
+            return Ok(Some(code));
+        }
+    }
+
+    /// Decode `code` in the string buffer and add an entry to the table.
+    fn decode(&mut self, code: u32) -> io::Result<()> {
+        self.string.clear();
+        self.pos = 0;
+
+        let Some(old_code) = self.old_code else {
+            // the first code is a literal
+            if code >= CLEAR {
+                return Err(corrupt());
+            }
+            self.old_code = Some(code);
+            self.fin_char = code as u8;
+            self.string.push(self.fin_char);
+            return Ok(());
+        };
+
+        let mut current = code;
+        // a string made of the previous one followed by its first byte
+        if current >= self.free_ent {
+            if current > self.free_ent {
+                return Err(corrupt());
+            }
+            self.string.push(self.fin_char);
+            current = old_code;
+        }
+
+        while current >= CLEAR {
+            self.string.push(self.suffix[current as usize]);
+            current = u32::from(self.prefix[current as usize]);
+        }
+        self.fin_char = current as u8;
+        self.string.push(self.fin_char);
+        self.string.reverse();
+
+        if self.free_ent < 1 << self.max_bits {
+            self.prefix[self.free_ent as usize] = old_code as u16;
+            self.suffix[self.free_ent as usize] = self.fin_char;
+            self.free_ent += 1;
+        }
+        self.old_code = Some(code);
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead> io::Read for LzwDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.pos == self.string.len() {
+            match self.next_code()? {
+                Some(code) => self.decode(code)?,
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.string.len() - self.pos);
+        buf[..len].copy_from_slice(&self.string[self.pos..self.pos + len]);
+        self.pos += len;
+
+        Ok(len)
+    }
+}
+
+/// Largest code of this width, codes of the widest width go up to the end of the table.
+fn max_code(n_bits: u8, max_bits: u8) -> u32 {
+    if n_bits == max_bits {
+        1 << max_bits
+    } else {
+        (1 << n_bits) - 1
+    }
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt compress (.Z) stream")
+}
@@ -1,32 +1,153 @@
 /* standard use */
 use std::io;
+use std::path::Path;
 
 /* project use */
 use crate::error::Error;
+use crate::format::Format;
 use crate::seek::compression::ReadSeek;
 
-pub fn get_first_five<'a, T>(mut in_stream: T) -> Result<([u8; 5], T), Error>
-where
-    T: io::Read + 'a,
-{
-    let mut buf = [0u8; 5];
-    match in_stream.read_exact(&mut buf) {
-        Ok(()) => Ok((buf, in_stream)),
-        Err(_) => Err(Error::FileTooShort),
+/// Read until `buf` is full or the end of the stream, return the number of bytes read.
+pub(crate) fn read_up_to<T: io::Read + ?Sized>(
+    in_stream: &mut T,
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match in_stream.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(len)
+}
+
+/// A buffered reader which can look ahead of the read position without consuming data.
+pub(crate) struct Peek<R> {
+    inner: R,
+    buffer: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+    eof: bool,
+}
+
+impl<R: io::Read> Peek<R> {
+    pub(crate) fn with_capacity(capacity: usize, inner: R) -> Self {
+        Peek {
+            inner,
+            buffer: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            eof: false,
+        }
+    }
+
+    /// Return the buffered data, with at least `len` bytes unless the stream ends before.
+    pub(crate) fn peek(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.filled - self.pos < len {
+            self.buffer.copy_within(self.pos..self.filled, 0);
+            self.filled -= self.pos;
+            self.pos = 0;
+
+            if self.buffer.len() < len {
+                let mut buffer = vec![0; len].into_boxed_slice();
+                buffer[..self.filled].copy_from_slice(&self.buffer[..self.filled]);
+                self.buffer = buffer;
+            }
+
+            let read = read_up_to(&mut self.inner, &mut self.buffer[self.filled..len])?;
+            self.eof = self.filled + read < len;
+            self.filled += read;
+        }
+
+        Ok(&self.buffer[self.pos..self.filled])
+    }
+
+    /// The end of the stream was reached and every buffered byte was consumed.
+    pub(crate) fn exhausted(&self) -> bool {
+        self.eof && self.pos == self.filled
+    }
+
+    /// Return the underlying stream, buffered data is lost.
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
     }
 }
 
-pub fn get_first_bytes<'a, T>(in_stream: &mut T) -> Result<[u8; 17], Error>
+impl<R: io::Read> io::Read for Peek<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // large reads on an empty buffer skip it
+        if self.pos == self.filled && buf.len() >= self.buffer.len() {
+            let read = self.inner.read(buf)?;
+            self.eof = read == 0;
+            return Ok(read);
+        }
+
+        let available = io::BufRead::fill_buf(self)?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        io::BufRead::consume(self, len);
+
+        Ok(len)
+    }
+}
+
+impl<R: io::Read> io::BufRead for Peek<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            self.filled = self.inner.read(&mut self.buffer)?;
+            self.pos = 0;
+            self.eof = self.filled == 0;
+        }
+
+        Ok(&self.buffer[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+}
+
+/// Decoder errors raised once the compressed stream is exhausted, or on an unexpected end of file,
+/// mean the stream is truncated.
+pub(crate) fn check_truncated(error: io::Error, format: Format, exhausted: bool) -> io::Error {
+    if format != Format::No && (exhausted || error.kind() == io::ErrorKind::UnexpectedEof) {
+        Error::Truncated { format }.into()
+    } else {
+        error
+    }
+}
+
+/// Read the first seventeen bytes of a stream and rewind it, unread bytes are zero if the
+/// stream is shorter.
+pub fn get_first_bytes<'a, T>(in_stream: &mut T) -> Result<([u8; 17], usize), Error>
 where
     T: ReadSeek + 'a,
 {
     let mut buf = [0u8; 17];
+    let len = read_up_to(in_stream, &mut buf)?;
+    in_stream.seek(io::SeekFrom::Start(0))?;
 
-    match in_stream.read_exact(&mut buf) {
-        Ok(()) => {
-            in_stream.seek(io::SeekFrom::Start(0))?;
-            Ok(buf)
-        }
-        Err(_) => Err(Error::FileTooShort),
+    Ok((buf, len))
+}
+
+/// Open the file at `path`, errors hold the path.
+pub(crate) fn open(path: &Path) -> Result<std::fs::File, Error> {
+    std::fs::File::open(path).map_err(|e| Error::from(e).with_path(path))
+}
+
+/// Create the file at `path`, errors hold the path.
+pub(crate) fn create(path: &Path) -> Result<std::fs::File, Error> {
+    std::fs::File::create(path).map_err(|e| Error::from(e).with_path(path))
+}
+
+/// Formats without magic number are found by the extension of `path` when nothing was sniffed.
+pub(crate) fn path_format(path: &Path, sniffed: Format) -> Format {
+    match (sniffed, path.extension().and_then(|ext| ext.to_str())) {
+        (Format::No, Some("br")) => Format::Brotli,
+        (format, _) => format,
     }
 }
@@ -0,0 +1,282 @@
+//! Confirm a zlib header by decoding the start of the deflate stream which follows.
+//!
+//! The two bytes of a zlib header are printable for the middle compression levels (`x^`), so
+//! plain text can pass the header checksum. Blocks are checked as inflate does: stored lengths
+//! match their complement, Huffman codes are neither over-subscribed nor incomplete, no
+//! back-reference goes before the start of the stream, and the Adler-32 of a stream ending in the
+//! checked bytes matches.
+
+/// Bytes of a stream decoded by [zlib_start].
+pub(crate) const ZLIB_CHECK_SIZE: usize = 256;
+
+const LITERALS: usize = 286;
+const DISTANCES: usize = 30;
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Check the start of a stream with a zlib header, `data` is the whole stream when it's shorter
+/// than [ZLIB_CHECK_SIZE] and must then hold a final block.
+pub(crate) fn zlib_start(data: &[u8]) -> bool {
+    let complete = data.len() < ZLIB_CHECK_SIZE;
+    let mut bits = Bits {
+        data: data.get(2..).unwrap_or_default(),
+        position: 0,
+    };
+    let mut output = Vec::new();
+
+    // `None` when the data ends first
+    let mut check = || -> Option<bool> {
+        loop {
+            let header = bits.read(3)?;
+            let valid = match header >> 1 {
+                0 => stored_block(&mut bits, &mut output)?,
+                1 => {
+                    let (literals, distances) = fixed_codes();
+                    codes(&mut bits, &mut output, &literals, &distances)?
+                }
+                2 => match dynamic_codes(&mut bits)? {
+                    Some((literals, distances)) => {
+                        codes(&mut bits, &mut output, &literals, &distances)?
+                    }
+                    None => false,
+                },
+                _ => false,
+            };
+
+            if !valid {
+                return Some(false);
+            }
+            if header & 1 == 1 {
+                // the bytes of a truncated checksum must match too, the decoder reports the rest
+                bits.position = bits.position.next_multiple_of(8);
+                let checksum = adler32(&output).to_be_bytes();
+                return Some(
+                    checksum
+                        .iter()
+                        .all(|byte| bits.read(8).map_or(true, |read| read == u32::from(*byte))),
+                );
+            }
+        }
+    };
+
+    check().unwrap_or(!complete)
+}
+
+/// Copy the data of a stored block.
+fn stored_block(bits: &mut Bits, output: &mut Vec<u8>) -> Option<bool> {
+    bits.position = bits.position.next_multiple_of(8);
+    let len = bits.read(16)?;
+    if bits.read(16)? != !len & 0xffff {
+        return Some(false);
+    }
+
+    let start = bits.position / 8;
+    output.extend(bits.data.get(start..start + len as usize)?);
+    bits.position += 8 * len as usize;
+
+    Some(true)
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+
+    // the fixed codes are complete
+    (
+        Huffman::new(&lengths).unwrap_or_default(),
+        Huffman::new(&[5; 30]).unwrap_or_default(),
+    )
+}
+
+/// Read the code lengths of a dynamic block, `Some(None)` if they are invalid.
+fn dynamic_codes(bits: &mut Bits) -> Option<Option<(Huffman, Huffman)>> {
+    let literals = bits.read(5)? as usize + 257;
+    let distances = bits.read(5)? as usize + 1;
+    let code_lengths = bits.read(4)? as usize + 4;
+    if literals > LITERALS || distances > DISTANCES {
+        return Some(None);
+    }
+
+    let mut lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = bits.read(3)? as u8;
+    }
+    let Some(code) = Huffman::new(&lengths).filter(|code| code.complete) else {
+        return Some(None);
+    };
+
+    let mut lengths = [0u8; LITERALS + DISTANCES];
+    let mut index = 0;
+    while index < literals + distances {
+        let (length, repeat) = match code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 if index > 0 => (lengths[index - 1], 3 + bits.read(2)?),
+            17 => (0, 3 + bits.read(3)?),
+            18 => (0, 11 + bits.read(7)?),
+            _ => return Some(None),
+        };
+
+        let end = index + repeat as usize;
+        if end > literals + distances {
+            return Some(None);
+        }
+        lengths[index..end].fill(length);
+        index = end;
+    }
+
+    // an end of block code is needed, an incomplete code is only allowed for a single symbol
+    let literal_code = Huffman::new(&lengths[..literals]).filter(Huffman::usable);
+    let distance_code =
+        Huffman::new(&lengths[literals..literals + distances]).filter(Huffman::usable);
+    match (literal_code, distance_code) {
+        (Some(literal_code), Some(distance_code)) if lengths[256] != 0 => {
+            Some(Some((literal_code, distance_code)))
+        }
+        _ => Some(None),
+    }
+}
+
+/// Decode the symbols of a Huffman block up to its end.
+fn codes(
+    bits: &mut Bits,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Option<bool> {
+    loop {
+        let symbol = u32::from(literals.decode(bits)?);
+        let length = match symbol {
+            0..=255 => {
+                output.push(symbol as u8);
+                continue;
+            }
+            256 => return Some(true),
+            257..=264 => symbol - 254,
+            265..=284 => {
+                let extra = (symbol - 261) / 4;
+                ((4 + (symbol - 265) % 4) << extra) + 3 + bits.read(extra)?
+            }
+            285 => 258,
+            _ => return Some(false),
+        };
+
+        let code = u32::from(distances.decode(bits)?);
+        let distance = match code {
+            0..=3 => code + 1,
+            4..=29 => {
+                let extra = code / 2 - 1;
+                ((2 + code % 2) << extra) + 1 + bits.read(extra)?
+            }
+            _ => return Some(false),
+        } as usize;
+        if distance > output.len() {
+            return Some(false);
+        }
+
+        for _ in 0..length {
+            output.push(output[output.len() - distance]);
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + u32::from(*byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+
+    b << 16 | a
+}
+
+/// A canonical Huffman code, decoded bit by bit.
+#[derive(Default)]
+struct Huffman {
+    // number of codes of each length
+    counts: [u16; 16],
+    // symbols ordered by code
+    symbols: Vec<u16>,
+    complete: bool,
+    max_length: usize,
+}
+
+impl Huffman {
+    /// `None` if the code is over-subscribed.
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0u16; 16];
+        for length in lengths {
+            counts[usize::from(*length)] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left = 1i32;
+        for count in &counts[1..] {
+            left = (left << 1) - i32::from(*count);
+            if left < 0 {
+                return None;
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[usize::from(offsets[usize::from(*length)])] = symbol as u16;
+                offsets[usize::from(*length)] += 1;
+            }
+        }
+
+        Some(Huffman {
+            counts,
+            symbols,
+            complete: left == 0,
+            max_length: counts.iter().rposition(|count| *count != 0).unwrap_or(0),
+        })
+    }
+
+    /// inflate only accepts an incomplete code when it has at most one symbol.
+    fn usable(&self) -> bool {
+        self.complete || self.max_length <= 1
+    }
+
+    /// Codes past the last one decode to `u16::MAX`, which isn't in any alphabet.
+    fn decode(&self, bits: &mut Bits) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for count in &self.counts[1..] {
+            code |= bits.read(1)? as i32;
+            let count = i32::from(*count);
+            if code - first < count {
+                return Some(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Some(u16::MAX)
+    }
+}
+
+/// Bits of a deflate stream, least significant bit first.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    /// Read a `count` bits number, `None` at the end of the data.
+    fn read(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.data.get(self.position / 8)?;
+            value |= u32::from(byte >> (self.position % 8) & 1) << i;
+            self.position += 1;
+        }
+
+        Some(value)
+    }
+}